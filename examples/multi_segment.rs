@@ -0,0 +1,29 @@
+use embedded_time::rate::Extensions;
+use lc::animations::{Animatable, Animation};
+use lc::utility::default_translation_array;
+use lc::{default_animations, LightingController, LogicalStrip};
+use lighting_controller as lc;
+use smart_leds::colors::*;
+
+const SEGMENT_LEN: usize = 48;
+
+fn main() {
+    let frame_rate = 60.Hz();
+    let color_buffer = &mut [BLACK; SEGMENT_LEN * 3];
+    let mut ls = LogicalStrip::new(color_buffer);
+
+    // three logical segments on a single 144 led strip:
+    let a1 = &mut Animation::<SEGMENT_LEN>::new(default_animations::ANI_TEST, frame_rate);
+    let a2 = &mut Animation::<SEGMENT_LEN>::new(default_animations::ANI_TEST, frame_rate)
+        .set_translation_array(default_translation_array(SEGMENT_LEN));
+    let a3 = &mut Animation::<SEGMENT_LEN>::new(default_animations::ANI_TEST, frame_rate)
+        .set_translation_array(default_translation_array(SEGMENT_LEN * 2));
+    let animations: [&mut dyn Animatable; 3] = [a1, a2, a3];
+    let mut lc = LightingController::new(animations, frame_rate);
+
+    loop {
+        //this should have a check to limit updates to match the frame_rate:
+        lc.update(&mut ls);
+        let _frame: &[_] = ls.as_slice();
+    }
+}
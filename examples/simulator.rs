@@ -0,0 +1,90 @@
+use embedded_time::duration::Nanoseconds;
+use embedded_time::rate::Extensions;
+use lc::animations::{background, foreground, Animatable, Animation};
+use lc::{default_animations, simulator, LightingController, LogicalStrip};
+use lighting_controller as lc;
+use smart_leds::colors::*;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::PathBuf;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const LED_COUNT: usize = 48;
+const FRAME_RATE: u32 = 60;
+const SECONDS_PER_MODE: u32 = 4;
+
+// Run with `cargo run --example simulator --features std`, optionally followed by a directory to
+// also save a PNG of each mode into.
+fn main() {
+    let png_directory = std::env::args().nth(1).map(PathBuf::from);
+
+    let background_modes = [
+        ("solid", background::Mode::Solid),
+        ("solid_fade", background::Mode::SolidFade),
+        ("breathe", background::Mode::Breathe),
+        ("fill_rainbow", background::Mode::FillRainbow),
+        ("fill_rainbow_rotate", background::Mode::FillRainbowRotate),
+        ("gradient", background::Mode::Gradient),
+        ("noise", background::Mode::Noise),
+        ("rain", background::Mode::Rain),
+    ];
+    let foreground_modes = [
+        ("marquee_solid", foreground::Mode::MarqueeSolid),
+        ("marquee_solid_fixed", foreground::Mode::MarqueeSolidFixed),
+        ("marquee_fade", foreground::Mode::MarqueeFade),
+        ("marquee_fade_fixed", foreground::Mode::MarqueeFadeFixed),
+        ("vu_meter", foreground::Mode::VUMeter),
+        ("level_meter", foreground::Mode::LevelMeter),
+        ("scanner", foreground::Mode::Scanner),
+        ("theater_chase", foreground::Mode::TheaterChase),
+    ];
+
+    let mut showcase = Vec::new();
+    for (name, mode) in background_modes {
+        let mut parameters = default_animations::ANI_TEST;
+        parameters.bg.mode = mode;
+        parameters.fg.mode = foreground::Mode::NoForeground;
+        showcase.push((name, parameters));
+    }
+    for (name, mode) in foreground_modes {
+        let mut parameters = default_animations::ANI_TEST;
+        parameters.bg.mode = background::Mode::NoBackground;
+        parameters.fg.mode = mode;
+        parameters.fg.trail_length = 6;
+        showcase.push((name, parameters));
+    }
+
+    let frame_rate = FRAME_RATE.Hz();
+    let color_buffer = &mut [BLACK; LED_COUNT];
+    let mut ls = LogicalStrip::new(color_buffer);
+    let a1 = &mut Animation::<LED_COUNT>::new(default_animations::ANI_TEST, frame_rate);
+    let animations: [&mut dyn Animatable; 1] = [a1];
+    let mut lc = LightingController::new(animations, frame_rate);
+
+    let frame_time = Duration::from_nanos(1_000_000_000 / FRAME_RATE as u64);
+    let frames_per_mode = FRAME_RATE * SECONDS_PER_MODE;
+    let mut stdout = std::io::stdout();
+    for (name, parameters) in &showcase {
+        lc.set_parameters(0, parameters);
+        for frame in 0..frames_per_mode {
+            let started = Instant::now();
+            // sweep the input level up and down for the meter modes:
+            let sweep = frame * 2 * u8::MAX as u32 / FRAME_RATE % (2 * u8::MAX as u32);
+            lc.set_input_level(0, sweep.min(2 * u8::MAX as u32 - sweep) as u8);
+            lc.update_with_elapsed(Nanoseconds(frame_time.as_nanos() as u64), &mut ls);
+
+            println!("{}{}", simulator::CLEAR_SCREEN, name);
+            simulator::write_ansi(&mut stdout, ls.as_slice(), None).unwrap();
+
+            if let Some(directory) = &png_directory {
+                if frame == frames_per_mode / 2 {
+                    let file = File::create(directory.join(format!("{}.png", name))).unwrap();
+                    simulator::write_png(&mut BufWriter::new(file), ls.as_slice(), None, 8)
+                        .unwrap();
+                }
+            }
+            thread::sleep(frame_time.saturating_sub(started.elapsed()));
+        }
+    }
+}
@@ -0,0 +1,37 @@
+//! Helpers for sending frames to any LED driver that implements the `SmartLedsWrite` trait from the
+//! [smart-leds](https://github.com/smart-leds-rs/smart-leds) crate. Translation arrays, brightness
+//! and all other output corrections have already been applied to the LogicalStrip by the
+//! LightingController, so the strip's colors can be written to the driver as they are.
+
+use crate::output::OutputColor;
+use crate::{LightingController, LogicalStrip};
+use smart_leds::SmartLedsWrite;
+
+/// Writes the colors of every LED on the strip to the driver, in physical order.
+pub fn write<D, C>(driver: &mut D, logical_strip: &LogicalStrip<C>) -> Result<(), D::Error>
+where
+    D: SmartLedsWrite,
+    C: OutputColor + Into<D::Color>,
+{
+    driver.write(logical_strip.iter().copied())
+}
+
+impl<'a, const N_ANI: usize> LightingController<'a, N_ANI> {
+    /// Updates all the animations and writes the resulting frame to the driver in one call. The
+    /// frame is only written when it changed, and the result says whether it was.
+    pub fn update_and_write<D, C>(
+        &mut self,
+        logical_strip: &mut LogicalStrip<C>,
+        driver: &mut D,
+    ) -> Result<bool, D::Error>
+    where
+        D: SmartLedsWrite,
+        C: OutputColor + Into<D::Color>,
+    {
+        if !self.update(logical_strip) {
+            return Ok(false);
+        }
+        write(driver, logical_strip)?;
+        Ok(true)
+    }
+}
@@ -1,13 +1,43 @@
 pub mod background;
 pub mod foreground;
+pub mod transition;
 pub mod trigger;
 
-use crate::utility::default_translation_array;
+use crate::colors::{self, ManipulatableColor};
+#[cfg(feature = "instrumentation")]
+use crate::instrumentation::{CycleCounter, LayerCosts, Stopwatch};
+use crate::mapping::Matrix;
+#[cfg(feature = "color16")]
+use crate::output;
+use crate::state::AnimationState;
+use crate::utility::{convert_ms_to_frames, default_translation_array, Speed};
+use arrayvec::ArrayVec;
+use core::marker::PhantomData;
+use embedded_time::duration::Nanoseconds;
+use embedded_time::fixed_point::FixedPoint;
 use embedded_time::rate::Hertz;
+#[cfg(feature = "color16")]
+use rgb::RGB16;
 use rgb::RGB8;
 
-/// Adjust MAX_NUM_* consts depending on RAM requirements:
-pub(crate) const MAX_NUM_ACTIVE_TRIGGERS: usize = 10;
+/// This is the number of triggers an animation can run at once unless it sets its own
+/// `N_TRIGGERS`. Each slot costs RAM whether or not it is in use, so small devices can use fewer.
+pub const DEFAULT_NUM_TRIGGERS: usize = 10;
+
+/// This is the most child animations that can be nested inside a single animation.
+pub const MAX_CHILD_ANIMATIONS: usize = 4;
+
+/// This is the most events an animation holds on to until they are polled. Events after this are
+/// dropped until the queue is polled again.
+pub const MAX_PENDING_EVENTS: usize = 8;
+
+/// This is the most keys of an animation that can have their own color at once. Colors for more
+/// keys than this are dropped until others are cleared.
+pub const MAX_KEY_COLORS: usize = 32;
+
+/// This is the most frames an animation will catch up on in one call to `update_with_elapsed()`.
+/// Any more elapsed time than this is dropped, so a long stall doesn't lock up the main loop.
+pub const MAX_CATCH_UP_FRAMES: u64 = 16;
 
 /// This is the maximum offset value for rotating animations. It's basically the supersampled
 /// resolution of the animation over the entire translation_array of leds.
@@ -15,25 +45,187 @@ pub const MAX_OFFSET: u16 = u16::MAX;
 
 /// Denotes the direction of animations, effects vary depending on animation modes:
 #[derive(Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum Direction {
     Positive,
     Stopped,
     Negative,
 }
 
+impl Direction {
+    /// Returns whether changing from this direction to another turns the animation around.
+    pub fn is_reversed_by(&self, other: Direction) -> bool {
+        matches!(
+            (self, other),
+            (Direction::Positive, Direction::Negative) | (Direction::Negative, Direction::Positive)
+        )
+    }
+}
+
+/// How many times a background or foreground runs through its cycle, where each cycle lasts the
+/// layer's duration. Layers that stop after a few cycles can be used as transitions between other
+/// effects, and `Animatable::is_run_complete()` tells when they are done.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum RunMode {
+    /// Repeats the cycle forever.
+    Loop,
+    /// Runs the cycle this many times, then holds the last frame.
+    Cycles(u32),
+    /// Runs the cycle once, then holds the last frame.
+    OneShotHold,
+    /// Runs the cycle once, then stops drawing the layer. A cleared background is black.
+    OneShotClear,
+}
+
+impl RunMode {
+    /// Returns whether a layer that has completed `cycles` cycles has finished its run.
+    pub fn is_complete(&self, cycles: u32) -> bool {
+        match self {
+            RunMode::Loop => false,
+            RunMode::Cycles(count) => cycles >= *count,
+            RunMode::OneShotHold | RunMode::OneShotClear => cycles >= 1,
+        }
+    }
+}
+
+/// An effect written outside of this crate, drawn by the `Custom` mode of a background or a
+/// foreground. The layer drawing the effect is handed to it, so the effect can follow the layer's
+/// offset, frames, rainbow and triggers the same way the built in modes do, and whatever it draws
+/// is composited with the other layers as usual. An effect only needs to implement the layers it
+/// is used on; the other method leaves the segment as it is.
+///
+/// Modes are copied along with their parameters, so the effect is borrowed immutably. Parameters
+/// can be passed between an interrupt and a task through a `CommandQueue`, so the effect must be
+/// `Sync`, and an effect that keeps state of its own from frame to frame can hold it in atomics.
+pub trait CustomAnimation: Sync {
+    /// Draws the next frame of a background into the segment.
+    fn update_background(&self, _bg: &mut background::Background, _segment: &mut [RGB8]) {}
+
+    /// Draws the next frame of a foreground into the segment.
+    fn update_foreground(&self, _fg: &mut foreground::Foreground, _segment: &mut [RGB8]) {}
+}
+
+/// Watches the layers of an animation as each frame is drawn, such as to show them one at a time
+/// in a debugging tool, or to composite them some other way than the animation does. See
+/// `LightingController::set_layer_observer()`.
+pub trait LayerObserver {
+    /// Called as each layer of a frame is finished, before it is composited over the layers
+    /// beneath it. The pixels are in the order of the segment and don't have the layer's
+    /// brightness applied yet; it is passed along with the alpha and blend mode the layer is
+    /// composited with.
+    ///
+    /// The background covers every LED. The foreground, when its blend mode is `Normal`, and the
+    /// triggers are drawn over a copy of the layers beneath them, so the LEDs they leave alone
+    /// show those layers. A foreground with any other blend mode is drawn over the identity color
+    /// of its blend mode.
+    fn layer_drawn(
+        &mut self,
+        layer: AnimationType,
+        pixels: &[RGB8],
+        alpha: u8,
+        brightness: u8,
+        blend_mode: colors::BlendMode,
+    );
+}
+
 /// Denotes the main types of animations, e.g. Foreground, Background, or Trigger:
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum AnimationType {
     Background,
     Foreground,
     Trigger,
 }
 
+/// The problems that `AnimationParameters::validate()` finds in a set of parameters. Each of these
+/// would otherwise be drawn as an animation that is frozen or garbled, without any other sign that
+/// something is wrong.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ParameterError {
+    /// The layer's mode draws with the colors of its rainbow, but the rainbow is empty.
+    EmptyRainbow(AnimationType),
+    /// One of the layer's durations is shorter than a frame at the frame rate, so it rounds down
+    /// to no frames and whatever it times never moves.
+    DurationUnderOneFrame(AnimationType),
+    /// The layer's pattern is repeated more times than there are LEDs to draw it on.
+    TooManySubdivisions(AnimationType),
+}
+
+/// Milestones reached while an animation runs, so that firmware can keep other behavior in step
+/// with the animation, such as playing a sound or chaining another trigger. Events are queued as
+/// the animation updates, and read back with `Animatable::poll_event()`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Event {
+    /// The background moved on to another color of its rainbow.
+    BackgroundColorAdvanced,
+
+    /// The foreground completed a cycle of its frames.
+    ForegroundCycleComplete,
+
+    /// A running trigger with this mode finished.
+    TriggerFinished(trigger::Mode),
+}
+
+/// Denotes how an animation is copied along a strip that is longer than the animation, so that
+/// it only has to be calculated once. Each copy starts N_LED indices after the previous one.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Tiling {
+    /// The animation is drawn once.
+    Single,
+
+    /// The animation is drawn this many times in a row, with every copy running the same way.
+    Repeat(usize),
+
+    /// The animation is drawn this many times in a row, with every other copy reversed. The first
+    /// copy is reversed, so LED 0 of the animation is in the middle of the first two copies, and
+    /// effects moving in the positive direction radiate out from the center.
+    Mirror(usize),
+}
+
+impl Tiling {
+    /// Returns the number of copies of the animation that are drawn.
+    pub fn copies(&self) -> usize {
+        match *self {
+            Tiling::Single => 1,
+            Tiling::Repeat(copies) | Tiling::Mirror(copies) => copies,
+        }
+    }
+
+    /// Returns the index on the LogicalStrip of an LED of the animation within one of its copies.
+    pub fn physical_index(&self, translation_array: &[usize], index: usize, copy: usize) -> usize {
+        let segment_length = translation_array.len();
+        let index = match self {
+            Tiling::Mirror(_) if copy.is_multiple_of(2) => segment_length - 1 - index,
+            _ => index,
+        };
+        translation_array[index] + copy * segment_length
+    }
+}
+
+/// An animation nested inside another one, drawn over the LEDs of its parent's segment starting
+/// at `start`.
+struct ChildAnimation<'a> {
+    animation: &'a mut dyn Animatable<'a>,
+    start: usize,
+    z_order: u8,
+}
+
 /// This holds the parameters that define everything needed to set up an animation. It's a struct
 /// holding the parameters for the foreground animation, the background animation, and the global
 /// information for trigger animations (such as the trigger Rainbow)
+#[derive(Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct AnimationParameters<'a> {
     pub bg: background::Parameters<'a>,
+    #[cfg_attr(feature = "serde", serde(borrow))]
     pub fg: foreground::Parameters<'a>,
     pub trigger: trigger::GlobalParameters<'a>,
 }
@@ -41,29 +233,163 @@ pub struct AnimationParameters<'a> {
 /// This struct contains all the fixed parameters of an animation, as well as the state of the
 /// foreground, background, and active trigger animations. It is updated by the LightingController
 /// that it is attached to at the LightingController's frame rate based on the parameters provided.
-/// To make a new animation,
-pub struct Animation<'a, const N_LED: usize> {
+/// To make a new animation, give it the number of LEDs in its segment, and optionally the number
+/// of triggers it can run at once, such as `Animation::<60, 4>::new()`.
+pub struct Animation<'a, const N_LED: usize, const N_TRIGGERS: usize = DEFAULT_NUM_TRIGGERS> {
     translation_array: [usize; N_LED],
     segment: [RGB8; N_LED],
+    #[cfg(feature = "color16")]
+    segment16: [RGB16; N_LED],
+    bg_frame: [RGB8; N_LED],
     fg_state: foreground::Foreground<'a>,
     bg_state: background::Background<'a>,
-    triggers: trigger::TriggerCollection<'a, MAX_NUM_ACTIVE_TRIGGERS>,
+    triggers: trigger::TriggerCollection<'a, N_TRIGGERS>,
+    frame_rate: Hertz,
+    brightness: u8,
+    mask: [u8; N_LED],
+    // the LED index and color of each key with its own color:
+    key_colors: ArrayVec<(usize, RGB8), MAX_KEY_COLORS>,
+    keymap: Option<&'a [usize]>,
+    persistence: u8,
+    afterglow: [RGB8; N_LED],
+    transition: Option<transition::Transition<'a>>,
+    transition_style: transition::Style,
+    tiling: Tiling,
+    // kept sorted by z_order, with the empty slots at the end:
+    children: [Option<ChildAnimation<'a>>; MAX_CHILD_ANIMATIONS],
+    events: ArrayVec<Event, MAX_PENDING_EVENTS>,
+    elapsed_ns: u64,
+    is_paused: bool,
+    layer_observer: Option<&'a mut dyn LayerObserver>,
+    #[cfg(feature = "instrumentation")]
+    cycle_counter: Option<&'a dyn CycleCounter>,
+    #[cfg(feature = "instrumentation")]
+    layer_costs: LayerCosts,
 }
 
+/// The update methods return whether any LED of the segment changed, so static animations can be
+/// detected and the LEDs left alone.
 pub trait Animatable<'a> {
-    fn update(&mut self);
+    fn update(&mut self) -> bool;
+    fn update_with_elapsed(&mut self, elapsed: Nanoseconds<u64>) -> bool;
+    fn pause(&mut self);
+    fn resume(&mut self);
+    fn is_paused(&self) -> bool;
+    fn step_one_frame(&mut self) -> bool;
+    fn set_frame_rate(&mut self, frame_rate: Hertz);
     fn set_offset(&mut self, a_type: AnimationType, offset: u16);
+    fn set_direction(&mut self, a_type: AnimationType, direction: Direction);
+    fn set_brightness(&mut self, brightness: u8);
+    fn set_persistence(&mut self, persistence: u8);
+    fn set_input_level(&mut self, level: u8);
+    fn set_progress(&mut self, progress: u16);
+    fn set_layer_brightness(&mut self, a_type: AnimationType, brightness: u8);
+    fn set_speed(&mut self, a_type: AnimationType, numerator: u32, denominator: u32);
+    fn restart_cycle(&mut self, a_type: AnimationType);
+    fn restart_run(&mut self, a_type: AnimationType);
+    fn is_run_complete(&self, a_type: AnimationType) -> bool;
+    fn set_mask(&mut self, mask: &[u8]);
+    fn set_mask_level(&mut self, index: usize, level: u8);
+    fn clear_mask(&mut self);
+    fn set_key_color(&mut self, key: usize, color: RGB8);
+    fn set_key_colors(&mut self, keys: &[(usize, RGB8)]);
+    fn clear_key_color(&mut self, key: usize);
+    fn clear_key_colors(&mut self);
     fn trigger(&mut self, params: &trigger::Parameters, frame_rate: Hertz);
+    fn try_trigger(
+        &mut self,
+        params: &trigger::Parameters,
+        frame_rate: Hertz,
+    ) -> Result<(), trigger::TriggerError>;
+    fn trigger_sequence(&mut self, steps: &[trigger::SequenceStep], frame_rate: Hertz);
+    fn clear_triggers(&mut self);
+    fn cancel_triggers_of_mode(&mut self, mode: trigger::Mode);
+    fn active_trigger_count(&self) -> usize;
     fn segment(&self) -> &[RGB8];
+    /// Returns the segment with 16 bits per channel. Animations that only draw in 8 bits can
+    /// leave it empty, and the controller widens the colors of `segment()` instead.
+    #[cfg(feature = "color16")]
+    fn segment16(&self) -> &[RGB16] {
+        &[]
+    }
     fn translation_array(&self) -> &[usize];
+    fn tiling(&self) -> Tiling;
+    fn set_parameters(&mut self, parameters: &AnimationParameters<'a>);
+    fn update_bg_parameters(&mut self, parameters: &background::Parameters<'a>);
+    fn update_fg_parameters(&mut self, parameters: &foreground::Parameters<'a>);
+    fn update_trigger_parameters(&mut self, parameters: &trigger::GlobalParameters<'a>);
+    fn transition_to(&mut self, parameters: &AnimationParameters<'a>, duration_ms: u64);
+    fn set_transition_style(&mut self, style: transition::Style);
+    fn poll_event(&mut self) -> Option<Event>;
+    fn state(&self) -> AnimationState;
+    fn restore_state(&mut self, state: &AnimationState);
+    fn set_layer_observer(&mut self, observer: Option<&'a mut dyn LayerObserver>);
+    /// Times the layers of the animation with a cycle counter. Animations that don't time their
+    /// layers can ignore it, and only report zero costs.
+    #[cfg(feature = "instrumentation")]
+    fn set_cycle_counter(&mut self, _counter: Option<&'a dyn CycleCounter>) {}
+    /// Returns the layer costs added up since the last call, and starts over from zero.
+    #[cfg(feature = "instrumentation")]
+    fn take_layer_costs(&mut self) -> LayerCosts {
+        LayerCosts::default()
+    }
 }
 
-impl<'a, const N_LED: usize> Animatable<'a> for Animation<'a, N_LED> {
-    fn update(&mut self) {
-        // Update all three states
-        self.bg_state.update(&mut self.segment);
-        self.fg_state.update(&mut self.segment);
-        self.triggers.update(&mut self.segment);
+impl<'a, const N_LED: usize, const N_TRIGGERS: usize> Animatable<'a>
+    for Animation<'a, N_LED, N_TRIGGERS>
+{
+    fn update(&mut self) -> bool {
+        !self.is_paused && self.render_frame()
+    }
+
+    fn update_with_elapsed(&mut self, elapsed: Nanoseconds<u64>) -> bool {
+        if self.is_paused {
+            return false;
+        }
+        // Run one update for every whole frame of time that has passed, keeping the remainder for
+        // next time so that the animation speed doesn't drift when the main loop timing jitters:
+        let frame_ns = 1_000_000_000 / self.frame_rate.integer().max(1) as u64;
+        self.elapsed_ns = (self.elapsed_ns + elapsed.integer()).min(frame_ns * MAX_CATCH_UP_FRAMES);
+        let mut has_changed = false;
+        while self.elapsed_ns >= frame_ns {
+            has_changed |= self.render_frame();
+            self.elapsed_ns -= frame_ns;
+        }
+        has_changed
+    }
+
+    /// Freezes the animation on its current frame. Updates leave the segment unchanged until the
+    /// animation is resumed, and the time spent paused is not caught up afterwards.
+    fn pause(&mut self) {
+        self.is_paused = true;
+    }
+
+    fn resume(&mut self) {
+        self.is_paused = false;
+    }
+
+    fn is_paused(&self) -> bool {
+        self.is_paused
+    }
+
+    /// Advances the animation by a single frame, even while it is paused.
+    fn step_one_frame(&mut self) -> bool {
+        self.render_frame()
+    }
+
+    fn set_frame_rate(&mut self, frame_rate: Hertz) {
+        // Rescale everything that is in progress so the animation keeps going at the same speed:
+        let old_rate = self.frame_rate;
+        self.bg_state.rescale_frame_rate(old_rate, frame_rate);
+        self.fg_state.rescale_frame_rate(old_rate, frame_rate);
+        self.triggers.rescale_frame_rate(old_rate, frame_rate);
+        if let Some(transition) = &mut self.transition {
+            transition.rescale_frame_rate(old_rate, frame_rate);
+        }
+        for child in self.children.iter_mut().flatten() {
+            child.animation.set_frame_rate(frame_rate);
+        }
+        self.frame_rate = frame_rate;
     }
 
     fn set_offset(&mut self, a_type: AnimationType, offset: u16) {
@@ -80,7 +406,141 @@ impl<'a, const N_LED: usize> Animatable<'a> for Animation<'a, N_LED> {
         }
     }
 
+    /// Changes the direction of the background or foreground while it runs. Modes that move
+    /// turn around from where they are instead of restarting.
+    fn set_direction(&mut self, a_type: AnimationType, direction: Direction) {
+        match a_type {
+            AnimationType::Background => self.bg_state.set_direction(direction),
+            AnimationType::Foreground => self.fg_state.set_direction(direction),
+            AnimationType::Trigger => {
+                // Each trigger takes its direction from its own parameters, so do nothing.
+            }
+        }
+    }
+
+    fn set_brightness(&mut self, brightness: u8) {
+        self.brightness = brightness;
+    }
+
+    /// Sets how much of its brightness each LED keeps from one frame to the next, like the glow
+    /// of a phosphor screen. LEDs that go darker fade out instead of turning off straight away,
+    /// so anything that moves leaves a trail behind it. 0 turns the persistence off.
+    fn set_persistence(&mut self, persistence: u8) {
+        self.persistence = persistence;
+    }
+
+    fn set_input_level(&mut self, level: u8) {
+        self.fg_state.input_level = level;
+    }
+
+    fn set_progress(&mut self, progress: u16) {
+        self.fg_state.set_progress(progress);
+    }
+
+    fn set_mask(&mut self, mask: &[u8]) {
+        for (level, &new_level) in self.mask.iter_mut().zip(mask.iter()) {
+            *level = new_level;
+        }
+    }
+
+    fn set_mask_level(&mut self, index: usize, level: u8) {
+        if let Some(mask_level) = self.mask.get_mut(index) {
+            *mask_level = level;
+        }
+    }
+
+    fn clear_mask(&mut self) {
+        self.mask = [colors::FULL_BRIGHTNESS; N_LED];
+    }
+
+    fn set_key_color(&mut self, key: usize, color: RGB8) {
+        let Some(led) = self.key_led(key) else {
+            return;
+        };
+        match self.key_colors.iter_mut().find(|(index, _)| *index == led) {
+            Some((_, key_color)) => *key_color = color,
+            None => {
+                let _ = self.key_colors.try_push((led, color));
+            }
+        }
+    }
+
+    fn set_key_colors(&mut self, keys: &[(usize, RGB8)]) {
+        for &(key, color) in keys {
+            self.set_key_color(key, color);
+        }
+    }
+
+    fn clear_key_color(&mut self, key: usize) {
+        if let Some(led) = self.key_led(key) {
+            self.key_colors.retain(|(index, _)| *index != led);
+        }
+    }
+
+    fn clear_key_colors(&mut self) {
+        self.key_colors.clear();
+    }
+
+    fn set_layer_brightness(&mut self, a_type: AnimationType, brightness: u8) {
+        match a_type {
+            AnimationType::Background => self.bg_state.brightness = brightness,
+            AnimationType::Foreground => self.fg_state.brightness = brightness,
+            AnimationType::Trigger => self.triggers.brightness = brightness,
+        }
+    }
+
+    /// Runs a layer at `numerator / denominator` times the speed set by its parameters, such as
+    /// 1/2 for half speed. Everything in progress on the layer is rescaled so it carries on from
+    /// the same point, and the speed stays in place when the layer's parameters are changed.
+    fn set_speed(&mut self, a_type: AnimationType, numerator: u32, denominator: u32) {
+        let speed = Speed::new(numerator, denominator);
+        match a_type {
+            AnimationType::Background => self.bg_state.set_speed(speed),
+            AnimationType::Foreground => self.fg_state.set_speed(speed),
+            AnimationType::Trigger => self.triggers.set_speed(speed),
+        }
+    }
+
+    /// Starts the current cycle of a layer over from its first frame, such as to line it up with
+    /// a beat.
+    fn restart_cycle(&mut self, a_type: AnimationType) {
+        match a_type {
+            AnimationType::Background => self.bg_state.frames.reset(),
+            AnimationType::Foreground => self.fg_state.frames.reset(),
+            AnimationType::Trigger => self.triggers.frames.reset(),
+        }
+    }
+
+    /// Starts a layer's run over from its first cycle, such as to play a one-shot layer again.
+    fn restart_run(&mut self, a_type: AnimationType) {
+        match a_type {
+            AnimationType::Background => self.bg_state.restart_run(),
+            AnimationType::Foreground => self.fg_state.restart_run(),
+            AnimationType::Trigger => self.triggers.frames.reset(),
+        }
+    }
+
+    /// Returns whether a layer has finished the cycles its run mode asks for. The trigger layer
+    /// has no run mode, so it is complete when none of its triggers are running.
+    fn is_run_complete(&self, a_type: AnimationType) -> bool {
+        match a_type {
+            AnimationType::Background => self.bg_state.is_complete(),
+            AnimationType::Foreground => self.fg_state.is_complete(),
+            AnimationType::Trigger => self.triggers.active_trigger_count() == 0,
+        }
+    }
+
     fn trigger(&mut self, params: &trigger::Parameters, frame_rate: Hertz) {
+        let _ = self.try_trigger(params, frame_rate);
+    }
+
+    /// The same as `trigger()`, but returns an error when the trigger was dropped because every
+    /// trigger slot was busy.
+    fn try_trigger(
+        &mut self,
+        params: &trigger::Parameters,
+        frame_rate: Hertz,
+    ) -> Result<(), trigger::TriggerError> {
         match params.mode {
             trigger::Mode::NoTrigger => {}
             trigger::Mode::Background => {
@@ -89,8 +549,27 @@ impl<'a, const N_LED: usize> Animatable<'a> for Animation<'a, N_LED> {
             trigger::Mode::Foreground => {
                 self.fg_state.has_been_triggered = true;
             }
-            _ => self.triggers.add_trigger(params, frame_rate),
+            _ => return self.triggers.add_trigger(params, frame_rate),
         }
+        Ok(())
+    }
+
+    fn trigger_sequence(&mut self, steps: &[trigger::SequenceStep], frame_rate: Hertz) {
+        self.triggers.add_sequence(steps, frame_rate);
+    }
+
+    /// Stops every running trigger and pending sequence step. Triggers of the Background and
+    /// Foreground modes only start a change in those layers, so they have nothing to stop.
+    fn clear_triggers(&mut self) {
+        self.triggers.clear_triggers();
+    }
+
+    fn cancel_triggers_of_mode(&mut self, mode: trigger::Mode) {
+        self.triggers.cancel_triggers_of_mode(mode);
+    }
+
+    fn active_trigger_count(&self) -> usize {
+        self.triggers.active_trigger_count()
     }
 
     fn segment(&self) -> &[RGB8] {
@@ -100,9 +579,251 @@ impl<'a, const N_LED: usize> Animatable<'a> for Animation<'a, N_LED> {
     fn translation_array(&self) -> &[usize] {
         &self.translation_array[..]
     }
+
+    fn tiling(&self) -> Tiling {
+        self.tiling
+    }
+
+    fn set_parameters(&mut self, parameters: &AnimationParameters<'a>) {
+        self.update_bg_parameters(&parameters.bg);
+        self.update_fg_parameters(&parameters.fg);
+        self.update_trigger_parameters(&parameters.trigger);
+    }
+
+    fn update_bg_parameters(&mut self, parameters: &background::Parameters<'a>) {
+        self.bg_state.set_parameters(parameters, self.frame_rate);
+    }
+
+    fn update_fg_parameters(&mut self, parameters: &foreground::Parameters<'a>) {
+        self.fg_state.set_parameters(parameters, self.frame_rate);
+    }
+
+    fn update_trigger_parameters(&mut self, parameters: &trigger::GlobalParameters<'a>) {
+        self.triggers.set_parameters(parameters, self.frame_rate);
+    }
+
+    /// Fades to new parameters over `duration_ms`, in the style set by
+    /// `set_transition_style()`. A duration shorter than a frame switches straight away. When a
+    /// transition is already running, the new one starts from whichever of its layers make up
+    /// most of what is shown, so the LEDs don't jump back to the older animation.
+    fn transition_to(&mut self, parameters: &AnimationParameters<'a>, duration_ms: u64) {
+        if convert_ms_to_frames(duration_ms, self.frame_rate) == 0 {
+            self.transition = None;
+            self.set_parameters(parameters);
+            return;
+        }
+        let mut bg_state = background::Background::new(&parameters.bg, self.frame_rate);
+        let mut fg_state = foreground::Foreground::new(&parameters.fg, self.frame_rate);
+        bg_state.carry_over_state(&self.bg_state);
+        fg_state.carry_over_state(&self.fg_state);
+
+        let mut outgoing_bg = core::mem::replace(&mut self.bg_state, bg_state);
+        // the outgoing background has no frame of its own to hold, so it is drawn every frame:
+        outgoing_bg.set_update_divisor(1);
+        let outgoing_fg = core::mem::replace(&mut self.fg_state, fg_state);
+        let (outgoing_bg, outgoing_fg) = match self.transition.take() {
+            Some(running) if !running.is_past_halfway() => (running.bg_state, running.fg_state),
+            _ => (outgoing_bg, outgoing_fg),
+        };
+        self.transition = Some(transition::Transition::new(
+            outgoing_bg,
+            outgoing_fg,
+            self.transition_style,
+            duration_ms,
+            self.frame_rate,
+        ));
+
+        self.update_trigger_parameters(&parameters.trigger);
+    }
+
+    fn set_transition_style(&mut self, style: transition::Style) {
+        self.transition_style = style;
+    }
+
+    /// Returns the oldest event that hasn't been polled yet. Events from child animations are
+    /// passed on through their parent.
+    fn poll_event(&mut self) -> Option<Event> {
+        match self.events.is_empty() {
+            true => None,
+            false => Some(self.events.remove(0)),
+        }
+    }
+
+    /// Returns the state of the animation that is set from outside, for saving it.
+    fn state(&self) -> AnimationState {
+        let position = |index: usize| index.min(u16::MAX as usize) as u16;
+        AnimationState {
+            brightness: self.brightness,
+            bg_brightness: self.bg_state.brightness,
+            fg_brightness: self.fg_state.brightness,
+            bg_offset: self.bg_state.offset,
+            fg_offset: self.fg_state.offset,
+            bg_rainbow_position: position(self.bg_state.rainbow.position.get_current()),
+            fg_rainbow_position: position(self.fg_state.rainbow.position.get_current()),
+        }
+    }
+
+    /// Puts back a saved state. Rainbow positions past the end of the current rainbows wrap
+    /// around, so the parameters the state was saved with should be set first.
+    fn restore_state(&mut self, state: &AnimationState) {
+        self.brightness = state.brightness;
+        self.bg_state.brightness = state.bg_brightness;
+        self.fg_state.brightness = state.fg_brightness;
+        self.bg_state.offset = state.bg_offset;
+        self.fg_state.offset = state.fg_offset;
+        self.bg_state
+            .rainbow
+            .set_position(state.bg_rainbow_position as usize);
+        self.fg_state
+            .rainbow
+            .set_position(state.fg_rainbow_position as usize);
+    }
+
+    /// Shows each layer of every frame to the observer from now on, or stops with None.
+    fn set_layer_observer(&mut self, observer: Option<&'a mut dyn LayerObserver>) {
+        self.layer_observer = observer;
+    }
+
+    /// Starts timing each layer of the animation with the counter, or stops timing with None.
+    #[cfg(feature = "instrumentation")]
+    fn set_cycle_counter(&mut self, counter: Option<&'a dyn CycleCounter>) {
+        self.cycle_counter = counter;
+        self.layer_costs = LayerCosts::default();
+    }
+
+    /// Returns the costs of the layers for the frames drawn since this was last called.
+    #[cfg(feature = "instrumentation")]
+    fn take_layer_costs(&mut self) -> LayerCosts {
+        core::mem::take(&mut self.layer_costs)
+    }
+
+    /// Returns the segment with 16 bits per channel. LEDs showing a fading background keep the
+    /// precision of the fade, and the rest are the 8 bit colors of `segment()` widened.
+    #[cfg(feature = "color16")]
+    fn segment16(&self) -> &[RGB16] {
+        &self.segment16[..]
+    }
+}
+
+/// Adds up how long each layer of an animation takes to draw with the `instrumentation` feature,
+/// and does nothing without it.
+struct LayerTimer<'c> {
+    #[cfg(feature = "instrumentation")]
+    stopwatch: Option<Stopwatch<'c>>,
+    #[cfg(feature = "instrumentation")]
+    costs: LayerCosts,
+    counter: PhantomData<&'c ()>,
+}
+
+impl<'c> LayerTimer<'c> {
+    #[cfg(feature = "instrumentation")]
+    fn start(counter: Option<&'c dyn CycleCounter>) -> Self {
+        LayerTimer {
+            stopwatch: counter.map(Stopwatch::start),
+            costs: LayerCosts::default(),
+            counter: PhantomData,
+        }
+    }
+
+    #[cfg(not(feature = "instrumentation"))]
+    fn start() -> Self {
+        LayerTimer {
+            counter: PhantomData,
+        }
+    }
+
+    /// Adds the time since the last lap onto the cost of a layer.
+    fn lap(&mut self, _layer: AnimationType) {
+        #[cfg(feature = "instrumentation")]
+        if let Some(stopwatch) = &mut self.stopwatch {
+            let cost = match _layer {
+                AnimationType::Background => &mut self.costs.background,
+                AnimationType::Foreground => &mut self.costs.foreground,
+                AnimationType::Trigger => &mut self.costs.triggers,
+            };
+            *cost = cost.saturating_add(stopwatch.lap());
+        }
+    }
+}
+
+/// Renders the background and then composites the foreground over it, returning the events
+/// reached by either layer. The background is drawn into `bg_frame`, which holds it between the
+/// frames it is due to be drawn on. Each layer is shown to the observer before it is composited.
+fn render_base_layers<const N_LED: usize>(
+    bg_state: &mut background::Background,
+    fg_state: &mut foreground::Foreground,
+    bg_frame: &mut [RGB8; N_LED],
+    segment: &mut [RGB8; N_LED],
+    timer: &mut LayerTimer,
+    mut observer: Option<&mut (dyn LayerObserver + '_)>,
+) -> [Option<Event>; 2] {
+    let bg_event = (bg_state.is_update_due() && bg_state.update(bg_frame))
+        .then_some(Event::BackgroundColorAdvanced);
+    if let Some(observer) = observer.as_deref_mut() {
+        let (alpha, normal) = (colors::OPAQUE, colors::BlendMode::Normal);
+        let brightness = bg_state.brightness;
+        observer.layer_drawn(
+            AnimationType::Background,
+            bg_frame,
+            alpha,
+            brightness,
+            normal,
+        );
+    }
+    *segment = *bg_frame;
+    colors::scale_brightness(segment, bg_state.brightness);
+    timer.lap(AnimationType::Background);
+
+    let blend_mode = fg_state.blend_mode;
+    let mut layer = match blend_mode {
+        colors::BlendMode::Normal => *segment,
+        _ => [blend_mode.identity(); N_LED],
+    };
+    let fg_event = fg_state
+        .update(&mut layer)
+        .then_some(Event::ForegroundCycleComplete);
+    if let Some(observer) = observer {
+        observer.layer_drawn(
+            AnimationType::Foreground,
+            &layer,
+            fg_state.alpha,
+            fg_state.brightness,
+            blend_mode,
+        );
+    }
+    colors::composite(
+        segment,
+        &layer,
+        fg_state.alpha,
+        fg_state.brightness,
+        blend_mode,
+    );
+    timer.lap(AnimationType::Foreground);
+    [bg_event, fg_event]
 }
 
-impl<'a, const N_LED: usize> Animation<'a, N_LED> {
+impl AnimationParameters<'_> {
+    /// Checks every layer of the parameters for settings that can't work on an animation of
+    /// `led_count` LEDs at the frame rate, and returns the first problem found. Durations of 0
+    /// are allowed, since they are how still modes are set up.
+    pub fn validate(&self, frame_rate: Hertz, led_count: usize) -> Result<(), ParameterError> {
+        self.bg.validate(led_count)?;
+        self.fg.validate(frame_rate, led_count)?;
+        self.trigger.validate(frame_rate)
+    }
+}
+
+impl<'a, const N_LED: usize, const N_TRIGGERS: usize> Animation<'a, N_LED, N_TRIGGERS> {
+    /// Makes an animation the same as `new()`, but only once the parameters pass
+    /// `AnimationParameters::validate()` for its LEDs and frame rate.
+    pub fn try_new(
+        parameters: AnimationParameters<'a>,
+        frame_rate: Hertz,
+    ) -> Result<Self, ParameterError> {
+        parameters.validate(frame_rate, N_LED)?;
+        Ok(Self::new(parameters, frame_rate))
+    }
+
     pub fn new(parameters: AnimationParameters<'a>, frame_rate: Hertz) -> Self {
         let translation_array = default_translation_array(0);
         let segment = [RGB8::default(); N_LED];
@@ -113,9 +834,234 @@ impl<'a, const N_LED: usize> Animation<'a, N_LED> {
         Animation {
             translation_array,
             segment,
+            #[cfg(feature = "color16")]
+            segment16: [RGB16::default(); N_LED],
+            bg_frame: [RGB8::default(); N_LED],
             fg_state,
             bg_state,
             triggers,
+            frame_rate,
+            brightness: colors::FULL_BRIGHTNESS,
+            mask: [colors::FULL_BRIGHTNESS; N_LED],
+            key_colors: ArrayVec::new(),
+            keymap: None,
+            persistence: 0,
+            afterglow: [RGB8::default(); N_LED],
+            transition: None,
+            transition_style: transition::Style::Crossfade,
+            tiling: Tiling::Single,
+            children: [const { None }; MAX_CHILD_ANIMATIONS],
+            events: ArrayVec::new(),
+            elapsed_ns: 0,
+            is_paused: false,
+            layer_observer: None,
+            #[cfg(feature = "instrumentation")]
+            cycle_counter: None,
+            #[cfg(feature = "instrumentation")]
+            layer_costs: LayerCosts::default(),
         }
     }
+
+    /// Renders the next frame into the segment, compositing each layer over the ones beneath it.
+    /// Returns whether the frame is different from the last one.
+    fn render_frame(&mut self) -> bool {
+        let previous_segment = self.segment;
+        #[cfg(feature = "instrumentation")]
+        let mut timer = LayerTimer::start(self.cycle_counter);
+        #[cfg(not(feature = "instrumentation"))]
+        let mut timer = LayerTimer::start();
+        let events = render_base_layers(
+            &mut self.bg_state,
+            &mut self.fg_state,
+            &mut self.bg_frame,
+            &mut self.segment,
+            &mut timer,
+            self.layer_observer.as_deref_mut(),
+        );
+        for event in events.into_iter().flatten() {
+            let _ = self.events.try_push(event);
+        }
+
+        if self
+            .transition
+            .as_ref()
+            .is_some_and(transition::Transition::is_finished)
+        {
+            self.transition = None;
+        }
+        if let Some(transition) = &mut self.transition {
+            // the outgoing layers are on their way out, so their events are dropped:
+            let mut outgoing_bg = [RGB8::default(); N_LED];
+            let mut outgoing = [RGB8::default(); N_LED];
+            render_base_layers(
+                &mut transition.bg_state,
+                &mut transition.fg_state,
+                &mut outgoing_bg,
+                &mut outgoing,
+                &mut timer,
+                None,
+            );
+            transition.mix(&mut self.segment, &outgoing);
+            if transition.is_finished() {
+                self.transition = None;
+            }
+        }
+
+        // keys with their own color cover the base layers, but triggers still play over them:
+        for &(led, color) in self.key_colors.iter() {
+            self.segment[led] = color;
+        }
+
+        // each trigger blends itself into the layer with its own blend mode, using the scratch
+        // layer to draw on:
+        let mut layer = self.segment;
+        let mut scratch = [RGB8::default(); N_LED];
+        let events = &mut self.events;
+        self.triggers.update(&mut layer, &mut scratch, |mode| {
+            let _ = events.try_push(Event::TriggerFinished(mode));
+        });
+        if let Some(color) = self.triggers.take_left_behind_color() {
+            self.bg_state.set_wash(background::Wash::Solid(color));
+        }
+        let (alpha, brightness) = (self.triggers.alpha, self.triggers.brightness);
+        let normal = colors::BlendMode::Normal;
+        if let Some(observer) = self.layer_observer.as_deref_mut() {
+            observer.layer_drawn(AnimationType::Trigger, &layer, alpha, brightness, normal);
+        }
+        colors::composite(&mut self.segment, &layer, alpha, brightness, normal);
+        timer.lap(AnimationType::Trigger);
+        #[cfg(feature = "instrumentation")]
+        self.layer_costs.add(timer.costs);
+
+        // each LED is kept at least as bright as the faded afterglow of the frame before:
+        if self.persistence > 0 {
+            for (led, glow) in self.segment.iter_mut().zip(self.afterglow.iter()) {
+                let glow = glow.scaled_by(self.persistence);
+                *led = RGB8::new(led.r.max(glow.r), led.g.max(glow.g), led.b.max(glow.b));
+            }
+        }
+        self.afterglow = self.segment;
+
+        // children are updated along with their parent, and replace the LEDs they cover:
+        for child in self.children.iter_mut().flatten() {
+            child.animation.update();
+            while let Some(event) = child.animation.poll_event() {
+                let _ = self.events.try_push(event);
+            }
+            let covered = self.segment.iter_mut().skip(child.start);
+            for (led, &color) in covered.zip(child.animation.segment()) {
+                *led = color;
+            }
+        }
+
+        #[cfg(feature = "color16")]
+        self.render_segment16();
+        colors::scale_brightness(&mut self.segment, self.brightness);
+        for (led, &level) in self.segment.iter_mut().zip(self.mask.iter()) {
+            *led = led.scaled_by(level);
+        }
+        self.segment != previous_segment
+    }
+
+    /// Fills `segment16` from the composited segment, before the brightness and mask of the
+    /// animation are applied. LEDs that nothing was drawn over since the background get the
+    /// background's 16 bit color, and the others are widened from 8 bits.
+    #[cfg(feature = "color16")]
+    fn render_segment16(&mut self) {
+        let layer_brightness = self.bg_state.brightness;
+        let precise = self.bg_state.precise_color().map(|color| {
+            let drawn = output::reduce(color);
+            (drawn, drawn.scaled_by(layer_brightness), color)
+        });
+        let leds = self.segment16.iter_mut().zip(self.segment.iter());
+        for (index, (led16, &led)) in leds.enumerate() {
+            let color = match precise {
+                Some((drawn, shown, color)) if self.bg_frame[index] == drawn && led == shown => {
+                    output::scaled_by16(color, layer_brightness)
+                }
+                _ => output::expand(led),
+            };
+            let color = output::scaled_by16(color, self.brightness);
+            *led16 = output::scaled_by16(color, self.mask[index]);
+        }
+    }
+
+    /// Replaces the default translation array, which maps each LED of this animation onto an
+    /// index of the LogicalStrip. Use this to place several animations on different parts of a
+    /// single physical strip.
+    pub fn set_translation_array(mut self, translation_array: [usize; N_LED]) -> Self {
+        self.translation_array = translation_array;
+        self
+    }
+
+    /// Draws copies of the animation along the strip after the first one, such as mirroring an
+    /// animation of half a strip onto the other half. The translation array only covers the
+    /// first copy, and the copies after it are placed on the indices that follow.
+    pub fn set_tiling(mut self, tiling: Tiling) -> Self {
+        self.tiling = tiling;
+        self
+    }
+
+    /// Nests a child animation inside this one, covering the LEDs of this animation's segment from
+    /// `start` onwards, such as a status bar within a larger ambient animation. The child is
+    /// updated whenever this animation is, and drawn over all of this animation's layers before
+    /// its brightness and mask are applied. Where children overlap, the ones with a higher
+    /// `z_order` are drawn on top. Children past MAX_CHILD_ANIMATIONS are ignored.
+    ///
+    /// The child's translation array and tiling are not used, and it should run at the same
+    /// frame rate as this animation.
+    pub fn add_child(
+        mut self,
+        child: &'a mut dyn Animatable<'a>,
+        start: usize,
+        z_order: u8,
+    ) -> Self {
+        if self.children.iter().all(Option::is_some) {
+            return self;
+        }
+        // insert the child after any with the same z_order, moving the ones above it up a slot:
+        let mut pending = Some(ChildAnimation {
+            animation: child,
+            start,
+            z_order,
+        });
+        let mut is_shifting = false;
+        for slot in self.children.iter_mut() {
+            is_shifting |= slot.as_ref().is_none_or(|c| c.z_order > z_order);
+            if is_shifting {
+                core::mem::swap(slot, &mut pending);
+                if pending.is_none() {
+                    break;
+                }
+            }
+        }
+        self
+    }
+
+    /// Lays the animation's LEDs out as a 2D matrix, so that modes can look up the (x, y)
+    /// position of each LED. This does not change the translation array, which can be generated
+    /// from the matrix's wiring with `Matrix::translation_array()`.
+    pub fn set_matrix(mut self, matrix: Matrix) -> Self {
+        self.bg_state.matrix = Some(matrix);
+        self.fg_state.matrix = Some(matrix);
+        self.triggers.matrix = Some(matrix);
+        self
+    }
+
+    /// Maps key codes, such as the scancodes of a keyboard, onto the LEDs of the animation's
+    /// segment for `set_key_color()`. Key `code` lights LED `keymap[code]`, and codes past the
+    /// end of the keymap have no LED. Without a keymap, keys are the LED indices themselves.
+    pub fn set_keymap(mut self, keymap: &'a [usize]) -> Self {
+        self.keymap = Some(keymap);
+        self
+    }
+
+    /// Returns the LED of the segment that a key lights, if it has one.
+    fn key_led(&self, key: usize) -> Option<usize> {
+        let led = match self.keymap {
+            Some(keymap) => *keymap.get(key)?,
+            None => key,
+        };
+        (led < N_LED).then_some(led)
+    }
 }
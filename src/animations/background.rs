@@ -1,18 +1,34 @@
-use crate::animations::{Direction, MAX_OFFSET};
-use crate::colors::{color_lerp, Rainbow};
+use crate::animations::{
+    AnimationType, CustomAnimation, Direction, ParameterError, RunMode, MAX_OFFSET,
+};
+use crate::colors::{
+    color_lerp, ColorOrder, ColorSpace, ManipulatableColor, Rainbow, FULL_BRIGHTNESS,
+};
+use crate::easing::{Easing, Waveform, EASING_SCALE};
+use crate::mapping::Matrix;
 use crate::utility::{
-    self, convert_ns_to_frames, get_random_offset, FadeRainbow, MarchingRainbow,
-    MarchingRainbowMut, Progression, StatefulRainbow,
+    self, convert_ns_to_frames, convert_ns_to_frames_x256, rescale_frames, rescale_speed, scramble,
+    FadeRainbow, MarchingRainbow, MarchingRainbowMut, Progression, Random, Speed, StatefulRainbow,
 };
+use arrayvec::ArrayVec;
 use embedded_time::rate::Hertz;
+#[cfg(feature = "color16")]
+use rgb::RGB16;
 use rgb::RGB8;
 use smart_leds::colors::*;
 type BgUpdater = fn(&mut Background, &mut [RGB8]);
 
+/// The most drops the Rain mode shows at once. New drops aren't spawned while this many are
+/// falling.
+pub const MAX_RAIN_DROPS: usize = 8;
+
 /// Background Modes are rendered onto the animation LEDs first before any Foreground or Trigger
 /// animations. The other types of animation will overwrite any pixel data from the background that
 /// is effected by their animation.
-pub enum Mode {
+#[derive(Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Mode<'a> {
     /// This turns off all the leds in the animation for the background layer.
     NoBackground,
 
@@ -26,6 +42,11 @@ pub enum Mode {
     /// at a constant rate, changing one color per `duration_ns` sized time step.
     SolidFade,
 
+    /// This will show the current rainbow color on all the LEDs, with its brightness rising and
+    /// falling between `min_brightness` and `max_brightness` following `waveform`, once every
+    /// `duration_ns`. The color steps to the next color in the rainbow when externally triggered.
+    Breathe,
+
     /// This will populate a rainbow's colors evenly across the LED in the animation in order. It
     /// does not animate once drawn.
     /// When externally triggered, it moves to a random offset.
@@ -36,19 +57,47 @@ pub enum Mode {
     /// When externally triggered, it moves to a random offset.
     FillRainbowRotate,
 
-    /// This will use the function provided with the enum to do the update
-    Custom(BgUpdater),
+    /// This will stretch the rainbow across the LEDs as a single smooth gradient from its first
+    /// color at the first LED to its last color at the last LED, repeated `subdivisions` times,
+    /// blending between neighbouring colors in the rainbow's color space. The gradient is moved
+    /// by the offset and rotates in the animation's direction once every `duration_ns`, with the
+    /// last color running straight back into the first.
+    /// When externally triggered, it moves to a random offset.
+    Gradient,
+
+    /// This will color the LEDs from smooth noise that slowly changes over time, giving an organic
+    /// looking field of rainbow colors. The noise drifts by one feature every `duration_ns`, and
+    /// `subdivisions` sets roughly how many features fit across the LEDs.
+    /// When externally triggered, it moves to a random offset.
+    Noise,
+
+    /// This will spawn drops at random positions, on average once every `spawn_interval_ns`,
+    /// which then fall in the animation's direction, taking `duration_ns` to fall the full length
+    /// of the LEDs. Each drop leaves a trail behind it that fades by `trail_decay` every frame.
+    /// On a matrix the drops fall down random columns from the top row. Each drop is the next
+    /// color of the rainbow.
+    /// When externally triggered, a new drop is spawned straight away.
+    Rain,
+
+    /// This will draw the layer with an effect from outside of this crate, see
+    /// `CustomAnimation`.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    Custom(&'a dyn CustomAnimation),
 }
 
-impl Mode {
+impl Mode<'_> {
     fn get_updater(&self) -> Option<BgUpdater> {
         match *self {
             Mode::NoBackground => Some(no_background),
             Mode::Solid => Some(solid),
             Mode::SolidFade => Some(solid_fade),
+            Mode::Breathe => Some(breathe),
             Mode::FillRainbow => Some(fill_rainbow),
             Mode::FillRainbowRotate => Some(fill_rainbow_rotate),
-            Mode::Custom(u) => Some(u),
+            Mode::Gradient => Some(gradient),
+            Mode::Noise => Some(noise),
+            Mode::Rain => Some(rain),
+            Mode::Custom(_) => None,
         }
     }
 }
@@ -67,14 +116,39 @@ fn solid(bg: &mut Background, segment: &mut [RGB8]) {
 
 fn solid_fade(bg: &mut Background, segment: &mut [RGB8]) {
     handle_solid_trigger(bg);
-    for led in segment {
-        *led = bg.calculate_fade_color();
-    }
-    if bg.frames.get_current() == (bg.frames.total - 1) {
+    #[cfg(feature = "color16")]
+    let color = bg.set_precise_color(bg.calculate_fade_color16());
+    #[cfg(not(feature = "color16"))]
+    let color = bg.calculate_fade_color();
+    bg.fill_solid(color, segment);
+    if bg.frames.is_last_frame() {
         bg.rainbow.increment();
     }
 }
 
+fn breathe(bg: &mut Background, segment: &mut [RGB8]) {
+    handle_solid_trigger(bg);
+    let level = bg.waveform.level_at(bg.frames) as i32;
+    let (min, max) = (bg.min_brightness as i32, bg.max_brightness as i32);
+    #[cfg(feature = "color16")]
+    let color = {
+        // the brightness gets the full 16 bits too, since slow breaths dwell on the dim steps:
+        // worked out in 64 bits, since the full range times the step times the level overflows:
+        let (min, max, level) = (min as i64, max as i64, level as i64);
+        let step = crate::output::CHANNEL_STEP as i64;
+        let brightness = min * step + (max - min) * step * level / EASING_SCALE as i64;
+        let scale = |channel: u8| (channel as i64 * step * brightness / 0xffff) as u16;
+        let color = bg.current_rainbow_color();
+        bg.set_precise_color(RGB16::new(scale(color.r), scale(color.g), scale(color.b)))
+    };
+    #[cfg(not(feature = "color16"))]
+    let color = {
+        let brightness = min + (max - min) * level / EASING_SCALE as i32;
+        bg.current_rainbow_color().scaled_by(brightness as u8)
+    };
+    bg.fill_solid(color, segment);
+}
+
 /// Fills the rainbow based on whatever value the offset is currently set to:
 fn fill_rainbow(bg: &mut Background, segment: &mut [RGB8]) {
     handle_rainbow_trigger(bg);
@@ -88,14 +162,106 @@ fn fill_rainbow_rotate(bg: &mut Background, segment: &mut [RGB8]) {
     // current frame / total frames ratio to decide where to begin the rainbow. Need to do the
     // addition of the set offset plus the frame offset as u32s to avoid going over u16::MAX,
     // then modulo back to a u16 value using MAX_OFFSET when done.
-    let color_start_offset = utility::shift_offset(bg.offset, bg.frames, bg.direction);
+    let frames = bg.easing.ease(bg.frames);
+    let color_start_offset = utility::shift_offset(bg.offset, frames, bg.direction);
     bg.fill_rainbow(color_start_offset, segment);
 }
 
+fn gradient(bg: &mut Background, segment: &mut [RGB8]) {
+    handle_rainbow_trigger(bg);
+    let frames = bg.easing.ease(bg.frames);
+    let start_offset = utility::shift_offset(bg.offset, frames, bg.direction);
+    bg.fill_gradient(start_offset, segment);
+}
+
+fn noise(bg: &mut Background, segment: &mut [RGB8]) {
+    handle_rainbow_trigger(bg);
+
+    // time is a noise coordinate that moves one lattice cell per trip through the frames:
+    let cell = 1 << utility::NOISE_FRACTION_BITS;
+    let frame_position = bg.frames.get_current() * cell / bg.frames.total.max(1);
+    let time = bg.noise_cycles.wrapping_mul(cell as u32) + frame_position as u32;
+
+    let led_count = segment.len();
+    let scale = bg.subdivisions.max(1);
+    for (index, led) in segment.iter_mut().enumerate() {
+        let position = (index * scale * cell / led_count) as u32 + bg.offset as u32;
+        *led = bg.noise_color(utility::noise(position, time));
+    }
+
+    if bg.frames.is_last_frame() {
+        bg.noise_cycles = bg.noise_cycles.wrapping_add(1);
+    }
+}
+
+fn rain(bg: &mut Background, segment: &mut [RGB8]) {
+    let (lane_count, lane_length) = match bg.matrix {
+        Some(matrix) => (matrix.width, matrix.height),
+        None => (1, segment.len()),
+    };
+    if lane_length == 0 {
+        return;
+    }
+    let is_spawning = bg
+        .random
+        .next_u32()
+        .is_multiple_of(bg.spawn_frames.max(1) as u32);
+    if bg.has_been_triggered || is_spawning {
+        bg.spawn_rain_drop(lane_count, lane_length);
+        bg.reset_trigger();
+    }
+
+    bg.fill_solid(BLACK, segment);
+    let fall_frames = bg.frames.frames_per_pass().max(1);
+    let decay = bg.trail_decay.min(FULL_BRIGHTNESS - 1) as u32;
+    let matrix = bg.matrix;
+
+    bg.rain_drops.retain(|drop| {
+        let travel = match drop.direction {
+            Direction::Stopped => 0,
+            _ => drop.age * lane_length / fall_frames,
+        };
+        let head = (drop.start + travel).min(lane_length - 1);
+
+        // walk back from the head, fading the trail by the frames since the head passed each LED:
+        let mut brightness = FULL_BRIGHTNESS as u32;
+        let mut faded_frames = 0;
+        let mut is_lit = false;
+        for position in (drop.start..=head).rev() {
+            let passed_at = ((position - drop.start) * fall_frames).div_ceil(lane_length);
+            while faded_frames < drop.age.saturating_sub(passed_at) && brightness > 0 {
+                brightness = brightness * decay / FULL_BRIGHTNESS as u32;
+                faded_frames += 1;
+            }
+            if brightness == 0 {
+                break;
+            }
+            is_lit = true;
+            let position = match drop.direction {
+                Direction::Negative => lane_length - 1 - position,
+                _ => position,
+            };
+            let index = match matrix {
+                Some(matrix) => matrix.index(drop.lane, position),
+                None => position,
+            };
+            if let Some(led) = segment.get_mut(index) {
+                let color = drop.color.scaled_by(brightness as u8);
+                *led = RGB8::new(led.r.max(color.r), led.g.max(color.g), led.b.max(color.b));
+            }
+        }
+        is_lit
+    });
+
+    for drop in bg.rain_drops.iter_mut() {
+        drop.age += 1;
+    }
+}
+
 /// Sets the background to a random offset then resets the trigger
 fn handle_rainbow_trigger(bg: &mut Background) {
     if bg.has_been_triggered {
-        bg.offset = get_random_offset();
+        bg.offset = bg.random.next_offset();
         bg.reset_trigger();
     }
 }
@@ -108,60 +274,513 @@ fn handle_solid_trigger(bg: &mut Background) {
     }
 }
 
+/// A dim base that shows through wherever the background is darker than it, so sparse modes such
+/// as Rain can sit on an ambient color instead of black.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Wash<'a> {
+    /// Leaves the dark parts of the background black.
+    None,
+
+    /// Fills the dark parts of the background with a single color.
+    Solid(RGB8),
+
+    /// Spreads a rainbow once along the segment, without moving, behind the background.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    Rainbow(Rainbow<'a>),
+}
+
 /// This contains all the information necessary to set up and run a background animation. All
 /// aspects of the animation can be derived from these parameters.
+#[derive(Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Parameters<'a> {
-    pub mode: Mode,
+    pub mode: Mode<'a>,
+    /// The rainbow is borrowed, so it is skipped when serializing. Store it separately as a
+    /// `colors::RainbowBuf` to save it along with the parameters. Deserialized parameters start
+    /// with `colors::R_WHITE` until the rainbow is set, so the modes always have a color to draw.
+    #[cfg_attr(
+        feature = "serde",
+        serde(skip, default = "crate::colors::deserialized_rainbow")
+    )]
     pub rainbow: Rainbow<'a>,
     pub direction: Direction,
     pub is_rainbow_forward: bool,
+    /// The color space used when fading between the colors of the rainbow.
+    pub color_space: ColorSpace,
+    /// How the next color of the rainbow is picked, such as at random instead of in order.
+    #[cfg_attr(feature = "serde", serde(borrow))]
+    pub color_order: ColorOrder<'a>,
+    /// The curve followed when fading between rainbow colors and when moving the animation.
+    pub easing: Easing,
     pub duration_ns: u64,
     pub subdivisions: usize,
+    /// The shape of the brightness changes of the Breathe mode.
+    pub waveform: Waveform,
+    /// The dimmest brightness of the Breathe mode.
+    pub min_brightness: u8,
+    /// The brightest brightness of the Breathe mode.
+    pub max_brightness: u8,
+    /// The average time between new drops of the Rain mode.
+    pub spawn_interval_ns: u64,
+    /// How much of its brightness each LED of a Rain trail keeps every frame, where higher values
+    /// leave longer trails.
+    pub trail_decay: u8,
+    /// How many cycles the background runs for before it stops.
+    pub run_mode: RunMode,
+    /// The base shown wherever the background is darker than it.
+    #[cfg_attr(feature = "serde", serde(borrow))]
+    pub wash: Wash<'a>,
+    /// Seeds the random effects of the background, so they are independent of other animations and
+    /// repeat the same way every time. 0 uses the global generator, see `utility::Random`.
+    pub seed: u32,
+    /// Draws the background only once every this many frames, holding it in between, so that
+    /// slow effects cost less. The background still takes `duration_ns` per cycle, and the other
+    /// layers are drawn every frame. 0 and 1 both draw it every frame.
+    pub update_divisor: u32,
+}
+
+impl Parameters<'_> {
+    /// Checks the parameters for a background of `led_count` LEDs, see
+    /// `AnimationParameters::validate()`. Cycles of any length keep moving, so the frame rate
+    /// doesn't matter here.
+    pub fn validate(&self, led_count: usize) -> Result<(), ParameterError> {
+        let error =
+            |kind: fn(AnimationType) -> ParameterError| Err(kind(AnimationType::Background));
+        let uses_rainbow = !matches!(self.mode, Mode::NoBackground | Mode::Custom(_));
+        if uses_rainbow && self.rainbow.is_empty() {
+            return error(ParameterError::EmptyRainbow);
+        }
+        let is_subdivided = matches!(
+            self.mode,
+            Mode::FillRainbow | Mode::FillRainbowRotate | Mode::Gradient | Mode::Noise
+        );
+        if is_subdivided && self.subdivisions > led_count {
+            return error(ParameterError::TooManySubdivisions);
+        }
+        Ok(())
+    }
+}
+
+/// A single falling drop of the Rain mode.
+#[derive(Copy, Clone)]
+struct RainDrop {
+    /// The frames since the drop spawned.
+    age: usize,
+    /// The position along the lane that the drop spawned at.
+    start: usize,
+    /// The column of a matrix that the drop falls down.
+    lane: usize,
+    color: RGB8,
+    /// The direction of the background when the drop spawned, which it keeps until it is gone.
+    direction: Direction,
 }
 
+#[derive(Clone)]
 pub struct Background<'a> {
     // state
     pub offset: u16,
     pub frames: Progression,
     pub has_been_triggered: bool,
+    pub brightness: u8,
+    noise_cycles: u32,
+    rain_drops: ArrayVec<RainDrop, MAX_RAIN_DROPS>,
+    cycles_completed: u32,
+    random: Random,
+    frames_until_update: u32,
+
+    // geometry, used by modes that draw in 2D when set
+    pub matrix: Option<Matrix>,
 
     // parameters
     pub rainbow: StatefulRainbow<'a>,
     direction: Direction,
+    easing: Easing,
     subdivisions: usize,
+    waveform: Waveform,
+    min_brightness: u8,
+    max_brightness: u8,
+    spawn_frames: usize,
+    trail_decay: u8,
+    run_mode: RunMode,
+    wash: Wash<'a>,
+    update_divisor: u32,
+    #[cfg(feature = "color16")]
+    precise_color: Option<RGB16>,
+    speed: Speed,
+    mode: Mode<'a>,
     updater: Option<BgUpdater>,
 }
 
 impl<'a> Background<'a> {
     pub fn new(init: &Parameters<'a>, frame_rate: Hertz) -> Self {
-        let frame_count = convert_ns_to_frames(init.duration_ns, frame_rate);
+        let update_divisor = init.update_divisor.max(1);
+        let to_frames = |ns: u64| convert_ns_to_frames(ns, frame_rate) / update_divisor as usize;
+        let cycle_x256 = convert_ns_to_frames_x256(init.duration_ns, frame_rate);
+
+        let mut rainbow = StatefulRainbow::new(init.rainbow, init.is_rainbow_forward);
+        rainbow.color_space = init.color_space;
+        rainbow.set_seed(scramble(init.seed));
+        rainbow.set_order(init.color_order);
 
         Self {
             offset: 0,
-            frames: Progression::new(frame_count),
+            frames: Progression::from_length_x256(cycle_x256 / update_divisor as usize),
             has_been_triggered: false,
-            rainbow: StatefulRainbow::new(init.rainbow, init.is_rainbow_forward),
+            brightness: FULL_BRIGHTNESS,
+            noise_cycles: 0,
+            rain_drops: ArrayVec::new(),
+            cycles_completed: 0,
+            random: Random::new(init.seed),
+            frames_until_update: 0,
+            matrix: None,
+            rainbow,
             direction: init.direction,
+            easing: init.easing,
             subdivisions: init.subdivisions,
+            waveform: init.waveform,
+            min_brightness: init.min_brightness,
+            max_brightness: init.max_brightness,
+            spawn_frames: to_frames(init.spawn_interval_ns),
+            trail_decay: init.trail_decay,
+            run_mode: init.run_mode,
+            wash: init.wash,
+            update_divisor,
+            #[cfg(feature = "color16")]
+            precise_color: None,
+            speed: Speed::NORMAL,
+            mode: init.mode,
             updater: init.mode.get_updater(),
         }
     }
 
-    pub fn update(&mut self, segment: &mut [RGB8]) {
-        if let Some(f) = self.updater {
-            f(self, segment);
+    /// Replaces the parameters of a running background without resetting its offset or the
+    /// current position of its frame and rainbow progressions.
+    pub fn set_parameters(&mut self, init: &Parameters<'a>, frame_rate: Hertz) {
+        self.set_update_divisor(init.update_divisor);
+        let update_divisor = self.update_divisor as usize;
+        let to_frames = |ns: u64| convert_ns_to_frames(ns, frame_rate) / update_divisor;
+        let cycle_x256 = convert_ns_to_frames_x256(init.duration_ns, frame_rate) / update_divisor;
+
+        self.frames
+            .set_length_x256(self.speed.scale_frames(cycle_x256));
+        self.set_seed(init.seed);
+        self.rainbow
+            .set_rainbow(init.rainbow, init.is_rainbow_forward);
+        self.rainbow.color_space = init.color_space;
+        self.rainbow.set_order(init.color_order);
+        self.direction = init.direction;
+        self.easing = init.easing;
+        self.subdivisions = init.subdivisions;
+        self.waveform = init.waveform;
+        self.min_brightness = init.min_brightness;
+        self.max_brightness = init.max_brightness;
+        self.spawn_frames = self.speed.scale_frames(to_frames(init.spawn_interval_ns));
+        self.trail_decay = init.trail_decay;
+        // a new run mode starts counting its cycles from here:
+        if self.run_mode != init.run_mode {
+            self.run_mode = init.run_mode;
+            self.cycles_completed = 0;
+        }
+        self.wash = init.wash;
+        if core::mem::discriminant(&self.mode) != core::mem::discriminant(&init.mode) {
+            trace!("background mode changed to {}", init.mode);
+        }
+        self.mode = init.mode;
+        self.updater = init.mode.get_updater();
+    }
+
+    /// Rescales the frame counts of the background so that it keeps the same speed at a new frame
+    /// rate.
+    pub fn rescale_frame_rate(&mut self, old_rate: Hertz, new_rate: Hertz) {
+        self.frames.rescale_frame_rate(old_rate, new_rate);
+        self.spawn_frames = rescale_frames(self.spawn_frames, old_rate, new_rate);
+        for drop in self.rain_drops.iter_mut() {
+            drop.age = rescale_frames(drop.age, old_rate, new_rate);
+        }
+    }
+
+    /// Changes how many frames pass between each time the background is drawn, rescaling its
+    /// frame counts so that it keeps the same speed.
+    pub fn set_update_divisor(&mut self, divisor: u32) {
+        let divisor = divisor.max(1);
+        // drawing the background less often is the same as running it at a lower frame rate:
+        self.rescale_frame_rate(Hertz(divisor), Hertz(self.update_divisor));
+        self.update_divisor = divisor;
+        self.frames_until_update = self.frames_until_update.min(divisor - 1);
+    }
+
+    /// Returns whether the background should be drawn this frame, counting down the frames it is
+    /// held for in between.
+    pub fn is_update_due(&mut self) -> bool {
+        match self.frames_until_update {
+            0 => {
+                self.frames_until_update = self.update_divisor - 1;
+                true
+            }
+            _ => {
+                self.frames_until_update -= 1;
+                false
+            }
+        }
+    }
+
+    /// Starts the random effects of the background over from a new seed, unless it already has
+    /// that seed.
+    pub fn set_seed(&mut self, seed: u32) {
+        if self.random.seed() != seed {
+            self.random = Random::new(seed);
+            self.rainbow.set_seed(scramble(seed));
+        }
+    }
+
+    /// Changes the direction of the background. A rotating rainbow or gradient turns around from
+    /// where it is, and rain drops that are already falling keep going the way they were.
+    pub fn set_direction(&mut self, direction: Direction) {
+        if matches!(self.mode, Mode::FillRainbowRotate | Mode::Gradient) {
+            match (self.direction, direction) {
+                (old, new) if old.is_reversed_by(new) => self.frames.mirror(),
+                // fold the rotation so far into the offset, so the rainbow stops where it is:
+                (Direction::Positive | Direction::Negative, Direction::Stopped) => {
+                    let frames = self.easing.ease(self.frames);
+                    self.offset = utility::shift_offset(self.offset, frames, self.direction);
+                    self.frames.reset();
+                }
+                (Direction::Stopped, Direction::Positive | Direction::Negative) => {
+                    self.frames.reset()
+                }
+                _ => {}
+            }
+        }
+        self.direction = direction;
+    }
+
+    /// Changes how fast the background runs, rescaling its frame counts and any drops that are
+    /// falling.
+    pub fn set_speed(&mut self, speed: Speed) {
+        self.frames.rescale_speed(self.speed, speed);
+        self.spawn_frames = rescale_speed(self.spawn_frames, self.speed, speed);
+        for drop in self.rain_drops.iter_mut() {
+            drop.age = rescale_speed(drop.age, self.speed, speed);
+        }
+        self.speed = speed;
+    }
+
+    /// Copies the externally controlled state of another background, such as its offset,
+    /// brightness and speed, so that replacing it with this one doesn't visibly reset those
+    /// settings.
+    pub fn carry_over_state(&mut self, other: &Self) {
+        self.offset = other.offset;
+        self.brightness = other.brightness;
+        self.matrix = other.matrix;
+        self.set_speed(other.speed);
+    }
+
+    /// Returns whether the background has finished the cycles its run mode asks for.
+    pub fn is_complete(&self) -> bool {
+        self.run_mode.is_complete(self.cycles_completed)
+    }
+
+    /// Starts the background's run over from the first frame of its first cycle.
+    pub fn restart_run(&mut self) {
+        self.frames.reset();
+        self.cycles_completed = 0;
+    }
+
+    /// Draws the next frame of the background. Returns whether the background moved on to
+    /// another color of its rainbow.
+    pub fn update(&mut self, segment: &mut [RGB8]) -> bool {
+        let did_advance = self.draw_next_frame(segment);
+        self.apply_wash(segment);
+        did_advance
+    }
+
+    fn draw_next_frame(&mut self, segment: &mut [RGB8]) -> bool {
+        if self.is_complete() {
+            self.draw_finished(segment);
+            return false;
+        }
+        if self.frames.is_last_frame() && self.run_mode.is_complete(self.cycles_completed + 1) {
+            // the last frame is drawn from a copy of the background, which leaves it in the same
+            // state so that it can be drawn again while it is held:
+            self.cycles_completed += 1;
+            self.clone().draw(segment);
+            return false;
+        }
+
+        let previous_color = self.rainbow.position.get_current();
+        self.draw(segment);
+        if self.frames.is_last_frame() {
+            self.cycles_completed = self.cycles_completed.saturating_add(1);
         }
         self.frames.increment();
+        let current_color = self.rainbow.position.get_current();
+        if current_color != previous_color {
+            trace!(
+                "background rainbow advanced to color {=usize}",
+                current_color
+            );
+        }
+        current_color != previous_color
+    }
+
+    /// Draws the background once its run is complete, either holding its last frame or clearing
+    /// the segment to black.
+    fn draw_finished(&self, segment: &mut [RGB8]) {
+        match self.run_mode {
+            RunMode::OneShotClear => segment.fill(RGB8::default()),
+            _ => self.clone().draw(segment),
+        }
+    }
+
+    /// Draws a frame with the updater of the mode, or with the effect of a Custom mode.
+    fn draw(&mut self, segment: &mut [RGB8]) {
+        #[cfg(feature = "color16")]
+        {
+            self.precise_color = None;
+        }
+        match (self.mode, self.updater) {
+            (Mode::Custom(animation), _) => animation.update_background(self, segment),
+            (_, Some(f)) => f(self, segment),
+            (_, None) => {}
+        }
+    }
+
+    /// Keeps the 16 bit color that every LED of the frame is filled with, and returns the 8 bit
+    /// color to draw.
+    #[cfg(feature = "color16")]
+    fn set_precise_color(&mut self, color: RGB16) -> RGB8 {
+        self.precise_color = Some(color);
+        crate::output::reduce(color)
+    }
+
+    /// Returns the 16 bit color of the last frame drawn, for modes that fill every LED with the
+    /// same color, before the wash and the brightness of the layer. LEDs that don't show this
+    /// color, reduced to 8 bits, were changed by something else.
+    #[cfg(feature = "color16")]
+    pub fn precise_color(&self) -> Option<RGB16> {
+        self.precise_color
+    }
+
+    /// Returns the direction the background is moving in.
+    pub fn direction(&self) -> Direction {
+        self.direction
+    }
+
+    /// Replaces the wash behind the background, such as with the color left behind by a wipe
+    /// trigger.
+    pub fn set_wash(&mut self, wash: Wash<'a>) {
+        self.wash = wash;
     }
 
     pub fn reset_trigger(&mut self) {
         self.has_been_triggered = false;
     }
 
+    /// Raises each channel of every LED to at least the matching channel of the wash.
+    fn apply_wash(&self, segment: &mut [RGB8]) {
+        let led_count = segment.len();
+        for (index, led) in segment.iter_mut().enumerate() {
+            let wash = match self.wash {
+                Wash::None => return,
+                Wash::Solid(color) => color,
+                Wash::Rainbow([]) => return,
+                Wash::Rainbow(rainbow) => {
+                    let position = index * rainbow.len();
+                    let start_index = position / led_count;
+                    let end_index = (start_index + 1) % rainbow.len();
+                    color_lerp(
+                        (position % led_count) as i32,
+                        0,
+                        led_count as i32,
+                        rainbow[start_index],
+                        rainbow[end_index],
+                    )
+                }
+            };
+            *led = RGB8::new(led.r.max(wash.r), led.g.max(wash.g), led.b.max(wash.b));
+        }
+    }
+
+    /// Starts a new drop of the Rain mode in a random lane, unless the most drops are already
+    /// falling.
+    fn spawn_rain_drop(&mut self, lane_count: usize, lane_length: usize) {
+        let start = match self.matrix {
+            Some(_) => 0,
+            None => self.random.next_u32() as usize % lane_length,
+        };
+        let drop = RainDrop {
+            age: 0,
+            start,
+            lane: self.random.next_u32() as usize % lane_count.max(1),
+            color: self.current_rainbow_color(),
+            direction: self.direction,
+        };
+        if self.rain_drops.try_push(drop).is_ok() {
+            self.rainbow.increment();
+        }
+    }
+
     fn fill_solid(&mut self, color: RGB8, segment: &mut [RGB8]) {
         segment.iter_mut().for_each(|led| *led = color);
     }
 
+    /// Picks the color of a noise value, spreading the full range of noise over the rainbow.
+    fn noise_color(&self, value: u8) -> RGB8 {
+        let rainbow = &self.rainbow.backer;
+        if rainbow.is_empty() {
+            return BLACK;
+        }
+        let position = value as usize * rainbow.len();
+        let start_index = position / 256;
+        let end_index = (start_index + 1) % rainbow.len();
+        color_lerp(
+            (position % 256) as i32,
+            0,
+            256,
+            rainbow[start_index],
+            rainbow[end_index],
+        )
+    }
+
+    /// Fills the segment with the Gradient mode's copies of the rainbow, shifted along the LEDs by
+    /// `start_offset`.
+    fn fill_gradient(&mut self, start_offset: u16, segment: &mut [RGB8]) {
+        const BLEND_STEPS: usize = 256;
+        let rainbow = &self.rainbow.backer;
+        let step_count = rainbow.len().saturating_sub(1);
+        if step_count == 0 {
+            let color = self.current_rainbow_color();
+            self.fill_solid(color, segment);
+            return;
+        }
+        let max_offset = MAX_OFFSET as usize;
+        let copies = self.subdivisions.clamp(1, max_offset);
+        let span = max_offset / copies;
+        let last_led = segment.len().saturating_sub(1).max(1);
+
+        for (index, led) in segment.iter_mut().enumerate() {
+            // the first LED is at the start of the gradient and the last LED is at its end:
+            let position = index * max_offset / last_led;
+            let shifted = (position + max_offset + 1 - start_offset as usize) % (max_offset + 1);
+            let copy = (shifted / span).min(copies - 1);
+            let within = (shifted - copy * span).min(span);
+
+            let scaled = within * step_count;
+            let color_index = (scaled / span).min(step_count - 1);
+            let blend = (scaled - color_index * span) * BLEND_STEPS / span;
+            *led = match blend >= BLEND_STEPS {
+                true => rainbow[color_index + 1],
+                false => {
+                    let mut factor = Progression::new(BLEND_STEPS);
+                    factor.set_current(blend);
+                    let (start, end) = (rainbow[color_index], rainbow[color_index + 1]);
+                    start.lerp_in_space_with(end, factor, self.rainbow.color_space)
+                }
+            };
+        }
+    }
+
     fn fill_rainbow(&mut self, start_offset: u16, segment: &mut [RGB8]) {
         let start_offset = start_offset as usize;
         let max_offset = MAX_OFFSET as usize;
@@ -239,4 +858,7 @@ impl<'a> FadeRainbow for Background<'a> {
     fn frames(&self) -> &Progression {
         &self.frames
     }
+    fn easing(&self) -> Easing {
+        self.easing
+    }
 }
@@ -1,9 +1,14 @@
 use crate::{
-    animations::{Direction, MAX_OFFSET},
-    colors::Rainbow,
+    animations::{AnimationType, CustomAnimation, Direction, ParameterError, RunMode, MAX_OFFSET},
+    colors::{
+        color_lerp, BlendMode, ColorOrder, ColorSpace, ManipulatableColor, Rainbow, FULL_BRIGHTNESS,
+    },
+    easing::Easing,
+    mapping::Matrix,
+    text,
     utility::{
-        convert_ns_to_frames, FadeRainbow, MarchingRainbow, MarchingRainbowMut, Progression,
-        StatefulRainbow,
+        convert_ns_to_frames, convert_ns_to_frames_x256, rounds_to_no_frames, scramble,
+        FadeRainbow, MarchingRainbow, MarchingRainbowMut, Progression, Speed, StatefulRainbow,
     },
 };
 use embedded_time::rate::Hertz;
@@ -15,7 +20,10 @@ type FgUpdater = fn(&mut Foreground, &mut [RGB8]);
 /// Foreground modes are rendered second, and will animate over the background animation layer but
 /// below the trigger animations. Any trigger animations will overwrite the pixel data from the
 /// foreground that is effected by their animation.
-pub enum Mode {
+#[derive(Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Mode<'a> {
     /// This is a mode that has no additional foreground animation over the background animation.
     NoForeground,
 
@@ -41,11 +49,63 @@ pub enum Mode {
     /// the offset value alone.
     VUMeter,
 
-    /// This will use the function provided with the enum to do the update
-    Custom(FgUpdater),
+    /// This will render a VU meter style bar driven by the level set with `set_input_level()`,
+    /// using the rainbow colors spread along the length of the bar. The bar falls back down from
+    /// full to empty over `duration_ns`, and a peak LED is held at the highest recent level for
+    /// `step_time_ns` before it falls as well. LEDs above the bar are left transparent.
+    LevelMeter,
+
+    /// This will scroll `text` across a matrix set with `Animation::set_matrix()`, moving one
+    /// column every `step_time_ns`. The text color fades through the rainbow over `duration_ns`,
+    /// and LEDs around the text are left transparent. A positive direction scrolls the text to the
+    /// left, a negative direction to the right, and stopped holds it at the left edge. Nothing is
+    /// drawn if the animation has no matrix.
+    ScrollingText,
+
+    /// This will sweep a bar back and forth across the LEDs like a Larson scanner, taking
+    /// `duration_ns` to cross from one end to the other. `pixels_per_pixel_group` sets the width
+    /// of the bar, from 1 LED up to the whole length of the LEDs. A trail of `trail_length` LEDs
+    /// fades out behind the bar. The bar color fades through the rainbow, and a negative
+    /// direction starts the sweep from the far end.
+    Scanner,
+
+    /// This will light every `spacing`th LED, moving the lit LEDs along by one every
+    /// `step_time_ns` in the animation's direction, like the lights around a theater marquee. The
+    /// lit LEDs show the rainbow spread `subdivisions` times along the LEDs, which rotates by one
+    /// color every `duration_ns`. LEDs between the lit ones are left transparent.
+    /// The foreground trigger will advance to the next color of the rainbow.
+    TheaterChase,
+
+    /// This will fill the LEDs up to the progress set with `set_progress()`, from 0 for empty to
+    /// MAX_OFFSET for full, like a boot progress or battery bar. A new progress isn't jumped to
+    /// straight away: the bar slides towards it, taking `duration_ns` to cross from empty to full,
+    /// and the LED at the tip of the bar is partly lit so that it moves smoothly. The bar shows
+    /// the rainbow spread along the LEDs, and a negative direction fills it from the far end.
+    /// LEDs past the bar are left transparent.
+    ProgressBar,
+
+    /// This will fill the LEDs up to the value set with `set_progress()` the same as ProgressBar,
+    /// but the whole bar is one color, picked by the zone the value is in: the first color of the
+    /// rainbow below the first of the `zone_thresholds`, the second color up to the next
+    /// threshold, and so on. While the value is below `blink_below`, the bar blinks on and off
+    /// every `step_time_ns`. `default_animations::FG_STATUS_BAR` sets up a battery style bar.
+    StatusBar,
+
+    /// This will fill the LEDs up to the offset set with `set_offset()`, from 0 for empty to
+    /// MAX_OFFSET for full, like the needle of a dial, so that a reading from an encoder or a
+    /// sensor shows up straight away without sliding. The bar is the current color of the
+    /// rainbow, the LED at its tip is partly lit, and a negative direction fills it from the far
+    /// end. LEDs past the bar are left transparent.
+    /// The foreground trigger will advance to the next color of the rainbow.
+    ScannerFill,
+
+    /// This will draw the layer with an effect from outside of this crate, see
+    /// `CustomAnimation`.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    Custom(&'a dyn CustomAnimation),
 }
 
-impl Mode {
+impl Mode<'_> {
     fn get_updater(&self) -> Option<FgUpdater> {
         match *self {
             Mode::NoForeground => None,
@@ -54,7 +114,14 @@ impl Mode {
             Mode::MarqueeFade => Some(marquee_fade),
             Mode::MarqueeFadeFixed => Some(marquee_fade_fixed),
             Mode::VUMeter => Some(vu_meter),
-            Mode::Custom(u) => Some(u),
+            Mode::LevelMeter => Some(level_meter),
+            Mode::ScrollingText => Some(scrolling_text),
+            Mode::Scanner => Some(scanner),
+            Mode::TheaterChase => Some(theater_chase),
+            Mode::ProgressBar => Some(progress_bar),
+            Mode::StatusBar => Some(status_bar),
+            Mode::ScannerFill => Some(scanner_fill),
+            Mode::Custom(_) => None,
         }
     }
 }
@@ -88,16 +155,174 @@ fn marquee_fade_fixed(fg: &mut Foreground, segment: &mut [RGB8]) {
 fn vu_meter(fg: &mut Foreground, segment: &mut [RGB8]) {
     fg.current_fade_color();
     let led_count = segment.len();
-    let last_on_led = fg.offset as usize / led_count;
+    let last_on_led = fg.offset as usize * led_count / MAX_OFFSET as usize;
     for led in &mut segment[last_on_led..] {
         *led = BLACK;
     }
 }
 
+fn level_meter(fg: &mut Foreground, segment: &mut [RGB8]) {
+    fg.update_meter_levels();
+
+    let led_count = segment.len();
+    let rainbow = &fg.rainbow.backer;
+    if rainbow.is_empty() || led_count == 0 {
+        return;
+    }
+    let color_at = |index: usize| rainbow[index * rainbow.len() / led_count];
+
+    let lit_led_count = fg.meter_level as usize * led_count / u8::MAX as usize;
+    for (index, led) in segment.iter_mut().enumerate().take(lit_led_count) {
+        *led = color_at(index);
+    }
+
+    if fg.peak_level > 0 {
+        let peak_index = (fg.peak_level as usize * led_count / u8::MAX as usize).min(led_count - 1);
+        segment[peak_index] = color_at(peak_index);
+    }
+}
+
+fn scrolling_text(fg: &mut Foreground, segment: &mut [RGB8]) {
+    let matrix = match fg.matrix {
+        Some(matrix) if matrix.led_count() <= segment.len() => matrix,
+        _ => return,
+    };
+    if fg.step_frames.total <= 1 || fg.step_frames.checked_increment() {
+        fg.scroll_position += 1;
+    }
+
+    // the text starts just off the edge of the matrix and scrolls until it has completely left:
+    let text_width = text::text_width(fg.text);
+    let scroll_length = matrix.width + text_width;
+    fg.scroll_position %= scroll_length.max(1);
+
+    let color = fg.calculate_fade_color();
+    let top_row = matrix.height.saturating_sub(text::FONT_HEIGHT) / 2;
+    for x in 0..matrix.width {
+        let text_column = match fg.direction {
+            Direction::Positive => (x + fg.scroll_position).checked_sub(matrix.width),
+            Direction::Negative => (x + text_width).checked_sub(fg.scroll_position),
+            Direction::Stopped => Some(x),
+        };
+        let bits = match text_column {
+            Some(column) if column < text_width => text::column(fg.text, column),
+            _ => continue,
+        };
+        for row in 0..text::FONT_HEIGHT {
+            let y = top_row + row;
+            if y < matrix.height && bits & (1 << row) != 0 {
+                segment[matrix.index(x, y)] = color;
+            }
+        }
+    }
+}
+
+fn scanner(fg: &mut Foreground, segment: &mut [RGB8]) {
+    if segment.is_empty() {
+        return;
+    }
+    let led_count = segment.len();
+    let bar_width = fg.pixels_per_pixel_group.clamp(1, led_count);
+    let travel = led_count - bar_width;
+
+    let frames = fg.easing.ease(fg.frames);
+    let progress = match fg.direction {
+        Direction::Stopped => 0,
+        _ => frames.get_current() * travel / frames.total.max(1),
+    };
+    let is_moving_forward = fg.is_scanning_forward != matches!(fg.direction, Direction::Negative);
+    let bar_start = match is_moving_forward {
+        true => progress,
+        false => travel - progress,
+    };
+
+    let color = fg.calculate_fade_color();
+    for led in &mut segment[bar_start..bar_start + bar_width] {
+        *led = color;
+    }
+
+    // the trail is drawn behind the bar, fading out the further it is from the bar:
+    let mut fade = Progression::new(fg.trail_length + 1);
+    for distance in 1..=fg.trail_length {
+        let index = match is_moving_forward {
+            true => bar_start.checked_sub(distance),
+            false => Some(bar_start + bar_width - 1 + distance).filter(|&i| i < led_count),
+        };
+        let Some(index) = index else { break };
+        fade.set_current(fg.trail_length + 1 - distance);
+        segment[index] = segment[index].lerp_with(color, fade);
+    }
+
+    // a stopped scanner or one without frames never reaches the end of a sweep to turn around at:
+    let is_sweeping = fg.frames.total > 0 && !matches!(fg.direction, Direction::Stopped);
+    if is_sweeping && fg.frames.is_last_frame() {
+        fg.is_scanning_forward = !fg.is_scanning_forward;
+    }
+}
+
+fn theater_chase(fg: &mut Foreground, segment: &mut [RGB8]) {
+    handle_marquee_trigger(fg);
+    let spacing = fg.spacing.max(1);
+    if fg.step_frames.total <= 1 || fg.step_frames.checked_increment() {
+        fg.chase_position = match fg.direction {
+            Direction::Positive => fg.chase_position + 1,
+            Direction::Negative => fg.chase_position + spacing - 1,
+            Direction::Stopped => fg.chase_position,
+        };
+    }
+    fg.chase_position %= spacing;
+
+    let led_count = segment.len();
+    for (index, led) in segment.iter_mut().enumerate() {
+        if index % spacing == fg.chase_position {
+            *led = fg.chase_color(index, led_count);
+        }
+    }
+}
+
+fn progress_bar(fg: &mut Foreground, segment: &mut [RGB8]) {
+    fg.update_progress();
+    let led_count = segment.len();
+    let rainbow = &fg.rainbow.backer;
+    if rainbow.is_empty() {
+        return;
+    }
+    fg.fill_bar(segment, fg.progress, |position| {
+        rainbow[position * rainbow.len() / led_count]
+    });
+}
+
+fn status_bar(fg: &mut Foreground, segment: &mut [RGB8]) {
+    fg.update_progress();
+    if fg.step_frames.total <= 1 || fg.step_frames.checked_increment() {
+        fg.is_blink_lit = !fg.is_blink_lit;
+    }
+    let rainbow = &fg.rainbow.backer;
+    if rainbow.is_empty() || (fg.progress < fg.blink_below && !fg.is_blink_lit) {
+        return;
+    }
+    let zone = fg
+        .zone_thresholds
+        .iter()
+        .filter(|&&threshold| fg.progress >= threshold)
+        .count();
+    let color = rainbow[zone.min(rainbow.len() - 1)];
+    fg.fill_bar(segment, fg.progress, |_| color);
+}
+
+fn scanner_fill(fg: &mut Foreground, segment: &mut [RGB8]) {
+    handle_marquee_trigger(fg);
+    if fg.rainbow.backer.is_empty() {
+        return;
+    }
+    let color = fg.rainbow.current_color();
+    fg.fill_bar(segment, fg.offset, |_| color);
+}
+
 fn set_marquee_toggle(fg: &mut Foreground, led_count: usize) {
-    let pip_distance = (MAX_OFFSET as usize / led_count) * fg.pixels_per_pixel_group.max(1);
+    let pip_distance = (MAX_OFFSET as usize / led_count.max(1)) * fg.pixels_per_pixel_group.max(1);
     let led_bucket = fg.offset as usize / pip_distance.max(1);
-    fg.marquee_position_toggle = led_bucket % 2 == 0;
+    fg.marquee_position_toggle = led_bucket.is_multiple_of(2);
 }
 
 fn handle_marquee_trigger(fg: &mut Foreground) {
@@ -109,18 +334,79 @@ fn handle_marquee_trigger(fg: &mut Foreground) {
 
 /// This contains all the information necessary to set up and run a foreground animation. All
 /// aspects of the animation can be derived from these parameters.
+#[derive(Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Parameters<'a> {
-    pub mode: Mode,
+    pub mode: Mode<'a>,
+    /// The rainbow is borrowed, so it is skipped when serializing. Store it separately as a
+    /// `colors::RainbowBuf` to save it along with the parameters. Deserialized parameters start
+    /// with `colors::R_WHITE` until the rainbow is set, so the modes always have a color to draw.
+    #[cfg_attr(
+        feature = "serde",
+        serde(skip, default = "crate::colors::deserialized_rainbow")
+    )]
     pub rainbow: Rainbow<'a>,
     pub direction: Direction,
     pub is_rainbow_forward: bool,
+    /// The color space used when fading between the colors of the rainbow.
+    pub color_space: ColorSpace,
+    /// How the next color of the rainbow is picked, such as at random instead of in order.
+    #[cfg_attr(feature = "serde", serde(borrow))]
+    pub color_order: ColorOrder<'a>,
+    /// The curve followed when fading between rainbow colors and when moving the animation.
+    pub easing: Easing,
     pub duration_ns: u64,
     pub step_time_ns: u64,
     pub subdivisions: usize,
+    /// The number of LEDs in each pip of the Marquee modes, and the width of the bar of the
+    /// Scanner mode.
     pub pixels_per_pixel_group: usize,
+    /// The length of the fading trail behind the bar of the Scanner mode.
+    pub trail_length: usize,
+    /// The distance between the lit LEDs of the TheaterChase mode, so 3 lights every third LED.
+    pub spacing: usize,
+    /// The text shown by the ScrollingText mode.
+    pub text: &'a str,
+    /// The values where the StatusBar mode moves on to the next color of its rainbow, from lowest
+    /// to highest. The thresholds are borrowed, so they are skipped when serializing.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub zone_thresholds: &'a [u16],
+    /// The StatusBar mode blinks while its value is below this. 0 never blinks.
+    pub blink_below: u16,
+    /// How opaque the foreground is when drawn over the background, from `colors::TRANSPARENT`
+    /// to `colors::OPAQUE`.
+    pub alpha: u8,
+    /// How the foreground's colors are combined with the background.
+    pub blend_mode: BlendMode,
+    /// How many cycles the foreground runs for before it stops.
+    pub run_mode: RunMode,
+    /// Seeds the random color orders of the foreground's rainbow, so they pick the same colors
+    /// every time. 0 uses the global generator.
+    pub seed: u32,
+}
+
+impl Parameters<'_> {
+    /// Checks the parameters for a foreground of `led_count` LEDs, see
+    /// `AnimationParameters::validate()`.
+    pub fn validate(&self, frame_rate: Hertz, led_count: usize) -> Result<(), ParameterError> {
+        let error =
+            |kind: fn(AnimationType) -> ParameterError| Err(kind(AnimationType::Foreground));
+        let uses_rainbow = !matches!(self.mode, Mode::NoForeground | Mode::Custom(_));
+        if uses_rainbow && self.rainbow.is_empty() {
+            return error(ParameterError::EmptyRainbow);
+        }
+        if rounds_to_no_frames(self.step_time_ns, frame_rate) {
+            return error(ParameterError::DurationUnderOneFrame);
+        }
+        if matches!(self.mode, Mode::TheaterChase) && self.subdivisions > led_count {
+            return error(ParameterError::TooManySubdivisions);
+        }
+        Ok(())
+    }
 }
 
 #[allow(dead_code)]
+#[derive(Clone)]
 pub struct Foreground<'a> {
     // state
     pub offset: u16,
@@ -128,48 +414,304 @@ pub struct Foreground<'a> {
     pub step_frames: Progression,
     marquee_position_toggle: bool,
     pub has_been_triggered: bool,
+    pub brightness: u8,
+    pub input_level: u8,
+    meter_level: u8,
+    peak_level: u8,
+    progress: u16,
+    target_progress: u16,
+    is_blink_lit: bool,
+    scroll_position: usize,
+    is_scanning_forward: bool,
+    chase_position: usize,
+    cycles_completed: u32,
+
+    // geometry, used by modes that draw in 2D when set
+    pub matrix: Option<Matrix>,
 
     // parameters
     pub rainbow: StatefulRainbow<'a>,
     direction: Direction,
+    easing: Easing,
     subdivisions: usize,
     pixels_per_pixel_group: usize,
+    trail_length: usize,
+    spacing: usize,
+    text: &'a str,
+    zone_thresholds: &'a [u16],
+    blink_below: u16,
+    pub alpha: u8,
+    pub blend_mode: BlendMode,
+    run_mode: RunMode,
+    speed: Speed,
+    mode: Mode<'a>,
     updater: Option<FgUpdater>,
 }
 
 impl<'a> Foreground<'a> {
     pub fn new(init: &Parameters<'a>, frame_rate: Hertz) -> Self {
-        let frame_count = convert_ns_to_frames(init.duration_ns, frame_rate);
+        let cycle_x256 = convert_ns_to_frames_x256(init.duration_ns, frame_rate);
         let step_frame_count = convert_ns_to_frames(init.step_time_ns, frame_rate);
 
+        let mut rainbow = StatefulRainbow::new(init.rainbow, init.is_rainbow_forward);
+        rainbow.color_space = init.color_space;
+        rainbow.set_seed(scramble(init.seed));
+        rainbow.set_order(init.color_order);
+
         Self {
             offset: 0,
-            frames: Progression::new(frame_count),
+            frames: Progression::from_length_x256(cycle_x256),
             step_frames: Progression::new(step_frame_count),
             marquee_position_toggle: false,
             has_been_triggered: false,
-            rainbow: StatefulRainbow::new(init.rainbow, init.is_rainbow_forward),
+            brightness: FULL_BRIGHTNESS,
+            input_level: 0,
+            meter_level: 0,
+            peak_level: 0,
+            progress: 0,
+            target_progress: 0,
+            is_blink_lit: true,
+            scroll_position: 0,
+            is_scanning_forward: true,
+            chase_position: 0,
+            cycles_completed: 0,
+            matrix: None,
+            rainbow,
             direction: init.direction,
+            easing: init.easing,
             subdivisions: init.subdivisions,
             pixels_per_pixel_group: init.pixels_per_pixel_group,
+            trail_length: init.trail_length,
+            spacing: init.spacing,
+            text: init.text,
+            zone_thresholds: init.zone_thresholds,
+            blink_below: init.blink_below,
+            alpha: init.alpha,
+            blend_mode: init.blend_mode,
+            run_mode: init.run_mode,
+            speed: Speed::NORMAL,
+            mode: init.mode,
             updater: init.mode.get_updater(),
         }
     }
 
-    pub fn update(&mut self, segment: &mut [RGB8]) {
-        if let Some(f) = self.updater {
-            f(self, segment);
+    /// Replaces the parameters of a running foreground without resetting its offset or the
+    /// current position of its frame, step and rainbow progressions.
+    pub fn set_parameters(&mut self, init: &Parameters<'a>, frame_rate: Hertz) {
+        let cycle_x256 = convert_ns_to_frames_x256(init.duration_ns, frame_rate);
+        let step_frame_count = convert_ns_to_frames(init.step_time_ns, frame_rate);
+        let step_frame_count = self.speed.scale_frames(step_frame_count);
+
+        self.frames
+            .set_length_x256(self.speed.scale_frames(cycle_x256));
+        self.step_frames.set_total(step_frame_count);
+        self.rainbow
+            .set_rainbow(init.rainbow, init.is_rainbow_forward);
+        self.rainbow.color_space = init.color_space;
+        self.rainbow.set_seed(scramble(init.seed));
+        self.rainbow.set_order(init.color_order);
+        self.direction = init.direction;
+        self.easing = init.easing;
+        self.subdivisions = init.subdivisions;
+        self.pixels_per_pixel_group = init.pixels_per_pixel_group;
+        self.trail_length = init.trail_length;
+        self.spacing = init.spacing;
+        self.text = init.text;
+        self.zone_thresholds = init.zone_thresholds;
+        self.blink_below = init.blink_below;
+        self.alpha = init.alpha;
+        self.blend_mode = init.blend_mode;
+        // a new run mode starts counting its cycles from here:
+        if self.run_mode != init.run_mode {
+            self.run_mode = init.run_mode;
+            self.cycles_completed = 0;
+        }
+        if core::mem::discriminant(&self.mode) != core::mem::discriminant(&init.mode) {
+            trace!("foreground mode changed to {}", init.mode);
+        }
+        self.mode = init.mode;
+        self.updater = init.mode.get_updater();
+    }
+
+    /// Rescales the frame counts of the foreground so that it keeps the same speed at a new frame
+    /// rate.
+    pub fn rescale_frame_rate(&mut self, old_rate: Hertz, new_rate: Hertz) {
+        self.frames.rescale_frame_rate(old_rate, new_rate);
+        self.step_frames.rescale_frame_rate(old_rate, new_rate);
+    }
+
+    /// Changes the direction of the foreground. When the direction is reversed, the Scanner's bar
+    /// and scrolling text turn around from where they are.
+    pub fn set_direction(&mut self, direction: Direction) {
+        if self.direction.is_reversed_by(direction) {
+            match self.mode {
+                Mode::Scanner => self.frames.mirror(),
+                Mode::ScrollingText => {
+                    let width = self.matrix.map_or(0, |matrix| matrix.width);
+                    let scroll_length = (width + text::text_width(self.text)).max(1);
+                    self.scroll_position = scroll_length - self.scroll_position % scroll_length;
+                }
+                _ => {}
+            }
+        }
+        self.direction = direction;
+    }
+
+    /// Changes how fast the foreground runs, rescaling its frame and step counts.
+    pub fn set_speed(&mut self, speed: Speed) {
+        self.frames.rescale_speed(self.speed, speed);
+        self.step_frames.rescale_speed(self.speed, speed);
+        self.speed = speed;
+    }
+
+    /// Copies the externally controlled state of another foreground, such as its offset,
+    /// brightness and speed, so that replacing it with this one doesn't visibly reset those
+    /// settings.
+    pub fn carry_over_state(&mut self, other: &Self) {
+        self.offset = other.offset;
+        self.brightness = other.brightness;
+        self.matrix = other.matrix;
+        self.set_speed(other.speed);
+    }
+
+    /// Returns whether the foreground has finished the cycles its run mode asks for.
+    pub fn is_complete(&self) -> bool {
+        self.run_mode.is_complete(self.cycles_completed)
+    }
+
+    /// Starts the foreground's run over from the first frame of its first cycle.
+    pub fn restart_run(&mut self) {
+        self.frames.reset();
+        self.cycles_completed = 0;
+    }
+
+    /// Draws the next frame of the foreground. Returns whether the foreground completed a cycle
+    /// of its frames.
+    pub fn update(&mut self, segment: &mut [RGB8]) -> bool {
+        if self.is_complete() {
+            // a cleared foreground leaves the layer beneath it as it is:
+            if self.run_mode != RunMode::OneShotClear {
+                self.clone().draw(segment);
+            }
+            return false;
+        }
+        if self.frames.is_last_frame() && self.run_mode.is_complete(self.cycles_completed + 1) {
+            // the last frame is drawn from a copy of the foreground, which leaves it in the same
+            // state so that it can be drawn again while it is held:
+            self.cycles_completed += 1;
+            self.clone().draw(segment);
+            return true;
+        }
+
+        self.draw(segment);
+        if self.frames.is_last_frame() {
+            self.cycles_completed = self.cycles_completed.saturating_add(1);
         }
         let did_roll = self.frames.checked_increment();
         if did_roll {
             self.rainbow.increment();
         }
+        did_roll
+    }
+
+    /// Draws a frame with the updater of the mode, or with the effect of a Custom mode.
+    fn draw(&mut self, segment: &mut [RGB8]) {
+        match (self.mode, self.updater) {
+            (Mode::Custom(animation), _) => animation.update_foreground(self, segment),
+            (_, Some(f)) => f(self, segment),
+            (_, None) => {}
+        }
+    }
+
+    /// Returns the direction the foreground is moving in.
+    pub fn direction(&self) -> Direction {
+        self.direction
     }
 
     pub fn reset_trigger(&mut self) {
         self.has_been_triggered = false;
     }
 
+    /// Sets the progress the ProgressBar mode moves towards, from 0 for an empty bar to
+    /// MAX_OFFSET for a full one.
+    pub fn set_progress(&mut self, progress: u16) {
+        self.target_progress = progress;
+    }
+
+    /// Slides the progress of the bar towards its target, covering the whole bar over the frame
+    /// count of the foreground.
+    fn update_progress(&mut self) {
+        let step = (MAX_OFFSET as usize / self.frames.frames_per_pass().max(1)).max(1) as u16;
+        self.progress = match self.progress <= self.target_progress {
+            true => self.progress.saturating_add(step).min(self.target_progress),
+            false => self.progress.saturating_sub(step).max(self.target_progress),
+        };
+    }
+
+    /// Lights the LEDs up to a level from 0 to MAX_OFFSET with the color for each position along
+    /// the bar. A negative direction fills the bar from the far end.
+    fn fill_bar(&self, segment: &mut [RGB8], level: u16, color_at: impl Fn(usize) -> RGB8) {
+        let led_count = segment.len();
+        // the length of the bar in 256ths of an LED, so the tip can be partly lit:
+        let bar_length = level as usize * led_count * 256 / MAX_OFFSET as usize;
+        for position in 0..led_count {
+            let lit = bar_length.saturating_sub(position * 256).min(256);
+            if lit == 0 {
+                break;
+            }
+            let index = match self.direction {
+                Direction::Negative => led_count - 1 - position,
+                _ => position,
+            };
+            segment[index] = color_lerp(lit as i32, 0, 256, segment[index], color_at(position));
+        }
+    }
+
+    /// Moves the meter and peak levels towards the input level. Levels jump up immediately, but
+    /// fall at a rate based on the frame count of the foreground.
+    fn update_meter_levels(&mut self) {
+        let decay = (u8::MAX as usize / self.frames.frames_per_pass().max(1)).max(1) as u8;
+
+        self.meter_level = match self.input_level >= self.meter_level {
+            true => self.input_level,
+            false => self.meter_level.saturating_sub(decay).max(self.input_level),
+        };
+
+        if self.meter_level >= self.peak_level {
+            self.peak_level = self.meter_level;
+            self.step_frames.reset();
+        } else if self.step_frames.total <= 1 || self.step_frames.checked_increment() {
+            // the hold time has run out, so let the peak fall with the meter:
+            self.peak_level = self.peak_level.saturating_sub(decay).max(self.meter_level);
+        }
+    }
+
+    /// Returns the color of the rainbow at an LED of the TheaterChase mode. The colors between
+    /// the rainbow's steps are blended, and shift towards the next color as the frames progress.
+    fn chase_color(&self, index: usize, led_count: usize) -> RGB8 {
+        const BLEND_STEPS: usize = 256;
+        let rainbow = &self.rainbow.backer;
+        let color_count = rainbow.len();
+        if color_count == 0 {
+            return BLACK;
+        }
+        let span = color_count * BLEND_STEPS;
+        let along = index * span * self.subdivisions.max(1) / led_count.max(1);
+        let rotation = self.frames.get_current() * BLEND_STEPS / self.frames.total.max(1);
+        let position = (along + rotation) % span;
+
+        let start_index =
+            (position / BLEND_STEPS + self.rainbow.position.get_current()) % color_count;
+        let end_index = (start_index + 1) % color_count;
+        color_lerp(
+            (position % BLEND_STEPS) as i32,
+            0,
+            BLEND_STEPS as i32,
+            rainbow[start_index],
+            rainbow[end_index],
+        )
+    }
+
     fn increment_marquee_step(&mut self) {
         // Increment and check to see if the color rolls over:
         let did_roll = self.step_frames.checked_increment();
@@ -222,4 +764,7 @@ impl<'a> FadeRainbow for Foreground<'a> {
     fn frames(&self) -> &Progression {
         &self.frames
     }
+    fn easing(&self) -> Easing {
+        self.easing
+    }
 }
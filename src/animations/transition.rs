@@ -0,0 +1,104 @@
+use crate::animations::{background, foreground, Direction};
+use crate::colors::ManipulatableColor;
+use crate::utility::{convert_ms_to_frames, scramble, Progression};
+use embedded_time::rate::Hertz;
+use rgb::RGB8;
+
+/// These are the ways an animation can change from its current background and foreground to new
+/// ones when `transition_to()` is called.
+#[derive(Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Style {
+    /// Fades every LED from the old animation to the new one at the same time.
+    Crossfade,
+
+    /// Sweeps the new animation across the LEDs. Positive starts at the first LED, Negative starts
+    /// at the last LED, and Stopped starts in the middle and moves out to both ends.
+    Wipe(Direction),
+
+    /// Switches each LED to the new animation at a different random time.
+    Dissolve,
+}
+
+/// This holds the outgoing background and foreground while an animation transitions to new ones.
+pub struct Transition<'a> {
+    pub bg_state: background::Background<'a>,
+    pub fg_state: foreground::Foreground<'a>,
+    frames: Progression,
+    style: Style,
+    is_finished: bool,
+}
+
+impl<'a> Transition<'a> {
+    pub fn new(
+        bg_state: background::Background<'a>,
+        fg_state: foreground::Foreground<'a>,
+        style: Style,
+        duration_ms: u64,
+        frame_rate: Hertz,
+    ) -> Self {
+        let frames = Progression::new(convert_ms_to_frames(duration_ms, frame_rate));
+
+        Self {
+            bg_state,
+            fg_state,
+            frames,
+            style,
+            is_finished: frames.total == 0,
+        }
+    }
+
+    /// Rescales the frame counts of the transition and the outgoing layers so that they keep the
+    /// same speed at a new frame rate.
+    pub fn rescale_frame_rate(&mut self, old_rate: Hertz, new_rate: Hertz) {
+        self.frames.rescale_frame_rate(old_rate, new_rate);
+        self.bg_state.rescale_frame_rate(old_rate, new_rate);
+        self.fg_state.rescale_frame_rate(old_rate, new_rate);
+        self.is_finished |= self.frames.total == 0;
+    }
+
+    /// Returns whether the last frame of the transition has been mixed, so the incoming layers
+    /// can be shown on their own from the next frame on.
+    pub fn is_finished(&self) -> bool {
+        self.is_finished
+    }
+
+    /// Returns whether the incoming layers make up at least half of the mix.
+    pub fn is_past_halfway(&self) -> bool {
+        2 * self.frames.get_current() >= self.frames.total
+    }
+
+    /// Mixes the outgoing colors into the segment, which already holds the incoming colors, based
+    /// on how far along the transition is. The last frame mixed is still partly the outgoing
+    /// colors, and the transition is finished after it.
+    pub fn mix(&mut self, segment: &mut [RGB8], outgoing: &[RGB8]) {
+        let led_count = segment.len();
+        let current = self.frames.get_current();
+        let total = self.frames.total.max(1);
+        let boundary = led_count * current / total;
+
+        for (index, (led, &old)) in segment.iter_mut().zip(outgoing.iter()).enumerate() {
+            let show_new = match self.style {
+                Style::Crossfade => {
+                    *led = old.lerp_with(*led, self.frames);
+                    continue;
+                }
+                Style::Wipe(Direction::Positive) => index < boundary,
+                Style::Wipe(Direction::Negative) => index >= led_count - boundary,
+                Style::Wipe(Direction::Stopped) => {
+                    let distance_from_center = (2 * index + 1).abs_diff(led_count);
+                    distance_from_center < boundary
+                }
+                Style::Dissolve => (scramble(index as u32) as usize % total) < current,
+            };
+            if !show_new {
+                *led = old;
+            }
+        }
+        match current + 1 >= self.frames.total {
+            true => self.is_finished = true,
+            false => self.frames.increment(),
+        }
+    }
+}
@@ -1,11 +1,18 @@
-use crate::animations::{Direction, MAX_OFFSET};
+use crate::animations::{AnimationType, Direction, ParameterError, MAX_OFFSET};
 use crate::colors;
-use crate::colors::ManipulatableColor;
+use crate::colors::FULL_BRIGHTNESS;
+use crate::colors::{BlendMode, ManipulatableColor};
+use crate::easing::{Easing, EASING_SCALE};
+use crate::fixed::Q16;
+use crate::mapping::Matrix;
 use crate::utility::{
-    convert_ns_to_frames, get_random_offset, shift_offset, FadeRainbow, MarchingRainbow,
-    MarchingRainbowMut, Progression, StatefulRainbow, TimedRainbows,
+    convert_ms_to_frames, convert_ns_to_frames, convert_ns_to_frames_x256, rescale_frames,
+    rescale_speed, rounds_to_no_frames, scramble, shift_offset, FadeRainbow, MarchingRainbow,
+    MarchingRainbowMut, Progression, Random, Speed, StatefulRainbow, TimedRainbows,
 };
 use arrayvec::ArrayVec;
+use core::mem::{discriminant, Discriminant};
+use embedded_time::fixed_point::FixedPoint;
 use embedded_time::rate::Hertz;
 use rgb::RGB8;
 
@@ -17,7 +24,8 @@ pub type TriggerBehavior = (Option<TriggerInit>, Option<TriggerUpdater>);
 /// be mixed and matched at any time over any combination of foreground and background animations.
 /// The trigger animation colors will override any foreground or background pixel data on the pixels
 /// it effects.
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Mode {
     /// This is a fallback value that doesn't have any trigger effect.
     NoTrigger,
@@ -61,6 +69,12 @@ pub enum Mode {
     /// of a rainbow.
     ColorShotRainbow,
 
+    /// This will launch a comet with a bright head and a fading tail that travels once around the
+    /// segment in the trigger's direction over the fade in plus fade out time, starting at the
+    /// starting offset. The tail is `pixels_per_pixel_group` LEDs long. Each comet will be a new
+    /// color in the order of the rainbow.
+    Comet,
+
     /// This will flash all the LEDs to a single color for a short time.
     /// Fade in and out times can be adjusted separately.
     Flash,
@@ -76,6 +90,38 @@ pub enum Mode {
     /// Each flash will be a new color in the order of the rainbow.
     FlashRainbow,
 
+    /// This will make random LEDs sparkle in random colors from the trigger rainbow, with about
+    /// `density` sparkles per second across the whole segment. Each sparkle lasts a random time
+    /// up to SPARKLE_WINDOW_MS. The sparkles get denser over the fade in time and thin out again
+    /// over the fade out time.
+    Sparkle,
+
+    /// This will strobe the LEDs `strobe_count` times in the given color, starting a new flash
+    /// every `strobe_period_ns` and keeping each one lit for `strobe_duty_cycle` of the period.
+    /// The LEDs are left alone between flashes, and the fade times are ignored. Each flash is
+    /// lit for at least one frame, and the flashes are spaced to the nearest frame without
+    /// drifting, so a strobe faster than the frame rate blurs into a steady color.
+    Strobe(RGB8),
+
+    /// This will sweep the given color across the LEDs from the first LED to the last over the
+    /// fade in time, or from the last LED to the first when the direction is negative. Once the
+    /// LEDs are covered, the color decays, sustains and fades out like a Flash. If
+    /// `leaves_color_behind` is set, the color stays on as the background's wash when the
+    /// trigger finishes.
+    Wipe(RGB8),
+
+    /// This works the same as Wipe, but sweeps the color outwards from the center of the LEDs
+    /// towards both ends at once.
+    WipeCenterOut(RGB8),
+
+    /// This will send a ring outwards from `ripple_origin`, growing by `ripple_speed` LEDs every
+    /// second, like the ripple from a key press or a touch. The ring is `pixels_per_pixel_group`
+    /// LEDs wide with soft edges, and it fades in and out over the trigger's fade times. On an
+    /// animation with a matrix the ring spreads out in 2D, and otherwise it runs both ways along
+    /// the LEDs. Each ripple will be a new color in the order of the rainbow.
+    Ripple,
+
+    #[cfg_attr(feature = "serde", serde(skip))]
     Custom(TriggerBehavior),
 }
 
@@ -91,19 +137,149 @@ impl Mode {
             Mode::ColorShot => (Some(init_color_shot), Some(color_shot)),
             Mode::ColorShotFade => (Some(init_color_shot_fade), Some(color_shot)),
             Mode::ColorShotRainbow => (Some(init_color_shot_rainbow), Some(color_shot)),
+            Mode::Comet => (Some(init_color_shot_rainbow), Some(comet)),
             Mode::Flash => (Some(init_flash), Some(flash)),
             Mode::FlashFade => (Some(init_flash_fade), Some(flash)),
             Mode::FlashRainbow => (Some(init_flash_rainbow), Some(flash)),
+            Mode::Sparkle => (Some(init_sparkle), Some(sparkle)),
+            Mode::Strobe(_) => (Some(init_strobe), Some(strobe)),
+            Mode::Wipe(_) | Mode::WipeCenterOut(_) => (Some(init_mode_color), Some(wipe)),
+            Mode::Ripple => (Some(init_ripple), Some(ripple)),
             Mode::Custom((i, u)) => (i, u),
         }
     }
 }
 
+/// The longest time that a single sparkle of the Sparkle mode can last.
+pub const SPARKLE_WINDOW_MS: u64 = 250;
+
+/// The number of colors from the start of the trigger rainbow that the Sparkle mode will use.
+pub const MAX_SPARKLE_COLORS: usize = 8;
+
+/// The most steps of trigger sequences that can be waiting to fire at once in each animation.
+pub const MAX_PENDING_STEPS: usize = 8;
+
+/// The most trigger modes whose last trigger is remembered for limiting retriggers. When more
+/// modes than this are triggered within the retrigger interval, the mode that is closest to the end
+/// of its interval is forgotten early.
+pub const MAX_RETRIGGER_MODES: usize = 8;
+
+/// One step of a trigger sequence, such as a flash followed by a wipe and then a fade.
+#[derive(Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SequenceStep {
+    /// How long to wait after the previous step fires, or after the sequence starts for the first
+    /// step.
+    pub delay_ns: u64,
+    pub parameters: Parameters,
+}
+
+/// A step of a sequence that is waiting to fire.
+struct PendingStep {
+    frames_remaining: usize,
+    frame_rate: Hertz,
+    parameters: Parameters,
+}
+
+/// Decides what happens when a new trigger is added while the maximum number of triggers are
+/// already running.
+#[derive(Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum EvictionPolicy {
+    /// The new trigger is dropped and the running triggers are left alone.
+    RejectNewest,
+
+    /// The trigger that has been running the longest is replaced by the new trigger.
+    ReplaceOldest,
+
+    /// The running trigger with the lowest priority is replaced by the new trigger, unless every
+    /// running trigger has a higher priority than the new one, in which case the new trigger is
+    /// dropped. The oldest trigger is replaced when several share the lowest priority.
+    ReplaceLowestPriority,
+}
+
+/// Decides what happens to a trigger that comes within `retrigger_interval_ns` of the last trigger
+/// with the same mode.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum RetriggerPolicy {
+    /// The new trigger is dropped, so the mode fires at most once per interval.
+    Drop,
+
+    /// The newest running trigger of the same mode is replaced by the new one, so that a burst of
+    /// triggers shows as a single trigger that keeps restarting. The new trigger is added as usual
+    /// if none of the same mode are still running.
+    Coalesce,
+}
+
 /// All triggers share a single rainbow / fade speed, which is configured in this struct
+#[derive(Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct GlobalParameters<'a> {
+    /// The rainbow is borrowed, so it is skipped when serializing. Store it separately as a
+    /// `colors::RainbowBuf` to save it along with the parameters. Deserialized parameters start
+    /// with `colors::R_WHITE` until the rainbow is set, so the modes always have a color to draw.
+    #[cfg_attr(
+        feature = "serde",
+        serde(skip, default = "crate::colors::deserialized_rainbow")
+    )]
     pub rainbow: colors::Rainbow<'a>,
     pub is_rainbow_forward: bool,
+    /// The color space used when fading between the colors of the rainbow.
+    pub color_space: colors::ColorSpace,
+    /// How the next color of the rainbow is picked, such as at random instead of in order.
+    #[cfg_attr(feature = "serde", serde(borrow))]
+    pub color_order: colors::ColorOrder<'a>,
     pub duration_ns: u64,
+    /// How opaque the triggers are when drawn over the foreground and background, from
+    /// `colors::TRANSPARENT` to `colors::OPAQUE`.
+    pub alpha: u8,
+    /// What to do with new triggers when the maximum number of triggers are already running.
+    pub eviction_policy: EvictionPolicy,
+    /// Triggers that come less than this long after the last trigger with the same mode are
+    /// handled by `retrigger_policy`, so that key repeats don't flood the trigger slots. 0 lets
+    /// every trigger through. Steps of trigger sequences are never limited.
+    pub retrigger_interval_ns: u64,
+    /// What to do with triggers that come too soon after the last one of the same mode.
+    pub retrigger_policy: RetriggerPolicy,
+    /// Seeds the random positions, sparkles and color orders of the triggers, see
+    /// `utility::Random`. 0 uses the global generator.
+    pub seed: u32,
+}
+
+impl GlobalParameters<'_> {
+    /// Checks the settings shared by all triggers, see `AnimationParameters::validate()`. Every
+    /// trigger starts from the current rainbow color, so the rainbow can't be empty whichever
+    /// modes are triggered.
+    pub fn validate(&self, frame_rate: Hertz) -> Result<(), ParameterError> {
+        if self.rainbow.is_empty() {
+            return Err(ParameterError::EmptyRainbow(AnimationType::Trigger));
+        }
+        if rounds_to_no_frames(self.duration_ns, frame_rate) {
+            return Err(ParameterError::DurationUnderOneFrame(
+                AnimationType::Trigger,
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// The reasons a trigger can fail to start.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum TriggerError {
+    /// Every trigger slot was in use, and the eviction policy didn't free one for the new trigger.
+    Full,
+
+    /// A trigger with the same mode started less than `retrigger_interval_ns` ago, and the
+    /// retrigger policy drops triggers that come too soon.
+    RateLimited,
+
+    /// The parameters of the trigger failed `Parameters::validate()`.
+    InvalidParameters(ParameterError),
 }
 
 /// This holds all triggers and contains the variables that apply to all triggers simultaneously, and not just to
@@ -112,50 +288,359 @@ pub struct TriggerCollection<'a, const N: usize> {
     pub fade_rainbow: StatefulRainbow<'a>,
     pub incremental_rainbow: StatefulRainbow<'a>,
     pub frames: Progression,
+    pub alpha: u8,
+    pub brightness: u8,
+    /// The geometry of the animation, which the Ripple mode uses to spread out in 2D.
+    pub matrix: Option<Matrix>,
+    eviction_policy: EvictionPolicy,
+    retrigger_frames: usize,
+    retrigger_policy: RetriggerPolicy,
+    /// The modes triggered within the retrigger interval, and the frames left until they can be
+    /// triggered freely again.
+    recent_modes: ArrayVec<(Discriminant<Mode>, usize), MAX_RETRIGGER_MODES>,
+    speed: Speed,
+    random: Random,
     triggers: ArrayVec<Trigger, N>,
+    pending_steps: ArrayVec<PendingStep, MAX_PENDING_STEPS>,
+    left_behind_color: Option<RGB8>,
 }
 
 impl<'a, const N: usize> TriggerCollection<'a, N> {
     pub fn new(init: &GlobalParameters<'a>, frame_rate: Hertz) -> Self {
-        let fade_rainbow = StatefulRainbow::new(init.rainbow, init.is_rainbow_forward);
-        let incremental_rainbow = StatefulRainbow::new(init.rainbow, init.is_rainbow_forward);
+        let mut fade_rainbow = StatefulRainbow::new(init.rainbow, init.is_rainbow_forward);
+        fade_rainbow.color_space = init.color_space;
+        fade_rainbow.set_seed(scramble(init.seed));
+        fade_rainbow.set_order(init.color_order);
+        let mut incremental_rainbow = StatefulRainbow::new(init.rainbow, init.is_rainbow_forward);
+        incremental_rainbow.set_seed(scramble(scramble(init.seed)));
+        incremental_rainbow.set_order(init.color_order);
         let frames = Progression::new(convert_ns_to_frames(init.duration_ns, frame_rate));
+        let alpha = init.alpha;
         let triggers = ArrayVec::new();
 
         Self {
             fade_rainbow,
             incremental_rainbow,
             frames,
+            alpha,
+            brightness: colors::FULL_BRIGHTNESS,
+            matrix: None,
+            eviction_policy: init.eviction_policy,
+            retrigger_frames: retrigger_frames(init.retrigger_interval_ns, frame_rate),
+            retrigger_policy: init.retrigger_policy,
+            recent_modes: ArrayVec::new(),
+            speed: Speed::NORMAL,
+            random: Random::new(init.seed),
             triggers,
+            pending_steps: ArrayVec::new(),
+            left_behind_color: None,
+        }
+    }
+
+    /// Replaces the global trigger parameters. Triggers that are already running keep the color
+    /// they were started with.
+    pub fn set_parameters(&mut self, init: &GlobalParameters<'a>, frame_rate: Hertz) {
+        let frame_count = convert_ns_to_frames(init.duration_ns, frame_rate);
+
+        if self.random.seed() != init.seed {
+            self.random = Random::new(init.seed);
+            self.fade_rainbow.set_seed(scramble(init.seed));
+            self.incremental_rainbow
+                .set_seed(scramble(scramble(init.seed)));
+        }
+        self.fade_rainbow
+            .set_rainbow(init.rainbow, init.is_rainbow_forward);
+        self.fade_rainbow.color_space = init.color_space;
+        self.fade_rainbow.set_order(init.color_order);
+        self.incremental_rainbow
+            .set_rainbow(init.rainbow, init.is_rainbow_forward);
+        self.incremental_rainbow.set_order(init.color_order);
+        self.frames.set_total(self.speed.scale_frames(frame_count));
+        self.alpha = init.alpha;
+        self.eviction_policy = init.eviction_policy;
+        self.retrigger_frames = retrigger_frames(init.retrigger_interval_ns, frame_rate);
+        self.retrigger_policy = init.retrigger_policy;
+    }
+
+    /// Rescales the frame counts of the collection and every running trigger so that they keep the
+    /// same speed at a new frame rate.
+    pub fn rescale_frame_rate(&mut self, old_rate: Hertz, new_rate: Hertz) {
+        self.frames.rescale_frame_rate(old_rate, new_rate);
+        for trigger in self.triggers.iter_mut() {
+            trigger.rescale_frame_rate(old_rate, new_rate);
+        }
+        for step in self.pending_steps.iter_mut() {
+            // a step that was still waiting fires on the next update at the soonest:
+            step.frames_remaining =
+                rescale_frames(step.frames_remaining, old_rate, new_rate).max(1);
+            step.frame_rate = new_rate;
+        }
+        // a limit that was on stays on for at least a frame, however far the frame rate drops:
+        if self.retrigger_frames > 0 {
+            self.retrigger_frames =
+                rescale_frames(self.retrigger_frames, old_rate, new_rate).max(1);
+        }
+        for (_, frames_left) in self.recent_modes.iter_mut() {
+            *frames_left = rescale_frames(*frames_left, old_rate, new_rate).max(1);
+        }
+    }
+
+    /// Changes how fast the triggers run, rescaling the collection's frame count and every running
+    /// trigger. The delays between the steps of a sequence are not affected.
+    pub fn set_speed(&mut self, speed: Speed) {
+        self.frames.rescale_speed(self.speed, speed);
+        for trigger in self.triggers.iter_mut() {
+            trigger.rescale_speed(self.speed, speed);
+        }
+        self.speed = speed;
+    }
+
+    /// Starts a sequence of triggers that fire one after another. Steps without a delay fire
+    /// straight away, and the rest fire during later updates. Steps that would go over
+    /// MAX_PENDING_STEPS are dropped. Only modes drawn by the collection can be used in a
+    /// sequence, so steps with the NoTrigger, Background or Foreground modes only add their delay,
+    /// as do steps whose parameters fail `Parameters::validate()`.
+    pub fn add_sequence(&mut self, steps: &[SequenceStep], frame_rate: Hertz) {
+        let mut delay_ns = 0;
+        for step in steps {
+            delay_ns += step.delay_ns;
+            let frames_remaining = convert_ns_to_frames(delay_ns, frame_rate);
+            if frames_remaining == 0 {
+                self.add_sequence_trigger(&step.parameters, frame_rate);
+                continue;
+            }
+            let _ = self.pending_steps.try_push(PendingStep {
+                frames_remaining,
+                frame_rate,
+                parameters: step.parameters,
+            });
+        }
+    }
+
+    /// Stops any sequences that are still waiting to fire their remaining steps.
+    pub fn cancel_sequences(&mut self) {
+        self.pending_steps.clear();
+    }
+
+    /// Stops every running trigger, along with any sequence steps that haven't fired yet. The
+    /// stopped triggers end straight away without fading out, and don't report that they finished.
+    pub fn clear_triggers(&mut self) {
+        self.triggers.clear();
+        self.cancel_sequences();
+    }
+
+    /// Stops the running triggers with the given mode, along with any sequence steps with that
+    /// mode that haven't fired yet. Like `clear_triggers()`, they end without fading out.
+    pub fn cancel_triggers_of_mode(&mut self, mode: Mode) {
+        let is_other_mode = |other: &Mode| discriminant(other) != discriminant(&mode);
+        self.triggers.retain(|t| is_other_mode(&t.mode));
+        self.pending_steps
+            .retain(|step| is_other_mode(&step.parameters.mode));
+    }
+
+    /// Returns the number of triggers that are running. Sequence steps that haven't fired yet are
+    /// not counted.
+    pub fn active_trigger_count(&self) -> usize {
+        self.triggers.len()
+    }
+
+    fn add_sequence_trigger(&mut self, init: &Parameters, frame_rate: Hertz) {
+        match init.mode {
+            Mode::NoTrigger | Mode::Background | Mode::Foreground => {}
+            _ if init.validate(frame_rate).is_err() => {
+                trace!("sequence step skipped, invalid parameters: {}", init.mode);
+            }
+            _ => {
+                let _ = self.start_trigger(init, frame_rate);
+            }
+        }
+    }
+
+    /// Counts down the waiting sequence steps, firing the ones that are due.
+    fn fire_due_steps(&mut self) {
+        let mut index = 0;
+        while index < self.pending_steps.len() {
+            let step = &mut self.pending_steps[index];
+            step.frames_remaining = step.frames_remaining.saturating_sub(1);
+            if step.frames_remaining > 0 {
+                index += 1;
+                continue;
+            }
+            let step = self.pending_steps.remove(index);
+            self.add_sequence_trigger(&step.parameters, step.frame_rate);
+        }
+    }
+
+    /// Starts a new trigger. A trigger that comes too soon after the last one with the same mode
+    /// is handled by the retrigger policy, and TriggerError::RateLimited is returned if it is
+    /// dropped. When every slot is in use, the eviction policy decides whether a running trigger
+    /// makes room for it, and TriggerError::Full is returned if none does. Parameters that fail
+    /// validation are rejected with TriggerError::InvalidParameters before any of that.
+    pub fn add_trigger(
+        &mut self,
+        init: &Parameters,
+        frame_rate: Hertz,
+    ) -> Result<(), TriggerError> {
+        init.validate(frame_rate)
+            .map_err(TriggerError::InvalidParameters)?;
+        let mode = discriminant(&init.mode);
+        if self.recent_modes.iter().any(|&(recent, _)| recent == mode) {
+            match self.retrigger_policy {
+                RetriggerPolicy::Drop => {
+                    trace!("trigger dropped, too soon after the last: {}", init.mode);
+                    return Err(TriggerError::RateLimited);
+                }
+                RetriggerPolicy::Coalesce => {
+                    let newest = self
+                        .triggers
+                        .iter()
+                        .rposition(|t| discriminant(&t.mode) == mode);
+                    if let Some(index) = newest {
+                        trace!("trigger coalesced: {}", init.mode);
+                        self.triggers.remove(index);
+                    }
+                }
+            }
+        }
+        self.start_trigger(init, frame_rate)?;
+        self.note_triggered(mode);
+        Ok(())
+    }
+
+    /// Remembers that a mode was just triggered, so that it is limited for the retrigger interval.
+    fn note_triggered(&mut self, mode: Discriminant<Mode>) {
+        if self.retrigger_frames == 0 {
+            return;
         }
+        let frames_left = self.retrigger_frames;
+        if let Some(recent) = self
+            .recent_modes
+            .iter_mut()
+            .find(|(recent, _)| *recent == mode)
+        {
+            recent.1 = frames_left;
+            return;
+        }
+        if self.recent_modes.is_full() {
+            let soonest_free = self
+                .recent_modes
+                .iter()
+                .enumerate()
+                .min_by_key(|(_, &(_, frames_left))| frames_left)
+                .map(|(index, _)| index);
+            if let Some(index) = soonest_free {
+                self.recent_modes.remove(index);
+            }
+        }
+        self.recent_modes.push((mode, frames_left));
     }
 
-    pub fn add_trigger(&mut self, init: &Parameters, frame_rate: Hertz) {
+    /// Starts a new trigger without checking how recently its mode was triggered.
+    fn start_trigger(&mut self, init: &Parameters, frame_rate: Hertz) -> Result<(), TriggerError> {
         let (initializer, updater) = init.mode.get_behavior();
-        let mut new_trigger = Trigger::new(init, self.current_rainbow_color(), frame_rate);
+        // a trigger with its own rainbow reads it from copies of the shared rainbows, so that
+        // advancing them doesn't move the shared ones:
+        let own_rainbow = init.rainbow.filter(|rainbow| !rainbow.is_empty());
+        let mut own_rainbows = own_rainbow.map(|rainbow| {
+            let mut fade_rainbow = self.fade_rainbow.clone();
+            fade_rainbow.set_rainbow(rainbow, fade_rainbow.backer.is_forward());
+            let mut incremental_rainbow = self.incremental_rainbow.clone();
+            incremental_rainbow.set_rainbow(rainbow, incremental_rainbow.backer.is_forward());
+            (fade_rainbow, incremental_rainbow, self.frames)
+        });
+        let mut rainbows = match &mut own_rainbows {
+            Some((fade_rainbow, incremental_rainbow, frames)) => TimedRainbows {
+                fade_rainbow,
+                incremental_rainbow,
+                frames,
+                random: &mut self.random,
+            },
+            None => TimedRainbows {
+                fade_rainbow: &mut self.fade_rainbow,
+                incremental_rainbow: &mut self.incremental_rainbow,
+                frames: &mut self.frames,
+                random: &mut self.random,
+            },
+        };
+        let color = rainbows.incremental_rainbow.current_color();
+        let mut new_trigger = Trigger::new(init, color, frame_rate);
+        new_trigger.matrix = self.matrix;
+        new_trigger.rescale_speed(Speed::NORMAL, self.speed);
 
         if let Some(initialize) = initializer {
-            initialize(
-                &mut new_trigger,
-                &mut TimedRainbows {
-                    fade_rainbow: &mut self.fade_rainbow,
-                    incremental_rainbow: &mut self.incremental_rainbow,
-                    frames: &mut self.frames,
-                },
-            );
+            initialize(&mut new_trigger, &mut rainbows);
         }
         new_trigger.updater = updater;
 
-        let _ = self.triggers.try_push(new_trigger);
+        if self.triggers.is_full() && !self.evict_for(new_trigger.priority) {
+            trace!("trigger dropped, every slot is in use: {}", init.mode);
+            return Err(TriggerError::Full);
+        }
+        trace!("trigger added: {}", init.mode);
+        self.triggers
+            .try_push(new_trigger)
+            .map_err(|_| TriggerError::Full)
+    }
+
+    /// Removes a running trigger according to the eviction policy to make room for a new trigger
+    /// with the given priority. Returns false if no trigger was removed.
+    fn evict_for(&mut self, priority: u8) -> bool {
+        let evicted_index = match self.eviction_policy {
+            EvictionPolicy::RejectNewest => None,
+            EvictionPolicy::ReplaceOldest => Some(0),
+            EvictionPolicy::ReplaceLowestPriority => self
+                .triggers
+                .iter()
+                .enumerate()
+                .min_by_key(|(_, t)| t.priority)
+                .filter(|(_, t)| t.priority <= priority)
+                .map(|(i, _)| i),
+        };
+        match evicted_index {
+            Some(index) if index < self.triggers.len() => {
+                trace!("trigger evicted: {}", self.triggers[index].mode);
+                self.triggers.remove(index);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns the color left behind by the last wipe that finished since this was last called,
+    /// which should become the new base of the background.
+    pub fn take_left_behind_color(&mut self) -> Option<RGB8> {
+        self.left_behind_color.take()
     }
 
-    pub fn update(&mut self, segment: &mut [RGB8]) {
+    /// Draws every running trigger onto the segment. The scratch layer must be as long as the
+    /// segment, and is used by triggers with a blend mode other than Normal. `on_finished` is
+    /// called with the mode of each trigger that finishes during the update.
+    pub fn update(
+        &mut self,
+        segment: &mut [RGB8],
+        scratch: &mut [RGB8],
+        mut on_finished: impl FnMut(Mode),
+    ) {
+        self.fire_due_steps();
+        for (_, frames_left) in self.recent_modes.iter_mut() {
+            *frames_left = frames_left.saturating_sub(1);
+        }
+        self.recent_modes
+            .retain(|(_, frames_left)| *frames_left > 0);
         for trigger in self.triggers.iter_mut() {
-            trigger.update(segment)
+            trigger.update(segment, scratch)
         }
 
-        self.triggers
-            .retain(|t| t.frames.get_current() < t.frames.total - 1);
+        self.triggers.retain(|t| {
+            let is_running = t.frames.get_current() < t.frames.total.saturating_sub(1);
+            if !is_running {
+                on_finished(t.mode);
+                if t.leaves_color_behind {
+                    self.left_behind_color = Some(t.color);
+                }
+            }
+            is_running
+        });
         let did_roll = self.frames.checked_increment();
         if did_roll {
             self.fade_rainbow.increment();
@@ -163,17 +648,124 @@ impl<'a, const N: usize> TriggerCollection<'a, N> {
     }
 }
 
+/// A range of LEDs within an animation's segment that a trigger is limited to.
+#[derive(Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct PixelRange {
+    pub start: usize,
+    pub length: usize,
+}
+
+impl PixelRange {
+    pub fn new(start: usize, length: usize) -> Self {
+        Self { start, length }
+    }
+
+    /// Returns the part of the segment covered by this range, cut short at the end of the segment.
+    pub fn slice_of<'s>(&self, segment: &'s mut [RGB8]) -> &'s mut [RGB8] {
+        let start = self.start.min(segment.len());
+        let end = start.saturating_add(self.length).min(segment.len());
+        &mut segment[start..end]
+    }
+}
+
+/// Where the ring of the Ripple mode starts from.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum RippleOrigin {
+    /// The index of an LED in the segment.
+    Led(usize),
+    /// The (x, y) position of an LED on the animation's matrix. Without a matrix, this is the
+    /// LED at index x.
+    Point(usize, usize),
+}
+
 /// This contains all the information necessary to set up and run a trigger animation. All
 /// aspects of the animation can be derived from these parameters and the
 /// AnimationGlobalTriggerParameters struct's parameters. Some parameters will not have an
 /// effect depending on the mode.
+#[derive(Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Parameters {
     pub mode: Mode,
     pub direction: Direction,
+    /// The curve followed by the trigger's fades and movement.
+    pub easing: Easing,
     pub fade_in_time_ns: u64,
     pub fade_out_time_ns: u64,
+    /// How long the trigger takes to fall from full brightness to `sustain_level` once it has
+    /// faded in.
+    pub decay_time_ns: u64,
+    /// The brightness the trigger holds between its decay and its fade out, up to
+    /// `colors::FULL_BRIGHTNESS`. It isn't used when the decay and sustain times are both 0, so
+    /// a trigger that only fades in and out fades out from full brightness.
+    pub sustain_level: u8,
+    /// How long the trigger holds at `sustain_level` before it fades out.
+    pub sustain_time_ns: u64,
     pub starting_offset: u16,
     pub pixels_per_pixel_group: usize,
+    /// Higher priority triggers can replace lower priority triggers when the maximum number of
+    /// triggers are running and the eviction policy is `ReplaceLowestPriority`.
+    pub priority: u8,
+    /// The number of sparkles per second for the Sparkle mode.
+    pub density: u16,
+    /// The number of flashes of the Strobe mode.
+    pub strobe_count: u16,
+    /// The time from the start of one flash of the Strobe mode to the start of the next, which is
+    /// one over the strobe frequency.
+    pub strobe_period_ns: u64,
+    /// The part of each period of the Strobe mode that the flash is lit for, where u8::MAX keeps
+    /// the LEDs lit for the whole period.
+    pub strobe_duty_cycle: u8,
+    /// Whether the Wipe modes leave their color behind as the new base of the background, see
+    /// `background::Wash::Solid`, so the wipe works as a change of scene.
+    pub leaves_color_behind: bool,
+    /// Where the ring of the Ripple mode starts from.
+    pub ripple_origin: RippleOrigin,
+    /// How many LEDs the ring of the Ripple mode grows by every second.
+    pub ripple_speed: u16,
+    /// Limits the trigger to a range of LEDs in the segment, such as the LEDs under a key that
+    /// was pressed. The trigger treats the range as if it were the whole segment. When `None`,
+    /// the trigger covers the whole segment.
+    pub target: Option<PixelRange>,
+    /// How the trigger's colors are combined with the colors beneath it, such as Add for
+    /// triggers that brighten the background instead of covering it.
+    pub blend_mode: BlendMode,
+    /// Colors the trigger from its own rainbow instead of the one in the GlobalParameters, such
+    /// as to give each key its own palette. The rainbow is read at the same position as the
+    /// shared rainbow, which is left where it is. Use a rainbow of one color for a fixed color.
+    /// The rainbow is `'static` so the parameters can still be queued from interrupts, and it is
+    /// skipped when serializing.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub rainbow: Option<colors::Rainbow<'static>>,
+}
+
+impl Parameters {
+    /// Checks the fades of a trigger for durations that are too short to show at the frame rate.
+    /// The Strobe mode keeps time in fractions of a frame, so its period is allowed to be short.
+    /// The trigger's own rainbow, if it has one, can't be empty.
+    pub fn validate(&self, frame_rate: Hertz) -> Result<(), ParameterError> {
+        if self.rainbow.is_some_and(|rainbow| rainbow.is_empty()) {
+            return Err(ParameterError::EmptyRainbow(AnimationType::Trigger));
+        }
+        let durations = [
+            self.fade_in_time_ns,
+            self.decay_time_ns,
+            self.sustain_time_ns,
+            self.fade_out_time_ns,
+        ];
+        if durations
+            .iter()
+            .any(|&duration| rounds_to_no_frames(duration, frame_rate))
+        {
+            return Err(ParameterError::DurationUnderOneFrame(
+                AnimationType::Trigger,
+            ));
+        }
+        Ok(())
+    }
 }
 
 /// This contains all the information needed to keep track of the current state of a trigger
@@ -182,43 +774,167 @@ pub struct Trigger {
     offset: u16,
     frames: Progression,
     transition_frame: usize,
+    decay_frames: usize,
+    sustain_frames: usize,
+    sustain_level: u8,
     direction: Direction,
+    easing: Easing,
     color: RGB8,
+    palette: ArrayVec<RGB8, MAX_SPARKLE_COLORS>,
     updater: Option<TriggerUpdater>,
     pixels_per_pixel_group: usize,
+    priority: u8,
+    seed: u32,
+    sparkle_window: usize,
+    sparkles_per_window_x256: usize,
+    strobe_period_x256: usize,
+    strobe_lit_x256: usize,
+    strobe_count: usize,
+    leaves_color_behind: bool,
+    ripple_origin: RippleOrigin,
+    ripple_frames_per_led_x256: usize,
+    matrix: Option<Matrix>,
+    target: Option<PixelRange>,
+    blend_mode: BlendMode,
+    mode: Mode,
 }
 
 impl Trigger {
     pub fn new(init: &Parameters, color: RGB8, frame_rate: Hertz) -> Self {
         let offset = init.starting_offset;
-        let total_duration_ns = init.fade_in_time_ns + init.fade_out_time_ns;
+        let transition_frame = convert_ns_to_frames(init.fade_in_time_ns, frame_rate);
+        let decay_frames = convert_ns_to_frames(init.decay_time_ns, frame_rate);
+        let sustain_frames = convert_ns_to_frames(init.sustain_time_ns, frame_rate);
+        let release_frames = convert_ns_to_frames(init.fade_out_time_ns, frame_rate);
 
-        let frames = convert_ns_to_frames(total_duration_ns, frame_rate);
-        let frames = Progression::new(frames);
+        // strobe periods are kept in 256ths of a frame, so the flashes don't drift away from
+        // their frequency when a period isn't a whole number of frames:
+        let strobe_period_x256 = convert_ns_to_frames_x256(init.strobe_period_ns, frame_rate);
+        let strobe_lit_x256 = match (init.strobe_count, init.strobe_duty_cycle) {
+            (0, _) | (_, 0) => 0,
+            (_, duty) => lit_for_a_frame(strobe_period_x256 * duty as usize / u8::MAX as usize),
+        };
 
-        let transition_frame = convert_ns_to_frames(init.fade_in_time_ns, frame_rate);
+        let ripple_frames_per_led_x256 = match init.ripple_speed {
+            0 => 0,
+            speed => frame_rate.integer() as usize * 256 / speed as usize,
+        };
+
+        let strobe_count = init.strobe_count as usize;
+        let frames = match init.mode {
+            Mode::Strobe(_) => strobe_frames(strobe_period_x256, strobe_count),
+            _ => transition_frame + decay_frames + sustain_frames + release_frames,
+        };
+        let frames = Progression::new(frames);
         let direction = init.direction;
+        let easing = init.easing;
         let updater = None;
 
         let pixels_per_pixel_group = init.pixels_per_pixel_group;
+        let priority = init.priority;
+
+        let sparkle_window = convert_ms_to_frames(SPARKLE_WINDOW_MS, frame_rate).max(1);
+        let sparkles_per_window_x256 =
+            (init.density as u64 * SPARKLE_WINDOW_MS * 256 / 1_000) as usize;
 
         Self {
             offset,
             frames,
             transition_frame,
+            decay_frames,
+            sustain_frames,
+            sustain_level: init.sustain_level,
             direction,
+            easing,
             color,
+            palette: ArrayVec::new(),
             updater,
             pixels_per_pixel_group,
+            priority,
+            seed: 0,
+            sparkle_window,
+            sparkles_per_window_x256,
+            strobe_period_x256,
+            strobe_lit_x256,
+            strobe_count,
+            leaves_color_behind: init.leaves_color_behind
+                && matches!(init.mode, Mode::Wipe(_) | Mode::WipeCenterOut(_)),
+            ripple_origin: init.ripple_origin,
+            ripple_frames_per_led_x256,
+            matrix: None,
+            target: init.target,
+            blend_mode: init.blend_mode,
+            mode: init.mode,
+        }
+    }
+
+    /// Rescales the frame counts of the trigger so that it keeps the same speed at a new frame rate.
+    pub fn rescale_frame_rate(&mut self, old_rate: Hertz, new_rate: Hertz) {
+        self.frames.rescale_frame_rate(old_rate, new_rate);
+        self.transition_frame = rescale_frames(self.transition_frame, old_rate, new_rate);
+        self.decay_frames = rescale_frames(self.decay_frames, old_rate, new_rate);
+        self.sustain_frames = rescale_frames(self.sustain_frames, old_rate, new_rate);
+        self.sparkle_window = rescale_frames(self.sparkle_window, old_rate, new_rate).max(1);
+        self.strobe_period_x256 = rescale_frames(self.strobe_period_x256, old_rate, new_rate);
+        let strobe_lit_x256 = rescale_frames(self.strobe_lit_x256, old_rate, new_rate);
+        self.strobe_lit_x256 = lit_for_a_frame(strobe_lit_x256);
+        self.ripple_frames_per_led_x256 =
+            rescale_frames(self.ripple_frames_per_led_x256, old_rate, new_rate);
+        self.rescale_strobe_frames();
+    }
+
+    /// Rescales the frame counts of the trigger so that it runs at a new speed.
+    pub fn rescale_speed(&mut self, old_speed: Speed, new_speed: Speed) {
+        self.frames.rescale_speed(old_speed, new_speed);
+        self.transition_frame = rescale_speed(self.transition_frame, old_speed, new_speed);
+        self.decay_frames = rescale_speed(self.decay_frames, old_speed, new_speed);
+        self.sustain_frames = rescale_speed(self.sustain_frames, old_speed, new_speed);
+        self.sparkle_window = rescale_speed(self.sparkle_window, old_speed, new_speed).max(1);
+        self.strobe_period_x256 = rescale_speed(self.strobe_period_x256, old_speed, new_speed);
+        let strobe_lit_x256 = rescale_speed(self.strobe_lit_x256, old_speed, new_speed);
+        self.strobe_lit_x256 = lit_for_a_frame(strobe_lit_x256);
+        self.ripple_frames_per_led_x256 =
+            rescale_speed(self.ripple_frames_per_led_x256, old_speed, new_speed);
+        self.rescale_strobe_frames();
+    }
+
+    /// Works the length of a strobe out again from its rescaled period, as rounding its frames
+    /// down could cut off the last flash.
+    fn rescale_strobe_frames(&mut self) {
+        if let Mode::Strobe(_) = self.mode {
+            let frames = strobe_frames(self.strobe_period_x256, self.strobe_count);
+            self.frames.rescale(frames);
         }
     }
 
-    pub fn update(&mut self, segment: &mut [RGB8]) {
+    pub fn update(&mut self, segment: &mut [RGB8], scratch: &mut [RGB8]) {
+        let (segment, scratch) = match self.target {
+            Some(range) => (range.slice_of(segment), range.slice_of(scratch)),
+            None => (segment, scratch),
+        };
         if let Some(f) = self.updater {
-            f(self, segment);
+            if !segment.is_empty() {
+                self.draw(f, segment, scratch);
+            }
         }
         self.frames.increment();
     }
+
+    fn draw(&mut self, updater: TriggerUpdater, segment: &mut [RGB8], scratch: &mut [RGB8]) {
+        if self.blend_mode == BlendMode::Normal {
+            updater(self, segment);
+            return;
+        }
+        let identity = self.blend_mode.identity();
+        // draw onto a blank layer first, so that only the trigger's own colors are blended:
+        scratch.fill(identity);
+        updater(self, scratch);
+        for (led, &color) in segment.iter_mut().zip(scratch.iter()) {
+            if color != identity {
+                *led = self.blend_mode.blend(*led, color);
+            }
+        }
+    }
 }
 
 impl<'a, const N: usize> MarchingRainbow for TriggerCollection<'a, N> {
@@ -230,20 +946,56 @@ impl<'a, const N: usize> MarchingRainbow for TriggerCollection<'a, N> {
     }
 }
 
+/// Converts the retrigger interval to frames, rounding up so that an interval shorter than a frame
+/// still limits triggers that come in the same frame.
+fn retrigger_frames(interval_ns: u64, frame_rate: Hertz) -> usize {
+    let frame_ns = 1_000_000_000 / frame_rate.integer().max(1) as u64;
+    convert_ns_to_frames(interval_ns + frame_ns.saturating_sub(1), frame_rate)
+}
+
+/// Returns how far the trigger has faded in, following its envelope: it fades in to full
+/// brightness, decays to the sustain level, holds there, and then fades out.
 fn get_trigger_fade_progress(trigger: &mut Trigger) -> Progression {
-    let is_fade_in = trigger.frames.get_current() < trigger.transition_frame;
+    let frame = trigger.frames.get_current();
+    let decay_start = trigger.transition_frame;
+    let sustain_start = decay_start + trigger.decay_frames;
+    let release_start = sustain_start + trigger.sustain_frames;
+    let sustain_level = Q16::from_ratio(trigger.sustain_level as u64, FULL_BRIGHTNESS as u64);
+
+    // the eased fraction of the way through the phase that started at `start`:
+    let phase = |start: usize, length: usize, is_rising: bool| {
+        let mut progress = Progression::new(length);
+        if !is_rising {
+            progress.reverse_direction();
+        }
+        progress.set_current(frame - start);
+        Q16::from_progression(trigger.easing.ease(progress))
+    };
 
-    let mut progress;
-    let mut transition_frame = 0;
-    if is_fade_in {
-        progress = Progression::new(trigger.transition_frame);
+    let level = if frame < decay_start {
+        phase(0, decay_start, true)
+    } else if frame < sustain_start {
+        let decayed = phase(decay_start, trigger.decay_frames, true);
+        Q16::from_bits(
+            decayed.lerp(Q16::ONE.to_bits() as i32, sustain_level.to_bits() as i32) as u32,
+        )
+    } else if frame < release_start {
+        sustain_level
     } else {
-        progress = Progression::new(trigger.frames.total - trigger.transition_frame);
-        progress.reverse_direction();
-        transition_frame = trigger.transition_frame;
-    }
+        // without a decay or a sustain, the trigger never left full brightness to fade out from:
+        let release_level = match trigger.decay_frames + trigger.sustain_frames {
+            0 => Q16::ONE,
+            _ => sustain_level,
+        };
+        let release_frames = trigger.frames.total - release_start;
+        let fading = phase(release_start, release_frames, false);
+        Q16::from_bits(fading.scale(release_level.to_bits()))
+    };
 
-    progress.set_current(trigger.frames.get_current() - transition_frame);
+    // a level of 1.0 only just falls short of the end of the progression, so it blends to the
+    // full trigger color:
+    let mut progress = Progression::new(Q16::ONE.to_bits() as usize + 1);
+    progress.set_current(level.to_bits() as usize);
     progress
 }
 
@@ -257,6 +1009,9 @@ fn flash(trigger: &mut Trigger, segment: &mut [RGB8]) {
 
 fn color_pulse(trigger: &mut Trigger, segment: &mut [RGB8]) {
     let progress = get_trigger_fade_progress(trigger);
+    if segment.is_empty() {
+        return;
+    }
 
     // the range will be always at least 1 led, up to pixels_per_pixel_group leds:
     let first_led_index = trigger.offset as usize / segment.len();
@@ -270,8 +1025,12 @@ fn color_pulse(trigger: &mut Trigger, segment: &mut [RGB8]) {
 }
 
 fn color_shot(trigger: &mut Trigger, segment: &mut [RGB8]) {
-    let current_offset = shift_offset(trigger.offset, trigger.frames, trigger.direction) as usize;
-    let offset_distance_between_leds = MAX_OFFSET as usize / segment.len();
+    let frames = trigger.easing.ease(trigger.frames);
+    let current_offset = shift_offset(trigger.offset, frames, trigger.direction) as usize;
+    if segment.is_empty() {
+        return;
+    }
+    let offset_distance_between_leds = (MAX_OFFSET as usize / segment.len()).max(1);
 
     // the range will be always at least 1 led, up to pixels_per_pixel_group leds:
     let first_led_index = current_offset / offset_distance_between_leds;
@@ -284,9 +1043,167 @@ fn color_shot(trigger: &mut Trigger, segment: &mut [RGB8]) {
     }
 }
 
-fn init_color_pulse(trigger: &mut Trigger, _: &mut TimedRainbows) {
+fn comet(trigger: &mut Trigger, segment: &mut [RGB8]) {
+    let frames = trigger.easing.ease(trigger.frames);
+    let current_offset = shift_offset(trigger.offset, frames, trigger.direction) as usize;
+    let led_count = segment.len();
+    if led_count == 0 {
+        return;
+    }
+    let head_index = current_offset / (MAX_OFFSET as usize / led_count).max(1) % led_count;
+    segment[head_index] = trigger.color;
+
+    // the tail trails behind the head, fading out over its length:
+    let tail_length = trigger.pixels_per_pixel_group;
+    let mut fade = Progression::new(tail_length + 1);
+    for distance in 1..=tail_length.min(led_count - 1) {
+        let index = match trigger.direction {
+            Direction::Negative => (head_index + distance) % led_count,
+            _ => (head_index + led_count - distance) % led_count,
+        };
+        fade.set_current(tail_length + 1 - distance);
+        segment[index] = segment[index].lerp_with(trigger.color, fade);
+    }
+}
+
+fn sparkle(trigger: &mut Trigger, segment: &mut [RGB8]) {
+    if trigger.palette.is_empty() || segment.is_empty() {
+        return;
+    }
+    let intensity = get_trigger_fade_progress(trigger);
+    let window = trigger.sparkle_window;
+
+    // the chance out of 65536 that any single LED sparkles during this window:
+    let chance = trigger.sparkles_per_window_x256 * 256 * intensity.get_current()
+        / intensity.total.max(1)
+        / segment.len();
+
+    for (index, led) in segment.iter_mut().enumerate() {
+        // stagger the windows of each LED so they don't all start sparkling at the same time:
+        let frame =
+            trigger.frames.get_current() + scramble(trigger.seed ^ index as u32) as usize % window;
+        let window_index = frame / window;
+        let frame_in_window = frame % window;
+
+        // every LED gets a repeatable random value for each window, so sparkles don't need state:
+        let key = ((window_index as u32) << 16) | (index as u32 & 0xffff);
+        let hash = scramble(trigger.seed ^ scramble(key));
+        if (hash & 0xffff) as usize >= chance {
+            continue;
+        }
+
+        let length = (window / 4 + (hash >> 16) as usize % (window * 3 / 4 + 1)).max(1);
+        let start = (hash >> 8) as usize % (window - length + 1);
+        if frame_in_window < start || frame_in_window >= start + length {
+            continue;
+        }
+
+        // fade each sparkle up to full brightness halfway through, and back down again:
+        let position = frame_in_window - start;
+        let half = length.div_ceil(2);
+        let mut fade = Progression::new(half + 1);
+        fade.set_current((position + 1).min(length - position));
+
+        let color = trigger.palette[(hash >> 24) as usize % trigger.palette.len()];
+        *led = led.lerp_with(color, fade);
+    }
+}
+
+/// The frames a strobe lasts: exactly as long as its flashes, with one more frame to finish on.
+fn strobe_frames(strobe_period_x256: usize, strobe_count: usize) -> usize {
+    (strobe_period_x256 * strobe_count).div_ceil(256) + 1
+}
+
+/// Keeps a strobe flash lit for at least one frame, in 256ths of a frame, so that fast strobes and
+/// high frame rates don't lose their flashes. Strobes without flashes stay at 0.
+fn lit_for_a_frame(strobe_lit_x256: usize) -> usize {
+    match strobe_lit_x256 {
+        0 => 0,
+        lit => lit.max(256),
+    }
+}
+
+fn strobe(trigger: &mut Trigger, segment: &mut [RGB8]) {
+    if trigger.strobe_period_x256 == 0 {
+        return;
+    }
+    // the first frame of each flash starts less than a frame into its period, so a flash lit
+    // for at least a frame is always drawn:
+    let position = trigger.frames.get_current() * 256 % trigger.strobe_period_x256;
+    if position < trigger.strobe_lit_x256 {
+        segment.fill(trigger.color);
+    }
+}
+
+fn wipe(trigger: &mut Trigger, segment: &mut [RGB8]) {
+    let frame = trigger.frames.get_current();
+    if frame >= trigger.transition_frame {
+        flash(trigger, segment);
+        return;
+    }
+    // the sweep covers the last LED on the last frame of the fade in:
+    let progress = (frame + 1) as u64 * EASING_SCALE as u64 / trigger.transition_frame as u64;
+    let covered = trigger.easing.apply(progress as u32) as usize;
+    let led_count = segment.len();
+    let covered = (led_count * covered).div_ceil(EASING_SCALE as usize);
+
+    for (index, led) in segment.iter_mut().enumerate() {
+        let is_covered = match (trigger.mode, trigger.direction) {
+            // twice the distance from the center, so that both halves are covered evenly:
+            (Mode::WipeCenterOut(_), _) => (2 * index + 1).abs_diff(led_count) < covered,
+            (_, Direction::Negative) => index >= led_count - covered,
+            _ => index < covered,
+        };
+        if is_covered {
+            *led = trigger.color;
+        }
+    }
+}
+
+fn ripple(trigger: &mut Trigger, segment: &mut [RGB8]) {
+    let level = Q16::from_progression(get_trigger_fade_progress(trigger));
+    // positions are in 256ths of an LED, so the ring grows smoothly between LEDs:
+    let radius = match trigger.ripple_frames_per_led_x256 {
+        0 => 0,
+        frames_per_led => trigger.frames.get_current() * 256 * 256 / frames_per_led,
+    };
+    // the soft edges reach half an LED past the width, so a thin ring never falls between LEDs:
+    let reach = trigger.pixels_per_pixel_group.max(1) * 128 + 128;
+
+    let matrix = trigger
+        .matrix
+        .filter(|matrix| matrix.width > 0 && matrix.led_count() <= segment.len());
+    let origin = match (trigger.ripple_origin, matrix) {
+        (RippleOrigin::Led(index), Some(matrix)) => matrix.coordinates(index),
+        (RippleOrigin::Point(x, y), Some(_)) => (x, y),
+        (RippleOrigin::Led(index) | RippleOrigin::Point(index, _), None) => (index, 0),
+    };
+
+    for (index, led) in segment.iter_mut().enumerate() {
+        let (x, y) = match matrix {
+            Some(matrix) => matrix.coordinates(index),
+            None => (index, 0),
+        };
+        let (dx, dy) = (x.abs_diff(origin.0) as u64, y.abs_diff(origin.1) as u64);
+        let distance = ((dx * dx + dy * dy) * 256 * 256).isqrt() as usize;
+        let from_ring = distance.abs_diff(radius);
+        if from_ring >= reach {
+            continue;
+        }
+        // the ring is brightest at its radius, and fades out towards its edges:
+        let edge = Q16::from_ratio((reach - from_ring) as u64, reach as u64);
+        let strength = Q16::from_bits(edge.scale(level.to_bits()));
+        *led = RGB8::new(
+            strength.lerp_u8(led.r, trigger.color.r),
+            strength.lerp_u8(led.g, trigger.color.g),
+            strength.lerp_u8(led.b, trigger.color.b),
+        );
+    }
+}
+
+fn init_color_pulse(trigger: &mut Trigger, global: &mut TimedRainbows) {
     trigger.direction = Direction::Stopped;
-    trigger.offset = get_random_offset();
+    trigger.offset = global.random.next_offset();
 }
 
 fn init_color_pulse_fade(trigger: &mut Trigger, global: &mut TimedRainbows) {
@@ -324,8 +1241,118 @@ fn init_flash_fade(trigger: &mut Trigger, global: &mut TimedRainbows) {
     trigger.color = global.calculate_fade_color();
 }
 
+fn init_sparkle(trigger: &mut Trigger, global: &mut TimedRainbows) {
+    trigger.direction = Direction::Stopped;
+    trigger.seed = global.random.next_u32();
+    let rainbow = &global.incremental_rainbow.backer;
+    for index in 0..rainbow.len().min(MAX_SPARKLE_COLORS) {
+        trigger.palette.push(rainbow[index]);
+    }
+}
+
+fn init_strobe(trigger: &mut Trigger, global: &mut TimedRainbows) {
+    init_flash(trigger, global);
+    init_mode_color(trigger, global);
+}
+
+fn init_mode_color(trigger: &mut Trigger, _: &mut TimedRainbows) {
+    if let Mode::Strobe(color) | Mode::Wipe(color) | Mode::WipeCenterOut(color) = trigger.mode {
+        trigger.color = color;
+    }
+}
+
+fn init_ripple(trigger: &mut Trigger, global: &mut TimedRainbows) {
+    init_flash(trigger, global);
+    trigger.color = global.current_rainbow_color();
+    global.advance_rainbow_color();
+}
+
 fn init_flash_rainbow(trigger: &mut Trigger, global: &mut TimedRainbows) {
     init_flash(trigger, global);
     trigger.color = global.current_rainbow_color();
     global.advance_rainbow_color();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::animations::{Animatable, Animation};
+    use crate::default_animations::ANI_ALL_OFF;
+    use embedded_time::rate::Extensions;
+
+    const WHITE: RGB8 = RGB8::new(255, 255, 255);
+
+    /// Flashes 4 times, once every 6 frames at 60 Hz, each lit for a third of its period.
+    const STROBE: Parameters = Parameters {
+        mode: Mode::Strobe(WHITE),
+        direction: Direction::Positive,
+        easing: Easing::Linear,
+        fade_in_time_ns: 0,
+        fade_out_time_ns: 0,
+        decay_time_ns: 0,
+        sustain_level: FULL_BRIGHTNESS,
+        sustain_time_ns: 0,
+        starting_offset: 0,
+        pixels_per_pixel_group: 1,
+        priority: 0,
+        density: 0,
+        strobe_count: 4,
+        strobe_period_ns: 100_000_000,
+        strobe_duty_cycle: 85,
+        leaves_color_behind: false,
+        ripple_origin: RippleOrigin::Led(0),
+        ripple_speed: 0,
+        target: None,
+        blend_mode: BlendMode::Normal,
+        rainbow: None,
+    };
+
+    /// Runs a strobe over black LEDs for a second, returning the number of flashes and the
+    /// number of frames that were lit.
+    fn count_flashes(params: &Parameters, speed_up: u32) -> (usize, usize) {
+        let mut animation = Animation::<4>::new(ANI_ALL_OFF, 60.Hz());
+        animation.set_speed(AnimationType::Trigger, speed_up, 1);
+        animation.trigger(params, 60.Hz());
+        let (mut flashes, mut lit_frames, mut was_lit) = (0, 0, false);
+        for _ in 0..60 {
+            animation.update();
+            let is_lit = animation.segment().iter().all(|&led| led == WHITE);
+            flashes += (is_lit && !was_lit) as usize;
+            lit_frames += is_lit as usize;
+            was_lit = is_lit;
+        }
+        assert_eq!(animation.active_trigger_count(), 0);
+        (flashes, lit_frames)
+    }
+
+    #[test]
+    fn strobe_flashes_its_count_for_its_duty_cycle() {
+        assert_eq!(count_flashes(&STROBE, 1), (4, 8));
+    }
+
+    #[test]
+    fn strobe_flashes_are_lit_for_at_least_a_frame() {
+        let short_flashes = Parameters {
+            strobe_duty_cycle: 1,
+            ..STROBE
+        };
+        assert_eq!(count_flashes(&short_flashes, 1), (4, 4));
+    }
+
+    #[test]
+    fn sped_up_strobe_keeps_every_flash() {
+        // at 4 times the speed the period is a frame and a half, so the flashes that start
+        // halfway through a frame run into the next ones, but each of them is still drawn:
+        let (_, lit_frames) = count_flashes(&STROBE, 4);
+        assert_eq!(lit_frames, 4);
+    }
+
+    #[test]
+    fn strobe_without_flashes_stays_dark() {
+        let no_flashes = Parameters {
+            strobe_count: 0,
+            ..STROBE
+        };
+        assert_eq!(count_flashes(&no_flashes, 1), (0, 0));
+    }
+}
@@ -0,0 +1,160 @@
+//! Output for SPI strips with a global brightness field, such as the APA102 and HD108. Each LED of
+//! these strips has a current gain as well as its color values, so dim colors can be shown with a
+//! low gain and full 8 bit color values instead of only a few steps of the color values. These
+//! colors are precise output colors, so they are made from the controller's output calculated
+//! with 16 bits per channel, which is split into the best gain and color values for each LED.
+
+use crate::output::{ChannelOrder, OutputColor};
+use rgb::{RGB16, RGB8};
+
+/// The largest value of the 5 bit global brightness field.
+pub const MAX_GLOBAL_BRIGHTNESS: u8 = 31;
+
+/// A color type that can be sent to an SPI strip.
+pub trait SpiColor: OutputColor {
+    /// The number of bytes sent for each LED.
+    const BYTES: usize;
+
+    /// The number of zero bytes sent before the first LED.
+    const START_FRAME_LENGTH: usize;
+
+    /// Writes the bytes of the color to the start of `bytes`, which is BYTES long.
+    fn write_bytes(&self, bytes: &mut [u8]);
+}
+
+/// The color of a single APA102 LED: a 5 bit global brightness and an 8 bit value per channel.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Apa102Color {
+    pub brightness: u8,
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl Apa102Color {
+    pub const fn new(brightness: u8, r: u8, g: u8, b: u8) -> Self {
+        Apa102Color {
+            brightness,
+            r,
+            g,
+            b,
+        }
+    }
+}
+
+impl OutputColor for Apa102Color {
+    const IS_PRECISE: bool = true;
+
+    fn from_rgb8(color: RGB8) -> Self {
+        Apa102Color::new(MAX_GLOBAL_BRIGHTNESS, color.r, color.g, color.b)
+    }
+
+    fn from_rgb16(color: RGB16) -> Self {
+        // the lowest brightness that still fits the brightest channel in 8 bits leaves the most
+        // steps for the color values:
+        let max = color.r.max(color.g).max(color.b) as u32;
+        let brightness = (max * MAX_GLOBAL_BRIGHTNESS as u32)
+            .div_ceil(u16::MAX as u32)
+            .max(1);
+        let value = |channel: u16| {
+            let numerator = channel as u64 * MAX_GLOBAL_BRIGHTNESS as u64 * u8::MAX as u64;
+            let denominator = brightness as u64 * u16::MAX as u64;
+            ((numerator + denominator / 2) / denominator).min(u8::MAX as u64) as u8
+        };
+        Apa102Color::new(
+            brightness as u8,
+            value(color.r),
+            value(color.g),
+            value(color.b),
+        )
+    }
+
+    /// Reorders the color values for clones of the APA102 that are wired differently. The values
+    /// are sent in the order `b`, `g`, `r`, so an order of BGR sends red first.
+    fn with_channel_order(self, order: ChannelOrder) -> Self {
+        let rgb = order.apply(RGB8::new(self.r, self.g, self.b));
+        Apa102Color::new(self.brightness, rgb.r, rgb.g, rgb.b)
+    }
+}
+
+impl SpiColor for Apa102Color {
+    const BYTES: usize = 4;
+    const START_FRAME_LENGTH: usize = 4;
+
+    fn write_bytes(&self, bytes: &mut [u8]) {
+        let header = 0xe0 | self.brightness.min(MAX_GLOBAL_BRIGHTNESS);
+        bytes[..Self::BYTES].copy_from_slice(&[header, self.b, self.g, self.r]);
+    }
+}
+
+/// The color of a single HD108 LED: a 5 bit gain and a 16 bit value per channel. The 16 bit
+/// values already have plenty of precision, so colors from the controller are sent at full gain,
+/// and the gains are left for setting the current of each channel by hand.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Hd108Color {
+    pub gain: [u8; 3],
+    pub color: RGB16,
+}
+
+impl Hd108Color {
+    pub const fn new(gain: [u8; 3], color: RGB16) -> Self {
+        Hd108Color { gain, color }
+    }
+}
+
+impl OutputColor for Hd108Color {
+    const IS_PRECISE: bool = true;
+
+    fn from_rgb8(color: RGB8) -> Self {
+        Self::from_rgb16(crate::output::expand(color))
+    }
+
+    fn from_rgb16(color: RGB16) -> Self {
+        Hd108Color::new([MAX_GLOBAL_BRIGHTNESS; 3], color)
+    }
+
+    fn with_channel_order(self, order: ChannelOrder) -> Self {
+        let [r, g, b] = self.gain;
+        let gain = order.apply(rgb::RGB::new(r, g, b));
+        Hd108Color::new([gain.r, gain.g, gain.b], order.apply(self.color))
+    }
+}
+
+impl SpiColor for Hd108Color {
+    const BYTES: usize = 8;
+    const START_FRAME_LENGTH: usize = 16;
+
+    fn write_bytes(&self, bytes: &mut [u8]) {
+        let gain = |index: usize| self.gain[index].min(MAX_GLOBAL_BRIGHTNESS) as u16;
+        let header = 0x8000 | gain(0) << 10 | gain(1) << 5 | gain(2);
+        let [r, g, b] = [self.color.r, self.color.g, self.color.b].map(u16::to_be_bytes);
+        bytes[..2].copy_from_slice(&header.to_be_bytes());
+        bytes[2..4].copy_from_slice(&r);
+        bytes[4..6].copy_from_slice(&g);
+        bytes[6..Self::BYTES].copy_from_slice(&b);
+    }
+}
+
+/// The number of zero bytes sent after the last LED. Each LED delays the clock by half a cycle,
+/// so the end frame needs one clock cycle for every two LEDs to push the data to the end.
+pub const fn end_frame_length(led_count: usize) -> usize {
+    led_count.div_ceil(16)
+}
+
+/// The number of bytes needed to send a frame with `led_count` LEDs.
+pub const fn frame_length<C: SpiColor>(led_count: usize) -> usize {
+    C::START_FRAME_LENGTH + led_count * C::BYTES + end_frame_length(led_count)
+}
+
+/// Writes the start frame, the LEDs of a frame and the end frame into `out`, ready to be sent
+/// over SPI. Returns the number of bytes written, or None if `out` is too short.
+pub fn write_frame<C: SpiColor>(frame: &[C], out: &mut [u8]) -> Option<usize> {
+    let length = frame_length::<C>(frame.len());
+    let out = out.get_mut(..length)?;
+    out.fill(0);
+    let leds = &mut out[C::START_FRAME_LENGTH..];
+    for (color, bytes) in frame.iter().zip(leds.chunks_exact_mut(C::BYTES)) {
+        color.write_bytes(bytes);
+    }
+    Some(length)
+}
@@ -0,0 +1,258 @@
+//! Art-Net input, so the controller can be operated remotely from a lighting console. A Receiver
+//! parses ArtDmx packets for a single universe and turns the channels listed in its ChannelMap
+//! into Commands, which can be applied straight to the LightingController or passed through a
+//! CommandQueue. Commands are only sent when a channel changes, so a console that repeats the same
+//! values every packet doesn't keep resetting the animations.
+
+use crate::animations::{trigger, AnimationType, MAX_OFFSET};
+use crate::commands::Command;
+use crate::dmx::CHANNELS_PER_UNIVERSE;
+use crate::presets::Preset;
+
+/// The UDP port that Art-Net nodes listen on.
+pub const PORT: u16 = 6454;
+
+/// Trigger channels fire when they rise from below this level to this level or above.
+pub const TRIGGER_THRESHOLD: u8 = 128;
+
+const ID: [u8; 8] = *b"Art-Net\0";
+const OP_DMX: u16 = 0x5000;
+const PROTOCOL_VERSION: u16 = 14;
+const HEADER_SIZE: usize = 18;
+
+/// The contents of an ArtDmx packet.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ArtDmx<'p> {
+    /// Increases with every packet, or 0 if the sender doesn't number its packets.
+    pub sequence: u8,
+    /// The physical input port the data came from on the sender.
+    pub physical: u8,
+    /// The 15 bit universe address, made up of the net, sub-net and universe.
+    pub port_address: u16,
+    /// The DMX channels, starting from channel 1.
+    pub data: &'p [u8],
+}
+
+impl<'p> ArtDmx<'p> {
+    /// Parses an ArtDmx packet. Returns None for other Art-Net packets, such as ArtPoll, and for
+    /// anything that isn't a valid Art-Net packet.
+    pub fn parse(packet: &'p [u8]) -> Option<Self> {
+        let header = packet.get(..HEADER_SIZE)?;
+        if header[0..8] != ID {
+            return None;
+        }
+        // the op code is the only little endian field in the packet:
+        if u16::from_le_bytes([header[8], header[9]]) != OP_DMX {
+            return None;
+        }
+        if u16::from_be_bytes([header[10], header[11]]) < PROTOCOL_VERSION {
+            return None;
+        }
+        let length = u16::from_be_bytes([header[16], header[17]]) as usize;
+        if length > CHANNELS_PER_UNIVERSE {
+            return None;
+        }
+        Some(ArtDmx {
+            sequence: header[12],
+            physical: header[13],
+            port_address: u16::from_le_bytes([header[14], header[15] & 0x7f]),
+            data: packet.get(HEADER_SIZE..HEADER_SIZE + length)?,
+        })
+    }
+
+    /// Returns the value of a channel, numbered from 1 as on a console, or None if the packet
+    /// doesn't include it.
+    pub fn channel(&self, channel: u16) -> Option<u8> {
+        self.data.get((channel as usize).checked_sub(1)?).copied()
+    }
+}
+
+/// A channel that sets the offset of an animation layer. The range of the channel is spread over
+/// the full range of offsets.
+#[derive(Copy, Clone)]
+pub struct OffsetChannel {
+    pub channel: u16,
+    pub animation_index: usize,
+    pub animation_type: AnimationType,
+}
+
+/// A channel that fires a trigger when it rises past TRIGGER_THRESHOLD.
+#[derive(Copy, Clone)]
+pub struct TriggerChannel<'a> {
+    pub channel: u16,
+    pub animation_index: usize,
+    pub parameters: &'a trigger::Parameters,
+}
+
+/// A channel that selects a preset by its ID. Values without a matching preset are ignored.
+#[derive(Copy, Clone)]
+pub struct PresetChannel<'a> {
+    pub channel: u16,
+    pub animation_index: usize,
+    pub presets: &'a [Preset<'a>],
+}
+
+/// Which channels of a universe control the LightingController. Channels are numbered from 1.
+#[derive(Copy, Clone)]
+pub struct ChannelMap<'a> {
+    /// The universe to listen to. Packets for other universes are ignored.
+    pub port_address: u16,
+    /// A channel that sets the brightness of the whole controller.
+    pub brightness: Option<u16>,
+    pub preset: Option<PresetChannel<'a>>,
+    pub offsets: &'a [OffsetChannel],
+    pub triggers: &'a [TriggerChannel<'a>],
+}
+
+/// Listens to a single universe and turns changes to the mapped channels into Commands.
+pub struct Receiver<'a> {
+    map: ChannelMap<'a>,
+    last_values: [u8; CHANNELS_PER_UNIVERSE],
+    has_received: bool,
+}
+
+impl<'a> Receiver<'a> {
+    pub fn new(map: ChannelMap<'a>) -> Self {
+        Receiver {
+            map,
+            last_values: [0; CHANNELS_PER_UNIVERSE],
+            has_received: false,
+        }
+    }
+
+    /// Parses a packet and calls `apply` with a Command for each mapped channel that changed since
+    /// the last packet. Every mapped channel is sent for the first packet. Returns false if the
+    /// packet wasn't an ArtDmx packet for this receiver's universe.
+    pub fn receive(&mut self, packet: &[u8], mut apply: impl FnMut(Command<'a>)) -> bool {
+        let dmx = match ArtDmx::parse(packet) {
+            Some(dmx) if dmx.port_address == self.map.port_address => dmx,
+            _ => return false,
+        };
+
+        if let Some(channel) = self.map.brightness {
+            if let Some(value) = self.changed_value(&dmx, channel) {
+                apply(Command::SetBrightness(value));
+            }
+        }
+
+        if let Some(preset) = self.map.preset {
+            let selected = self
+                .changed_value(&dmx, preset.channel)
+                .and_then(|id| preset.presets.iter().find(|p| p.id == id));
+            if let Some(selected) = selected {
+                apply(Command::SetParameters {
+                    animation_index: preset.animation_index,
+                    parameters: &selected.parameters,
+                });
+            }
+        }
+
+        for offset in self.map.offsets {
+            if let Some(value) = self.changed_value(&dmx, offset.channel) {
+                apply(Command::SetOffset {
+                    animation_index: offset.animation_index,
+                    animation_type: offset.animation_type,
+                    offset: (value as u32 * MAX_OFFSET as u32 / u8::MAX as u32) as u16,
+                });
+            }
+        }
+
+        for trigger in self.map.triggers {
+            let last_value = self.last_value(trigger.channel);
+            let rising = dmx
+                .channel(trigger.channel)
+                .is_some_and(|value| value >= TRIGGER_THRESHOLD && last_value < TRIGGER_THRESHOLD);
+            if rising {
+                apply(Command::Trigger {
+                    animation_index: trigger.animation_index,
+                    parameters: *trigger.parameters,
+                });
+            }
+        }
+
+        self.last_values[..dmx.data.len()].copy_from_slice(dmx.data);
+        self.has_received = true;
+        true
+    }
+
+    fn last_value(&self, channel: u16) -> u8 {
+        match (channel as usize).checked_sub(1) {
+            Some(index) => self.last_values.get(index).copied().unwrap_or(0),
+            None => 0,
+        }
+    }
+
+    /// Returns the value of a channel if it is in the packet and has changed since the last one.
+    fn changed_value(&self, dmx: &ArtDmx, channel: u16) -> Option<u8> {
+        let value = dmx.channel(channel)?;
+        let has_changed = !self.has_received || value != self.last_value(channel);
+        has_changed.then_some(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds an ArtDmx packet for port address 0x0123 with the given channels.
+    fn packet(channels: &[u8], buffer: &mut [u8; HEADER_SIZE + CHANNELS_PER_UNIVERSE]) -> usize {
+        buffer[0..8].copy_from_slice(&ID);
+        buffer[8..10].copy_from_slice(&OP_DMX.to_le_bytes());
+        buffer[10..12].copy_from_slice(&PROTOCOL_VERSION.to_be_bytes());
+        buffer[12] = 7;
+        buffer[13] = 1;
+        buffer[14..16].copy_from_slice(&0x0123_u16.to_le_bytes());
+        buffer[16..18].copy_from_slice(&(channels.len() as u16).to_be_bytes());
+        buffer[HEADER_SIZE..HEADER_SIZE + channels.len()].copy_from_slice(channels);
+        HEADER_SIZE + channels.len()
+    }
+
+    #[test]
+    fn parses_an_art_dmx_packet() {
+        let mut buffer = [0; HEADER_SIZE + CHANNELS_PER_UNIVERSE];
+        let length = packet(&[10, 20, 30], &mut buffer);
+        let dmx = ArtDmx::parse(&buffer[..length]).unwrap();
+        assert_eq!(dmx.sequence, 7);
+        assert_eq!(dmx.physical, 1);
+        assert_eq!(dmx.port_address, 0x0123);
+        assert_eq!(dmx.data, &[10, 20, 30]);
+        assert_eq!(dmx.channel(1), Some(10));
+        assert_eq!(dmx.channel(3), Some(30));
+        assert_eq!(dmx.channel(0), None);
+        assert_eq!(dmx.channel(4), None);
+    }
+
+    #[test]
+    fn rejects_short_packets() {
+        let mut buffer = [0; HEADER_SIZE + CHANNELS_PER_UNIVERSE];
+        let length = packet(&[10, 20, 30], &mut buffer);
+        // cut off in the header, and cut off before the end of the channels it announces:
+        assert_eq!(ArtDmx::parse(&buffer[..HEADER_SIZE - 1]), None);
+        assert_eq!(ArtDmx::parse(&buffer[..length - 1]), None);
+        assert_eq!(ArtDmx::parse(&[]), None);
+    }
+
+    #[test]
+    fn rejects_malformed_packets() {
+        let mut buffer = [0; HEADER_SIZE + CHANNELS_PER_UNIVERSE];
+        let length = packet(&[10, 20, 30], &mut buffer);
+
+        let mut wrong_id = buffer;
+        wrong_id[0] = b'X';
+        assert_eq!(ArtDmx::parse(&wrong_id[..length]), None);
+
+        // an ArtPoll:
+        let mut wrong_op = buffer;
+        wrong_op[8..10].copy_from_slice(&0x2000_u16.to_le_bytes());
+        assert_eq!(ArtDmx::parse(&wrong_op[..length]), None);
+
+        let mut old_version = buffer;
+        old_version[10..12].copy_from_slice(&(PROTOCOL_VERSION - 1).to_be_bytes());
+        assert_eq!(ArtDmx::parse(&old_version[..length]), None);
+
+        let mut too_long = [0; HEADER_SIZE + CHANNELS_PER_UNIVERSE + 1];
+        too_long[..buffer.len()].copy_from_slice(&buffer);
+        too_long[16..18].copy_from_slice(&(CHANNELS_PER_UNIVERSE as u16 + 1).to_be_bytes());
+        assert_eq!(ArtDmx::parse(&too_long), None);
+    }
+}
@@ -3,8 +3,12 @@
 //! [smart-leds](https://github.com/smart-leds-rs/smart-leds) crate, which in turn is compatible
 //! with the [rgb](https://github.com/kornelski/rust-rgb) crate.
 
+use crate::animations::MAX_OFFSET;
+use crate::fixed::Q16;
 use crate::utility::Progression;
-use rgb::RGB8;
+use arrayvec::ArrayVec;
+use core::ops::{Deref, DerefMut};
+use rgb::{RGB16, RGB8, RGBA8};
 use smart_leds::colors::*;
 
 pub fn color_lerp(
@@ -14,20 +18,389 @@ pub fn color_lerp(
     start_color: RGB8,
     end_color: RGB8,
 ) -> RGB8 {
-    let lerp = |start: u8, end: u8| {
-        let start = start as i32;
-        let end = end as i32;
-        ((factor - in_min) * (end - start) / (in_max - in_min) + start) as u8
-    };
-    let mut mid_color = <RGB8>::new(0, 0, 0);
-    mid_color.r = lerp(start_color.r, end_color.r);
-    mid_color.g = lerp(start_color.g, end_color.g);
-    mid_color.b = lerp(start_color.b, end_color.b);
-    mid_color
+    // factors outside of in_min..in_max are clamped to the start and end colors:
+    let progress = (factor - in_min).max(0) as u64;
+    let t = Q16::from_ratio(progress, (in_max - in_min).max(0) as u64);
+    RGB8::new(
+        t.lerp_u8(start_color.r, end_color.r),
+        t.lerp_u8(start_color.g, end_color.g),
+        t.lerp_u8(start_color.b, end_color.b),
+    )
+}
+
+/// The same as `color_lerp()`, but with 16 bits per channel so that slow fades between close
+/// colors have more steps than the 8 bit values between them.
+pub fn color_lerp16(
+    factor: i32,
+    in_min: i32,
+    in_max: i32,
+    start_color: RGB16,
+    end_color: RGB16,
+) -> RGB16 {
+    let progress = (factor - in_min).max(0) as u64;
+    let t = Q16::from_ratio(progress, (in_max - in_min).max(0) as u64);
+    RGB16::new(
+        t.lerp_u16(start_color.r, end_color.r),
+        t.lerp_u16(start_color.g, end_color.g),
+        t.lerp_u16(start_color.b, end_color.b),
+    )
+}
+
+/// Alpha value for a layer that completely covers the layers beneath it.
+pub const OPAQUE: u8 = u8::MAX;
+
+/// Alpha value for a layer that is not visible at all.
+pub const TRANSPARENT: u8 = 0;
+
+/// Brightness value that leaves colors unchanged when scaling.
+pub const FULL_BRIGHTNESS: u8 = u8::MAX;
+
+/// Blends the `over` color on top of the `under` color using the `alpha` value of the top color.
+pub fn alpha_blend(under: RGB8, over: RGB8, alpha: u8) -> RGB8 {
+    color_lerp(alpha as i32, 0, OPAQUE as i32, under, over)
+}
+
+/// Selects how the colors of a layer are combined with the colors beneath it.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum BlendMode {
+    /// The layer's colors replace the colors beneath them.
+    Normal,
+
+    /// The layer's colors are added to the colors beneath them, so bright layers add light
+    /// instead of covering what is beneath them.
+    Add,
+
+    /// The colors are multiplied together, which tints and darkens the colors beneath the layer.
+    Multiply,
+
+    /// The inverse of the colors are multiplied together, which brightens the colors beneath the
+    /// layer without washing them out as quickly as Add.
+    Screen,
+
+    /// Each channel keeps the brighter of the two colors.
+    Lighten,
+}
+
+impl BlendMode {
+    /// Combines a color from a layer with the color beneath it.
+    pub fn blend(&self, under: RGB8, over: RGB8) -> RGB8 {
+        let channel = |under: u8, over: u8| match self {
+            BlendMode::Normal => over,
+            BlendMode::Add => under.saturating_add(over),
+            BlendMode::Multiply => (under as u16 * over as u16 / u8::MAX as u16) as u8,
+            BlendMode::Screen => {
+                u8::MAX
+                    - ((u8::MAX - under) as u16 * (u8::MAX - over) as u16 / u8::MAX as u16) as u8
+            }
+            BlendMode::Lighten => under.max(over),
+        };
+        RGB8::new(
+            channel(under.r, over.r),
+            channel(under.g, over.g),
+            channel(under.b, over.b),
+        )
+    }
+
+    /// The color that leaves the colors beneath it unchanged when blended. Layers with a blend
+    /// mode other than Normal are drawn onto this color rather than over the layers beneath them,
+    /// so that only the layer's own colors are blended.
+    pub fn identity(&self) -> RGB8 {
+        match self {
+            BlendMode::Multiply => WHITE,
+            _ => BLACK,
+        }
+    }
+}
+
+/// Composites a rendered `layer` on top of the `base` pixels. Only pixels that the layer actually
+/// drew on are blended, so the rest stay exactly as they were in `base`. With the Normal blend
+/// mode, the layer is drawn over a copy of `base`, and otherwise it is drawn onto a blank layer
+/// filled with `blend_mode.identity()`. The drawn pixels are scaled by `brightness` before they
+/// are blended.
+pub fn composite(
+    base: &mut [RGB8],
+    layer: &[RGB8],
+    alpha: u8,
+    brightness: u8,
+    blend_mode: BlendMode,
+) {
+    for (under, &over) in base.iter_mut().zip(layer.iter()) {
+        let is_drawn = match blend_mode {
+            BlendMode::Normal => *under != over,
+            _ => over != blend_mode.identity(),
+        };
+        if is_drawn {
+            let blended = blend_mode.blend(*under, over.scaled_by(brightness));
+            *under = alpha_blend(*under, blended, alpha);
+        }
+    }
+}
+
+/// Scales every color in the segment by `brightness`.
+pub fn scale_brightness(segment: &mut [RGB8], brightness: u8) {
+    if brightness == FULL_BRIGHTNESS {
+        return;
+    }
+    for led in segment {
+        *led = led.scaled_by(brightness);
+    }
+}
+
+/// Selects which color space is used when fading from one color to another.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ColorSpace {
+    /// Interpolates the red, green and blue channels separately.
+    Rgb,
+
+    /// Interpolates hue, saturation and value, taking the shortest way around the color wheel.
+    /// This gives more even looking sweeps between very different hues.
+    Hsv,
+}
+
+/// Selects how a rainbow picks the color that follows the current one. The random orders never
+/// pick the current color again, so every step changes the color.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ColorOrder<'a> {
+    /// Steps through the colors of the rainbow in order.
+    Sequential,
+
+    /// Picks any of the other colors of the rainbow at random.
+    Random,
+
+    /// Picks one of the other colors at random, with each color picked in proportion to the
+    /// weight at the same index of the rainbow. Colors without a weight are never picked, and if
+    /// none of the other colors have a weight the rainbow steps through the colors in order.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    Weighted(&'a [u8]),
+}
+
+/// Number of hue steps in one sixth of the color wheel.
+const HUE_SECTOR: i32 = 65_536 / 6;
+
+/// A color in the HSV color space. The hue covers the entire color wheel over the range of a u16,
+/// wrapping back around to red at u16::MAX. Saturation and value go from 0 to 255.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Hsv {
+    pub h: u16,
+    pub s: u8,
+    pub v: u8,
+}
+
+impl Hsv {
+    pub const fn new(h: u16, s: u8, v: u8) -> Self {
+        Hsv { h, s, v }
+    }
+
+    /// Converts the color to RGB. This is the same as `RGB8::from()`, but can be used in consts.
+    pub const fn to_rgb(&self) -> RGB8 {
+        let v = self.v as i32;
+        if self.s == 0 {
+            return RGB8::new(self.v, self.v, self.v);
+        }
+        let s = self.s as i32;
+        let mut region = self.h as i32 / HUE_SECTOR;
+        if region > 5 {
+            region = 5;
+        }
+        let remainder = (self.h as i32 - region * HUE_SECTOR) * 255 / HUE_SECTOR;
+
+        let p = (v * (255 - s) / 255) as u8;
+        let q = (v * (255 - s * remainder / 255) / 255) as u8;
+        let t = (v * (255 - s * (255 - remainder) / 255) / 255) as u8;
+        let v = v as u8;
+
+        match region {
+            0 => RGB8::new(v, t, p),
+            1 => RGB8::new(q, v, p),
+            2 => RGB8::new(p, v, t),
+            3 => RGB8::new(p, q, v),
+            4 => RGB8::new(t, p, v),
+            _ => RGB8::new(v, p, q),
+        }
+    }
+
+    /// Returns the color with its hue rotated around the color wheel by `offset`.
+    pub const fn rotated_by(&self, offset: u16) -> Hsv {
+        Hsv::new(self.h.wrapping_add(offset), self.s, self.v)
+    }
+
+    /// Interpolates between two HSV colors, taking the shortest path around the color wheel.
+    pub fn lerp_with(&self, to_color: Hsv, factor: Progression) -> Hsv {
+        if factor.total == 0 {
+            return *self;
+        }
+        let current = factor.get_current() as i32;
+        let total = factor.total as i32;
+        let lerp = |start: i32, end: i32| start + (end - start) * current / total;
+
+        // wrap the hue difference into -32768..32767 so the fade goes the short way around:
+        let hue_distance = (to_color.h as i32 - self.h as i32 + 32_768).rem_euclid(65_536) - 32_768;
+        let h = (self.h as i32 + hue_distance * current / total).rem_euclid(65_536) as u16;
+
+        Hsv {
+            h,
+            s: lerp(self.s as i32, to_color.s as i32) as u8,
+            v: lerp(self.v as i32, to_color.v as i32) as u8,
+        }
+    }
+}
+
+impl From<RGB8> for Hsv {
+    fn from(color: RGB8) -> Self {
+        let (r, g, b) = (color.r as i32, color.g as i32, color.b as i32);
+        let max = r.max(g).max(b);
+        let min = r.min(g).min(b);
+        let delta = max - min;
+
+        if delta == 0 {
+            return Hsv::new(0, 0, max as u8);
+        }
+
+        let s = (delta * 255 / max) as u8;
+        let h = if max == r {
+            HUE_SECTOR * (g - b) / delta
+        } else if max == g {
+            2 * HUE_SECTOR + HUE_SECTOR * (b - r) / delta
+        } else {
+            4 * HUE_SECTOR + HUE_SECTOR * (r - g) / delta
+        };
+
+        Hsv::new(h.rem_euclid(65_536) as u16, s, max as u8)
+    }
+}
+
+impl From<Hsv> for RGB8 {
+    fn from(color: Hsv) -> Self {
+        color.to_rgb()
+    }
+}
+
+impl From<RGBA8> for Hsv {
+    fn from(color: RGBA8) -> Self {
+        Hsv::from(RGB8::new(color.r, color.g, color.b))
+    }
+}
+
+impl From<Hsv> for RGBA8 {
+    fn from(color: Hsv) -> Self {
+        let color = RGB8::from(color);
+        RGBA8::new(color.r, color.g, color.b, OPAQUE)
+    }
+}
+
+/// A lookup table used to gamma correct colors before they are sent to the LEDs. LEDs respond
+/// linearly to their input values, which makes low values look too bright to the eye and colors
+/// look washed out. Tables are built at compile time when used in a const:
+///
+/// `const MY_GAMMA: GammaTable = GammaTable::new(2.2);`
+#[derive(Copy, Clone)]
+pub struct GammaTable {
+    table: [u8; 256],
+}
+
+impl GammaTable {
+    pub const fn new(gamma: f32) -> Self {
+        let mut table = [0; 256];
+        let mut i = 1;
+        while i < 256 {
+            let corrected = 255.0 * const_powf(i as f32 / 255.0, gamma);
+            table[i] = (corrected + 0.5) as u8;
+            i += 1;
+        }
+        GammaTable { table }
+    }
+
+    pub fn correct(&self, color: RGB8) -> RGB8 {
+        RGB8::new(
+            self.correct_channel(color.r),
+            self.correct_channel(color.g),
+            self.correct_channel(color.b),
+        )
+    }
+
+    /// Gamma corrects the value of a single color channel.
+    pub fn correct_channel(&self, value: u8) -> u8 {
+        self.table[value as usize]
+    }
+}
+
+/// The commonly used gamma value for WS2812 style LEDs.
+pub const GAMMA_2_8: GammaTable = GammaTable::new(2.8);
+
+/// Calculates base^exponent for a base between 0 and 1 without needing the standard library, so
+/// that gamma tables can be generated in const contexts.
+const fn const_powf(base: f32, exponent: f32) -> f32 {
+    if base <= 0.0 {
+        return 0.0;
+    }
+    const_exp(exponent * const_ln(base))
+}
+
+/// Natural log using an atanh series after scaling the value into the range 1..2
+const fn const_ln(value: f32) -> f32 {
+    const LN_2: f32 = core::f32::consts::LN_2;
+    let mut mantissa = value;
+    let mut exponent = 0.0;
+    while mantissa < 1.0 {
+        mantissa *= 2.0;
+        exponent -= 1.0;
+    }
+    while mantissa >= 2.0 {
+        mantissa /= 2.0;
+        exponent += 1.0;
+    }
+
+    let y = (mantissa - 1.0) / (mantissa + 1.0);
+    let y_squared = y * y;
+    let mut term = y;
+    let mut sum = 0.0;
+    let mut n = 1.0;
+    while n < 20.0 {
+        sum += term / n;
+        term *= y_squared;
+        n += 2.0;
+    }
+    2.0 * sum + exponent * LN_2
+}
+
+/// e^value using a taylor series on value / 32, squared back up 5 times
+const fn const_exp(value: f32) -> f32 {
+    let reduced = value / 32.0;
+    let mut term = 1.0;
+    let mut sum = 1.0;
+    let mut n = 1.0;
+    while n < 12.0 {
+        term *= reduced / n;
+        sum += term;
+        n += 1.0;
+    }
+    let mut i = 0;
+    while i < 5 {
+        sum *= sum;
+        i += 1;
+    }
+    sum
 }
 
 pub trait ManipulatableColor<RgbType> {
     fn lerp_with(&self, to_color: RgbType, factor: Progression) -> RgbType;
+    fn lerp_in_space_with(
+        &self,
+        to_color: RgbType,
+        factor: Progression,
+        color_space: ColorSpace,
+    ) -> RgbType;
+    fn scaled_by(&self, brightness: u8) -> RgbType;
+    /// Scales each channel separately by the matching channel of the correction, where 255
+    /// leaves the channel unchanged.
+    fn corrected_by(&self, correction: RgbType) -> RgbType;
     fn set_color(&mut self, c: RgbType);
 }
 
@@ -42,6 +415,35 @@ impl ManipulatableColor<RGB8> for RGB8 {
         )
     }
 
+    fn lerp_in_space_with(
+        &self,
+        to_color: RGB8,
+        factor: Progression,
+        color_space: ColorSpace,
+    ) -> RGB8 {
+        match color_space {
+            ColorSpace::Rgb => self.lerp_with(to_color, factor),
+            ColorSpace::Hsv => Hsv::from(*self)
+                .lerp_with(Hsv::from(to_color), factor)
+                .into(),
+        }
+    }
+
+    fn scaled_by(&self, brightness: u8) -> RGB8 {
+        let scale =
+            |channel: u8| (channel as u16 * brightness as u16 / FULL_BRIGHTNESS as u16) as u8;
+        RGB8::new(scale(self.r), scale(self.g), scale(self.b))
+    }
+
+    fn corrected_by(&self, correction: RGB8) -> RGB8 {
+        let scale = |channel: u8, factor: u8| (channel as u16 * factor as u16 / 255) as u8;
+        RGB8::new(
+            scale(self.r, correction.r),
+            scale(self.g, correction.g),
+            scale(self.b, correction.b),
+        )
+    }
+
     fn set_color(&mut self, c: RGB8) {
         self.r = c.r;
         self.g = c.g;
@@ -49,6 +451,24 @@ impl ManipulatableColor<RGB8> for RGB8 {
     }
 }
 
+// Color corrections for common types of LEDs, used with `LightingController::set_color_correction()`.
+// These balance the brightness of the red, green and blue LEDs so that white looks white:
+pub const UNCORRECTED: RGB8 = RGB8 {
+    r: 255,
+    g: 255,
+    b: 255,
+};
+pub const TYPICAL_LED_STRIP: RGB8 = RGB8 {
+    r: 255,
+    g: 176,
+    b: 240,
+};
+pub const TYPICAL_PIXEL_STRING: RGB8 = RGB8 {
+    r: 255,
+    g: 224,
+    b: 140,
+};
+
 // Generic colors:
 pub const DEEP_BLUE: RGB8 = RGB8 {
     r: 0,
@@ -91,12 +511,307 @@ pub const T_5000K: RGB8 = RGB8 {
     b: 206,
 };
 
+/// The tint of white light at color temperatures from 1000K to 10000K, in steps of 500K up to
+/// 6500K and 1000K above that. 6500K is close to the white of most RGB LEDs.
+const TEMPERATURE_TABLE: [(u16, RGB8); 16] = [
+    (
+        1000,
+        RGB8 {
+            r: 255,
+            g: 56,
+            b: 0,
+        },
+    ),
+    (
+        1500,
+        RGB8 {
+            r: 255,
+            g: 109,
+            b: 0,
+        },
+    ),
+    (
+        2000,
+        RGB8 {
+            r: 255,
+            g: 137,
+            b: 18,
+        },
+    ),
+    (
+        2500,
+        RGB8 {
+            r: 255,
+            g: 161,
+            b: 72,
+        },
+    ),
+    (3000, T_3000K),
+    (3500, T_3500K),
+    (4000, T_4000K),
+    (
+        4500,
+        RGB8 {
+            r: 255,
+            g: 219,
+            b: 186,
+        },
+    ),
+    (5000, T_5000K),
+    (
+        5500,
+        RGB8 {
+            r: 255,
+            g: 236,
+            b: 224,
+        },
+    ),
+    (
+        6000,
+        RGB8 {
+            r: 255,
+            g: 243,
+            b: 239,
+        },
+    ),
+    (
+        6500,
+        RGB8 {
+            r: 255,
+            g: 249,
+            b: 253,
+        },
+    ),
+    (
+        7000,
+        RGB8 {
+            r: 245,
+            g: 243,
+            b: 255,
+        },
+    ),
+    (
+        8000,
+        RGB8 {
+            r: 227,
+            g: 233,
+            b: 255,
+        },
+    ),
+    (
+        9000,
+        RGB8 {
+            r: 214,
+            g: 225,
+            b: 255,
+        },
+    ),
+    (
+        10000,
+        RGB8 {
+            r: 204,
+            g: 219,
+            b: 255,
+        },
+    ),
+];
+
+/// Returns the approximate tint of white light at a color temperature in kelvin, interpolated from
+/// a table. Temperatures outside 1000K to 10000K are clamped to that range. The result can be used
+/// as a color correction to shift the output of a whole strip warmer or cooler.
+pub fn temperature_tint(kelvin: u16) -> RGB8 {
+    let (first_kelvin, first_tint) = TEMPERATURE_TABLE[0];
+    let (last_kelvin, last_tint) = TEMPERATURE_TABLE[TEMPERATURE_TABLE.len() - 1];
+    if kelvin <= first_kelvin {
+        return first_tint;
+    }
+    if kelvin >= last_kelvin {
+        return last_tint;
+    }
+    let above = TEMPERATURE_TABLE
+        .iter()
+        .position(|&(table_kelvin, _)| table_kelvin > kelvin)
+        .unwrap_or(TEMPERATURE_TABLE.len() - 1);
+    let (low_kelvin, low_tint) = TEMPERATURE_TABLE[above - 1];
+    let (high_kelvin, high_tint) = TEMPERATURE_TABLE[above];
+    let mut factor = Progression::new((high_kelvin - low_kelvin) as usize);
+    factor.set_current((kelvin - low_kelvin) as usize);
+    low_tint.lerp_with(high_tint, factor)
+}
+
+/// A single color laid over the whole output of the controller, independent of the animations,
+/// such as flashing everything red on an error, see `LightingController::set_tint()`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Tint {
+    /// Multiplies each channel of every LED by the channels of the color, so white leaves the
+    /// output unchanged and black turns it off.
+    Multiply(RGB8),
+    /// Blends every LED toward the color by an amount from 0, which leaves the output unchanged,
+    /// to 255, which shows only the color.
+    Blend(RGB8, u8),
+}
+
+impl Tint {
+    pub fn apply(&self, color: RGB8) -> RGB8 {
+        match *self {
+            Tint::Multiply(tint) => color.corrected_by(tint),
+            Tint::Blend(tint, amount) => {
+                let factor = Q16::from_ratio(amount as u64, u8::MAX as u64);
+                RGB8::new(
+                    factor.lerp_u8(color.r, tint.r),
+                    factor.lerp_u8(color.g, tint.g),
+                    factor.lerp_u8(color.b, tint.b),
+                )
+            }
+        }
+    }
+}
+
 // Use const generic rainbows to make iterable rainbows of various sizes. Rainbows contain a
 // list of colors in order, which will be used by animations as a color rainbow.
 pub type Rainbow<'a> = &'a [RGB8];
 
+/// Treats a rainbow as a continuous gradient instead of a list of separate colors.
+pub trait SampleableRainbow {
+    /// Returns the color at a position along the rainbow from 0, its first color, to MAX_OFFSET,
+    /// its last color, blending between the neighbouring colors in between. An empty rainbow is
+    /// black.
+    fn sample(&self, position: u16) -> RGB8 {
+        self.sample_in_space(position, ColorSpace::Rgb)
+    }
+
+    /// The same as `sample()`, blending between the colors in a color space.
+    fn sample_in_space(&self, position: u16, color_space: ColorSpace) -> RGB8;
+}
+
+impl SampleableRainbow for [RGB8] {
+    fn sample_in_space(&self, position: u16, color_space: ColorSpace) -> RGB8 {
+        let step_count = match self.len() {
+            0 => return BLACK,
+            1 => return self[0],
+            len => len - 1,
+        };
+        let max_offset = MAX_OFFSET as usize;
+        let scaled = position as usize * step_count;
+        let index = (scaled / max_offset).min(step_count - 1);
+        let mut factor = Progression::new(max_offset);
+        match scaled - index * max_offset {
+            blend if blend >= max_offset => self[index + 1],
+            blend => {
+                factor.set_current(blend);
+                self[index].lerp_in_space_with(self[index + 1], factor, color_space)
+            }
+        }
+    }
+}
+
+/// An owned rainbow holding up to N colors. Unlike a Rainbow, it doesn't borrow its colors, so it
+/// can be deserialized, generated or edited at runtime. It dereferences to a slice of colors, so a
+/// reference to it can be used anywhere a Rainbow is, including in `StatefulRainbow::new()`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RainbowBuf<const N: usize> {
+    colors: ArrayVec<RGB8, N>,
+}
+
+impl<const N: usize> RainbowBuf<N> {
+    pub const fn new() -> Self {
+        RainbowBuf {
+            colors: ArrayVec::new_const(),
+        }
+    }
+
+    /// Copies the colors of a rainbow, keeping only the first N if there are more than that.
+    pub fn from_rainbow(rainbow: Rainbow) -> Self {
+        let mut buf = Self::new();
+        buf.colors.extend(rainbow.iter().copied().take(N));
+        buf
+    }
+
+    pub fn as_rainbow(&self) -> Rainbow<'_> {
+        &self.colors
+    }
+
+    /// Replaces all the colors with those of a rainbow, keeping only the first N if there are more.
+    pub fn set_rainbow(&mut self, rainbow: Rainbow) {
+        self.colors.clear();
+        self.colors.extend(rainbow.iter().copied().take(N));
+    }
+
+    /// Adds a color to the end of the rainbow. Returns false if the rainbow is already full.
+    pub fn push(&mut self, color: RGB8) -> bool {
+        self.colors.try_push(color).is_ok()
+    }
+
+    /// Inserts a color before the color at `index`. Returns false if the rainbow is already full or
+    /// the index is past the end of the rainbow.
+    pub fn insert(&mut self, index: usize, color: RGB8) -> bool {
+        index <= self.colors.len() && self.colors.try_insert(index, color).is_ok()
+    }
+
+    /// Removes and returns the color at `index`, if there is one.
+    pub fn remove(&mut self, index: usize) -> Option<RGB8> {
+        self.colors.pop_at(index)
+    }
+
+    pub fn clear(&mut self) {
+        self.colors.clear();
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.colors.is_full()
+    }
+
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+}
+
+impl<const N: usize> Deref for RainbowBuf<N> {
+    type Target = [RGB8];
+
+    fn deref(&self) -> &[RGB8] {
+        &self.colors
+    }
+}
+
+impl<const N: usize> DerefMut for RainbowBuf<N> {
+    fn deref_mut(&mut self) -> &mut [RGB8] {
+        &mut self.colors
+    }
+}
+
+impl<'a, const N: usize> From<Rainbow<'a>> for RainbowBuf<N> {
+    fn from(rainbow: Rainbow<'a>) -> Self {
+        Self::from_rainbow(rainbow)
+    }
+}
+
+impl<'a, const N: usize> From<&'a RainbowBuf<N>> for Rainbow<'a> {
+    fn from(buf: &'a RainbowBuf<N>) -> Self {
+        buf.as_rainbow()
+    }
+}
+
+impl<const N: usize> FromIterator<RGB8> for RainbowBuf<N> {
+    /// Collects colors into a rainbow, keeping only the first N.
+    fn from_iter<I: IntoIterator<Item = RGB8>>(iter: I) -> Self {
+        let mut buf = Self::new();
+        buf.colors.extend(iter.into_iter().take(N));
+        buf
+    }
+}
+
 pub const R_BLACK: Rainbow = &[BLACK];
 pub const R_WHITE: Rainbow = &[WHITE];
+
+/// The rainbow that deserialized parameters start with in place of the borrowed rainbow, which
+/// isn't serialized. It can't be empty, since the modes index into it.
+#[cfg(feature = "serde")]
+pub(crate) fn deserialized_rainbow() -> Rainbow<'static> {
+    R_WHITE
+}
 pub const R_RED: Rainbow = &[RED];
 pub const R_ORANGE: Rainbow = &[ORANGE];
 pub const R_YELLOW: Rainbow = &[YELLOW];
@@ -139,6 +854,129 @@ pub const fn dark_pattern(base: RGB8) -> [RGB8; 6] {
     colors
 }
 
+/// Generates a rainbow of N colors fading evenly from `start` to `end`, including both ends.
+pub const fn gradient<const N: usize>(start: RGB8, end: RGB8) -> [RGB8; N] {
+    let mut colors = [BLACK; N];
+    let steps = if N > 1 { N as i32 - 1 } else { 1 };
+    let mut i = 0;
+    while i < N {
+        let step = i as i32;
+        colors[i] = RGB8 {
+            r: channel_lerp(start.r, end.r, step, steps),
+            g: channel_lerp(start.g, end.g, step, steps),
+            b: channel_lerp(start.b, end.b, step, steps),
+        };
+        i += 1;
+    }
+    colors
+}
+
+const fn channel_lerp(from: u8, to: u8, step: i32, steps: i32) -> u8 {
+    (from as i32 + (to as i32 - from as i32) * step / steps) as u8
+}
+
+/// Generates a rainbow of N colors with hues evenly spaced around the whole color wheel, starting
+/// at red.
+pub const fn hue_wheel<const N: usize>(saturation: u8, value: u8) -> [RGB8; N] {
+    let mut colors = [BLACK; N];
+    let mut i = 0;
+    while i < N {
+        let hue = (i * 65_536 / N) as u16;
+        colors[i] = Hsv::new(hue, saturation, value).to_rgb();
+        i += 1;
+    }
+    colors
+}
+
+/// Generates a rainbow of the base color and the color opposite it on the color wheel.
+pub const fn complementary(base: Hsv) -> [RGB8; 2] {
+    [base.to_rgb(), base.rotated_by(32_768).to_rgb()]
+}
+
+/// Generates a rainbow of the base color and the two colors a third of the way around the color
+/// wheel from it on either side.
+pub const fn triadic(base: Hsv) -> [RGB8; 3] {
+    [
+        base.to_rgb(),
+        base.rotated_by(21_845).to_rgb(),
+        base.rotated_by(43_691).to_rgb(),
+    ]
+}
+
+/// Generates a rainbow of the base color and its neighbors on the color wheel, `spread` hue steps
+/// away on either side. A spread of around 5461 (a twelfth of the wheel) is typical.
+pub const fn analogous(base: Hsv, spread: u16) -> [RGB8; 3] {
+    [
+        base.rotated_by(spread.wrapping_neg()).to_rgb(),
+        base.to_rgb(),
+        base.rotated_by(spread).to_rgb(),
+    ]
+}
+
+// The functions below derive new rainbows from existing ones, so they can be used to build
+// rainbow consts such as `&reversed::<4>(R_ROYGBIV)`. N is the length of the new rainbow: past the
+// end of the colors it takes, it is padded with black, and colors past N are cut off.
+
+/// Generates a rainbow with the colors of another in reverse order.
+pub const fn reversed<const N: usize>(rainbow: Rainbow) -> [RGB8; N] {
+    let mut colors = [BLACK; N];
+    let mut i = 0;
+    while i < N && i < rainbow.len() {
+        colors[i] = rainbow[rainbow.len() - 1 - i];
+        i += 1;
+    }
+    colors
+}
+
+/// Generates a rainbow that starts at the color at index `start` of another and wraps around past
+/// its end, so with an N longer than the rainbow its colors are repeated instead of padded.
+pub const fn rotated<const N: usize>(rainbow: Rainbow, start: usize) -> [RGB8; N] {
+    let mut colors = [BLACK; N];
+    if rainbow.is_empty() {
+        return colors;
+    }
+    let mut i = 0;
+    while i < N {
+        colors[i] = rainbow[(start + i) % rainbow.len()];
+        i += 1;
+    }
+    colors
+}
+
+/// Generates a rainbow from every `step`th color of another, starting with its first color. A step
+/// of 0 is treated as 1.
+pub const fn subsampled<const N: usize>(rainbow: Rainbow, step: usize) -> [RGB8; N] {
+    let step = if step == 0 { 1 } else { step };
+    let mut colors = [BLACK; N];
+    let mut i = 0;
+    while i < N && i * step < rainbow.len() {
+        colors[i] = rainbow[i * step];
+        i += 1;
+    }
+    colors
+}
+
+/// Returns the number of colors `subsampled()` takes from a rainbow of `len` colors, for use as its
+/// N.
+pub const fn subsampled_len(len: usize, step: usize) -> usize {
+    let step = if step == 0 { 1 } else { step };
+    len.div_ceil(step)
+}
+
+/// Generates a rainbow with the colors of `first` followed by the colors of `second`.
+pub const fn concat<const N: usize>(first: Rainbow, second: Rainbow) -> [RGB8; N] {
+    let mut colors = [BLACK; N];
+    let mut i = 0;
+    while i < N && i < first.len() + second.len() {
+        colors[i] = match i < first.len() {
+            true => first[i],
+            false => second[i - first.len()],
+        };
+        i += 1;
+    }
+    colors
+}
+
 pub const R_DARK_RED_PATTERN: Rainbow = &dark_pattern(RED);
 pub const R_DARK_YELLOW_PATTERN: Rainbow = &dark_pattern(YELLOW);
 pub const R_DARK_GREEN_PATTERN: Rainbow = &dark_pattern(LIME);
@@ -146,11 +984,58 @@ pub const R_DARK_SKY_BLUE_PATTERN: Rainbow = &dark_pattern(CYAN);
 pub const R_DARK_BLUE_PATTERN: Rainbow = &dark_pattern(BLUE);
 pub const R_DARK_PURPLE_PATTERN: Rainbow = &dark_pattern(FUCHSIA);
 pub const R_WHITE_PATTERN: Rainbow = &dark_pattern(WHITE);
+pub const R_HUE_WHEEL: Rainbow = &hue_wheel::<12>(255, 255);
+pub const R_SUNSET: Rainbow = &gradient::<8>(RED, DARK_PURPLE);
 pub const R_VU_METER: Rainbow = &[
     LIME, LIME, LIME, LIME, LIME, LIME, LIME, YELLOW, YELLOW, RED,
 ];
+pub const R_STATUS: Rainbow = &[RED, YELLOW, LIME];
 
-pub const NUM_RAINBOWS: usize = 31;
+// The Okabe-Ito palette, whose colors stay distinguishable from each other with the common kinds
+// of color blindness. It was picked for screens, so it looks closest on LEDs with gamma correction:
+pub const CB_ORANGE: RGB8 = RGB8::new(230, 159, 0);
+pub const CB_SKY_BLUE: RGB8 = RGB8::new(86, 180, 233);
+pub const CB_BLUISH_GREEN: RGB8 = RGB8::new(0, 158, 115);
+pub const CB_YELLOW: RGB8 = RGB8::new(240, 228, 66);
+pub const CB_BLUE: RGB8 = RGB8::new(0, 114, 178);
+pub const CB_VERMILLION: RGB8 = RGB8::new(213, 94, 0);
+pub const CB_REDDISH_PURPLE: RGB8 = RGB8::new(204, 121, 167);
+
+pub const R_OKABE_ITO: Rainbow = &[
+    CB_ORANGE,
+    CB_SKY_BLUE,
+    CB_BLUISH_GREEN,
+    CB_YELLOW,
+    CB_BLUE,
+    CB_VERMILLION,
+    CB_REDDISH_PURPLE,
+];
+/// Paul Tol's bright qualitative palette.
+pub const R_TOL_BRIGHT: Rainbow = &[
+    RGB8::new(68, 119, 170),
+    RGB8::new(238, 102, 119),
+    RGB8::new(34, 136, 51),
+    RGB8::new(204, 187, 68),
+    RGB8::new(102, 204, 238),
+    RGB8::new(170, 51, 119),
+];
+/// The viridis color map from dark purple to yellow, which also keeps rising in brightness so it
+/// reads in order without any color vision.
+pub const R_VIRIDIS: Rainbow = &[
+    RGB8::new(68, 1, 84),
+    RGB8::new(70, 50, 126),
+    RGB8::new(54, 92, 141),
+    RGB8::new(39, 127, 142),
+    RGB8::new(31, 161, 135),
+    RGB8::new(74, 193, 109),
+    RGB8::new(160, 218, 57),
+    RGB8::new(253, 231, 37),
+];
+pub const R_CB_BLUE_ORANGE: Rainbow = &[CB_BLUE, BLACK, CB_ORANGE, BLACK];
+/// A replacement for R_STATUS that doesn't rely on telling red from green.
+pub const R_CB_STATUS: Rainbow = &[CB_VERMILLION, CB_YELLOW, CB_SKY_BLUE];
+
+pub const NUM_RAINBOWS: usize = 36;
 
 /// This is an array of the rainbow consts above that can be used to cycle through rainbows in animations.
 pub const RAINBOW_ARRAY: [&[RGB8]; NUM_RAINBOWS] = [
@@ -185,4 +1070,106 @@ pub const RAINBOW_ARRAY: [&[RGB8]; NUM_RAINBOWS] = [
     R_DARK_BLUE_PATTERN,
     R_DARK_PURPLE_PATTERN,
     R_WHITE_PATTERN,
+    R_OKABE_ITO,
+    R_TOL_BRIGHT,
+    R_VIRIDIS,
+    R_CB_BLUE_ORANGE,
+    R_CB_STATUS,
 ];
+
+/// Groups the built-in rainbows by the kind of palette they are, so that a UI can offer them by
+/// kind instead of by their index in RAINBOW_ARRAY.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum RainbowCategory {
+    /// A single color.
+    Solid,
+
+    /// Several bright colors one after another.
+    Multicolor,
+
+    /// Colors with black between them, so that each color stands out on its own.
+    Striped,
+
+    /// Dim shades of a single color.
+    Dim,
+
+    /// Colors that can still be told apart with the common kinds of color blindness.
+    ColorblindSafe,
+}
+
+/// The name and category of one of the rainbows in RAINBOW_ARRAY.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct RainbowInfo {
+    pub name: &'static str,
+    pub category: RainbowCategory,
+}
+
+impl RainbowInfo {
+    const fn new(name: &'static str, category: RainbowCategory) -> Self {
+        RainbowInfo { name, category }
+    }
+}
+
+/// The name and category of each rainbow, at the same index as the rainbow in RAINBOW_ARRAY.
+pub const RAINBOW_INFO: [RainbowInfo; NUM_RAINBOWS] = {
+    use RainbowCategory::*;
+    [
+        RainbowInfo::new("Black", Solid),
+        RainbowInfo::new("White", Solid),
+        RainbowInfo::new("Red", Solid),
+        RainbowInfo::new("Orange", Solid),
+        RainbowInfo::new("Yellow", Solid),
+        RainbowInfo::new("Chartreuse", Solid),
+        RainbowInfo::new("Lime", Solid),
+        RainbowInfo::new("Spring Green", Solid),
+        RainbowInfo::new("Cyan", Solid),
+        RainbowInfo::new("Deep Blue", Solid),
+        RainbowInfo::new("Blue", Solid),
+        RainbowInfo::new("Blue Purple", Solid),
+        RainbowInfo::new("Fuchsia", Solid),
+        RainbowInfo::new("Dark Purple", Solid),
+        RainbowInfo::new("Rainbow", Multicolor),
+        RainbowInfo::new("Red Yellow Blue", Striped),
+        RainbowInfo::new("Orange Green Purple", Striped),
+        RainbowInfo::new("Red Green Blue", Striped),
+        RainbowInfo::new("Blue Yellow", Striped),
+        RainbowInfo::new("Red Cyan", Striped),
+        RainbowInfo::new("Orange Blue", Striped),
+        RainbowInfo::new("Blue White", Striped),
+        RainbowInfo::new("Red White", Striped),
+        RainbowInfo::new("Green White", Striped),
+        RainbowInfo::new("Dark Red", Dim),
+        RainbowInfo::new("Dark Yellow", Dim),
+        RainbowInfo::new("Dark Green", Dim),
+        RainbowInfo::new("Dark Sky Blue", Dim),
+        RainbowInfo::new("Dark Blue", Dim),
+        RainbowInfo::new("Dark Purple Pattern", Dim),
+        RainbowInfo::new("Dark White", Dim),
+        RainbowInfo::new("Okabe-Ito", ColorblindSafe),
+        RainbowInfo::new("Tol Bright", ColorblindSafe),
+        RainbowInfo::new("Viridis", ColorblindSafe),
+        RainbowInfo::new("Blue Orange", ColorblindSafe),
+        RainbowInfo::new("Status", ColorblindSafe),
+    ]
+};
+
+/// Returns the indexes in RAINBOW_ARRAY of the rainbows in a category, along with their info,
+/// in the order they are listed.
+pub fn rainbows_in(
+    category: RainbowCategory,
+) -> impl Iterator<Item = (usize, &'static RainbowInfo)> {
+    RAINBOW_INFO
+        .iter()
+        .enumerate()
+        .filter(move |(_, info)| info.category == category)
+}
+
+/// Returns the index in RAINBOW_ARRAY of the rainbow with a name, ignoring case.
+pub fn find_rainbow(name: &str) -> Option<usize> {
+    RAINBOW_INFO
+        .iter()
+        .position(|info| info.name.eq_ignore_ascii_case(name))
+}
@@ -0,0 +1,294 @@
+//! Passing changes to the LightingController from interrupt handlers. An interrupt pushes Commands
+//! onto a CommandQueue through its Producer, and the task that renders the frames applies them all
+//! at the start of the next frame through the Consumer, so neither side needs to hold a critical
+//! section around the whole controller.
+//!
+//! Firmware that only fires triggers from its interrupts, such as from an encoder or a key matrix
+//! scan, can use the smaller TriggerQueue instead. Its consumer is handed to the controller with
+//! `LightingController::set_trigger_source()`, and the queued triggers are fired during every
+//! `update()`.
+//!
+//! The queues are single producer, single consumer ring buffers that only use atomic loads and
+//! stores, so they also work on cores without atomic compare and swap, such as the Cortex-M0.
+
+use crate::animations::{trigger, AnimationParameters, AnimationType};
+use core::cell::UnsafeCell;
+use core::mem::MaybeUninit;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// A change to make to the LightingController, matching one of its methods.
+#[derive(Copy, Clone)]
+pub enum Command<'a> {
+    Trigger {
+        animation_index: usize,
+        parameters: trigger::Parameters,
+    },
+    SetOffset {
+        animation_index: usize,
+        animation_type: AnimationType,
+        offset: u16,
+    },
+    SetInputLevel {
+        animation_index: usize,
+        level: u8,
+    },
+    SetBrightness(u8),
+    SetAnimationBrightness {
+        animation_index: usize,
+        brightness: u8,
+    },
+    SetMaskLevel {
+        animation_index: usize,
+        led_index: usize,
+        level: u8,
+    },
+    SetParameters {
+        animation_index: usize,
+        parameters: &'a AnimationParameters<'a>,
+    },
+}
+
+/// The ring buffer behind the queues. One slot is always left empty to tell a full buffer from an
+/// empty one, so it holds up to N - 1 items.
+struct Ring<T, const N: usize> {
+    buffer: UnsafeCell<[MaybeUninit<T>; N]>,
+    // the index of the next item to be read, only changed by the consumer:
+    head: AtomicUsize,
+    // the index of the next slot to be written, only changed by the producer:
+    tail: AtomicUsize,
+}
+
+impl<T: Copy, const N: usize> Ring<T, N> {
+    const fn new() -> Self {
+        // the indices wrap around modulo N, so a buffer without any slots fails to compile:
+        const { assert!(N > 0, "a queue needs at least one slot") };
+        Ring {
+            buffer: UnsafeCell::new([MaybeUninit::uninit(); N]),
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+        }
+    }
+
+    /// Adds an item to the end of the buffer, returning false if it is full.
+    ///
+    /// # Safety
+    ///
+    /// Only one producer may push to the buffer at a time.
+    unsafe fn push(&self, item: T) -> bool {
+        let tail = self.tail.load(Ordering::Relaxed);
+        let next_tail = (tail + 1) % N;
+        if next_tail == self.head.load(Ordering::Acquire) {
+            return false;
+        }
+        // Only the producer writes to the slot at the tail, and the consumer won't read it until
+        // the new tail is stored below:
+        unsafe {
+            (*self.buffer.get())[tail] = MaybeUninit::new(item);
+        }
+        self.tail.store(next_tail, Ordering::Release);
+        true
+    }
+
+    /// Takes the oldest item from the buffer, if there is one.
+    ///
+    /// # Safety
+    ///
+    /// Only one consumer may pop from the buffer at a time.
+    unsafe fn pop(&self) -> Option<T> {
+        let head = self.head.load(Ordering::Relaxed);
+        if head == self.tail.load(Ordering::Acquire) {
+            return None;
+        }
+        // The producer has finished writing every slot before the tail, and won't write to this
+        // one again until the new head is stored below:
+        let item = unsafe { (*self.buffer.get())[head].assume_init() };
+        self.head.store((head + 1) % N, Ordering::Release);
+        Some(item)
+    }
+}
+
+/// A fixed size queue of commands. One slot is always left empty to tell a full queue from an
+/// empty one, so it holds up to N - 1 commands.
+pub struct CommandQueue<'a, const N: usize> {
+    ring: Ring<Command<'a>, N>,
+}
+
+// The Producer and Consumer never access the same slot at the same time, so sharing the queue
+// between an interrupt and a task only moves each command from one to the other, which is safe
+// for commands that can be sent between them.
+unsafe impl<'a, const N: usize> Sync for CommandQueue<'a, N> where Command<'a>: Send {}
+
+impl<'a, const N: usize> CommandQueue<'a, N> {
+    pub const fn new() -> Self {
+        CommandQueue { ring: Ring::new() }
+    }
+
+    /// Splits the queue into the Producer that adds commands, and the Consumer that takes them.
+    pub fn split(&mut self) -> (Producer<'_, 'a, N>, Consumer<'_, 'a, N>) {
+        (Producer { queue: self }, Consumer { queue: self })
+    }
+}
+
+impl<'a, const N: usize> Default for CommandQueue<'a, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The half of a CommandQueue that adds commands, usually owned by an interrupt handler.
+pub struct Producer<'q, 'a, const N: usize> {
+    queue: &'q CommandQueue<'a, N>,
+}
+
+unsafe impl<'q, 'a, const N: usize> Send for Producer<'q, 'a, N> where Command<'a>: Send {}
+
+impl<'q, 'a, const N: usize> Producer<'q, 'a, N> {
+    /// Adds a command to the end of the queue. Returns false and drops the command if the queue
+    /// is full.
+    pub fn push(&mut self, command: Command<'a>) -> bool {
+        // there is only one Producer for each queue:
+        unsafe { self.queue.ring.push(command) }
+    }
+}
+
+/// The half of a CommandQueue that takes commands, usually owned by the task that renders frames.
+pub struct Consumer<'q, 'a, const N: usize> {
+    queue: &'q CommandQueue<'a, N>,
+}
+
+unsafe impl<'q, 'a, const N: usize> Send for Consumer<'q, 'a, N> where Command<'a>: Send {}
+
+impl<'q, 'a, const N: usize> Consumer<'q, 'a, N> {
+    /// Takes the oldest command from the queue, if there is one.
+    pub fn pop(&mut self) -> Option<Command<'a>> {
+        // there is only one Consumer for each queue:
+        unsafe { self.queue.ring.pop() }
+    }
+}
+
+/// A trigger waiting in a TriggerQueue to be fired on an animation.
+#[derive(Copy, Clone)]
+pub struct QueuedTrigger {
+    pub animation_index: usize,
+    pub parameters: trigger::Parameters,
+}
+
+/// Something the LightingController takes triggers from during every update, see
+/// `LightingController::set_trigger_source()`.
+pub trait TriggerSource {
+    /// Takes the oldest trigger waiting to be fired, if there is one.
+    fn next_trigger(&mut self) -> Option<QueuedTrigger>;
+}
+
+/// A fixed size queue of triggers, filled from interrupt handlers and drained by the controller.
+/// Like the CommandQueue, it holds up to N - 1 triggers.
+pub struct TriggerQueue<const N: usize> {
+    ring: Ring<QueuedTrigger, N>,
+}
+
+// Queued triggers are plain data, so like the CommandQueue, it can be shared between an interrupt
+// and the controller.
+unsafe impl<const N: usize> Sync for TriggerQueue<N> {}
+
+impl<const N: usize> TriggerQueue<N> {
+    pub const fn new() -> Self {
+        TriggerQueue { ring: Ring::new() }
+    }
+
+    /// Splits the queue into the producer that adds triggers, and the consumer that the
+    /// controller takes them from.
+    pub fn split(&mut self) -> (TriggerProducer<'_, N>, TriggerConsumer<'_, N>) {
+        (
+            TriggerProducer { queue: self },
+            TriggerConsumer { queue: self },
+        )
+    }
+}
+
+impl<const N: usize> Default for TriggerQueue<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The half of a TriggerQueue that adds triggers, usually owned by an interrupt handler.
+pub struct TriggerProducer<'q, const N: usize> {
+    queue: &'q TriggerQueue<N>,
+}
+
+impl<'q, const N: usize> TriggerProducer<'q, N> {
+    /// Queues a trigger to be fired on an animation during the next update. Returns false and
+    /// drops the trigger if the queue is full.
+    pub fn push(&mut self, animation_index: usize, parameters: trigger::Parameters) -> bool {
+        let trigger = QueuedTrigger {
+            animation_index,
+            parameters,
+        };
+        // there is only one TriggerProducer for each queue:
+        unsafe { self.queue.ring.push(trigger) }
+    }
+}
+
+/// The half of a TriggerQueue that takes triggers, usually handed to the LightingController.
+pub struct TriggerConsumer<'q, const N: usize> {
+    queue: &'q TriggerQueue<N>,
+}
+
+impl<'q, const N: usize> TriggerSource for TriggerConsumer<'q, N> {
+    fn next_trigger(&mut self) -> Option<QueuedTrigger> {
+        // there is only one TriggerConsumer for each queue:
+        unsafe { self.queue.ring.pop() }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn brightness(brightness: u8) -> Command<'static> {
+        Command::SetBrightness(brightness)
+    }
+
+    fn popped_brightness(consumer: &mut Consumer<'_, '_, 4>) -> Option<u8> {
+        match consumer.pop()? {
+            Command::SetBrightness(brightness) => Some(brightness),
+            _ => panic!("popped a command that wasn't pushed"),
+        }
+    }
+
+    #[test]
+    fn queue_holds_one_less_than_its_size() {
+        let mut queue = CommandQueue::<4>::new();
+        let (mut producer, mut consumer) = queue.split();
+        assert!(producer.push(brightness(1)));
+        assert!(producer.push(brightness(2)));
+        assert!(producer.push(brightness(3)));
+        assert!(!producer.push(brightness(4)));
+        assert_eq!(popped_brightness(&mut consumer), Some(1));
+        assert!(producer.push(brightness(4)));
+        assert!(!producer.push(brightness(5)));
+    }
+
+    #[test]
+    fn queue_pops_in_order_and_then_runs_empty() {
+        let mut queue = CommandQueue::<4>::new();
+        let (mut producer, mut consumer) = queue.split();
+        assert_eq!(popped_brightness(&mut consumer), None);
+        // enough rounds to wrap the indices around the buffer a few times:
+        for round in 0..5 {
+            assert!(producer.push(brightness(round * 2)));
+            assert!(producer.push(brightness(round * 2 + 1)));
+            assert_eq!(popped_brightness(&mut consumer), Some(round * 2));
+            assert_eq!(popped_brightness(&mut consumer), Some(round * 2 + 1));
+            assert_eq!(popped_brightness(&mut consumer), None);
+        }
+    }
+
+    #[test]
+    fn queue_of_one_slot_holds_nothing() {
+        let mut queue = CommandQueue::<1>::new();
+        let (mut producer, mut consumer) = queue.split();
+        assert!(!producer.push(brightness(1)));
+        assert!(consumer.pop().is_none());
+    }
+}
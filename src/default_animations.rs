@@ -1,5 +1,6 @@
 use crate::animations::*;
 use crate::colors as c;
+use crate::easing::{Easing, Waveform};
 
 /// This value is used as a default value for the number of subdivisions on the const animations at
 /// the end of the file. Typically this number should be 1 for shorter strips, and higher as you add
@@ -21,8 +22,20 @@ pub const BG_OFF: background::Parameters = background::Parameters {
     rainbow: c::R_BLACK,
     direction: Direction::Stopped,
     is_rainbow_forward: true,
+    color_space: c::ColorSpace::Rgb,
+    color_order: c::ColorOrder::Sequential,
+    easing: Easing::Linear,
     duration_ns: 0,
     subdivisions: DEFAULT_NUMBER_OF_SUBDIVISIONS,
+    waveform: Waveform::Sine,
+    min_brightness: c::FULL_BRIGHTNESS,
+    max_brightness: c::FULL_BRIGHTNESS,
+    spawn_interval_ns: 500_000_000,
+    trail_decay: 200,
+    run_mode: RunMode::Loop,
+    wash: background::Wash::None,
+    seed: 0,
+    update_divisor: 1,
 };
 
 /// This foreground parameter struct can be used to turn off all foreground effects
@@ -31,17 +44,36 @@ pub const FG_OFF: foreground::Parameters = foreground::Parameters {
     rainbow: c::R_BLACK,
     direction: Direction::Stopped,
     is_rainbow_forward: true,
+    color_space: c::ColorSpace::Rgb,
+    color_order: c::ColorOrder::Sequential,
+    easing: Easing::Linear,
     duration_ns: 0,
     step_time_ns: 0,
     subdivisions: DEFAULT_NUMBER_OF_SUBDIVISIONS,
     pixels_per_pixel_group: DEFAULT_NUMBER_OF_PIXELS_PER_MARQUEE_PIP,
+    trail_length: 0,
+    spacing: 3,
+    text: "",
+    zone_thresholds: &[],
+    blink_below: 0,
+    alpha: c::OPAQUE,
+    blend_mode: c::BlendMode::Normal,
+    run_mode: RunMode::Loop,
+    seed: 0,
 };
 
 /// This global trigger parameter struct can be used to turn off all trigger effects.
 pub const TRIGGER_OFF: trigger::GlobalParameters = trigger::GlobalParameters {
     rainbow: c::R_BLACK,
     is_rainbow_forward: true,
+    color_space: c::ColorSpace::Rgb,
+    color_order: c::ColorOrder::Sequential,
     duration_ns: 0,
+    alpha: c::OPAQUE,
+    eviction_policy: trigger::EvictionPolicy::RejectNewest,
+    retrigger_interval_ns: 0,
+    retrigger_policy: trigger::RetriggerPolicy::Drop,
+    seed: 0,
 };
 
 /// This animation parameter struct will turn off ALL animations: fg, bg, and trigger.
@@ -57,8 +89,20 @@ pub const BG_TEST: background::Parameters = background::Parameters {
     rainbow: c::R_ROYGBIV,
     direction: Direction::Positive,
     is_rainbow_forward: true,
+    color_space: c::ColorSpace::Rgb,
+    color_order: c::ColorOrder::Sequential,
+    easing: Easing::Linear,
     duration_ns: 30_000_000_000,
     subdivisions: 0,
+    waveform: Waveform::Sine,
+    min_brightness: c::FULL_BRIGHTNESS,
+    max_brightness: c::FULL_BRIGHTNESS,
+    spawn_interval_ns: 500_000_000,
+    trail_decay: 200,
+    run_mode: RunMode::Loop,
+    wash: background::Wash::None,
+    seed: 0,
+    update_divisor: 1,
 };
 
 /// This is an animation foreground struct used for testing
@@ -67,17 +111,65 @@ pub const FG_TEST: foreground::Parameters = foreground::Parameters {
     rainbow: c::R_ROYGBIV,
     direction: Direction::Positive,
     is_rainbow_forward: true,
+    color_space: c::ColorSpace::Rgb,
+    color_order: c::ColorOrder::Sequential,
+    easing: Easing::Linear,
     duration_ns: 10_000_000_000,
     step_time_ns: 1_000_000_000,
     subdivisions: DEFAULT_NUMBER_OF_SUBDIVISIONS,
     pixels_per_pixel_group: 1,
+    trail_length: 0,
+    spacing: 3,
+    text: "",
+    zone_thresholds: &[],
+    blink_below: 0,
+    alpha: c::OPAQUE,
+    blend_mode: c::BlendMode::Normal,
+    run_mode: RunMode::Loop,
+    seed: 0,
+};
+
+/// The values where FG_STATUS_BAR turns from red to yellow, and from yellow to green.
+pub const STATUS_THRESHOLDS: &[u16] = &[MAX_OFFSET / 5, MAX_OFFSET / 2];
+
+/// A battery style status bar, set with `LightingController::set_progress()`. The bar is red
+/// below a fifth full, yellow below half full and green above that, and it blinks twice a second
+/// below a tenth full.
+pub const FG_STATUS_BAR: foreground::Parameters = foreground::Parameters {
+    mode: foreground::Mode::StatusBar,
+    rainbow: c::R_STATUS,
+    direction: Direction::Positive,
+    is_rainbow_forward: true,
+    color_space: c::ColorSpace::Rgb,
+    color_order: c::ColorOrder::Sequential,
+    easing: Easing::Linear,
+    duration_ns: 1_000_000_000,
+    step_time_ns: 250_000_000,
+    subdivisions: DEFAULT_NUMBER_OF_SUBDIVISIONS,
+    pixels_per_pixel_group: 1,
+    trail_length: 0,
+    spacing: 3,
+    text: "",
+    zone_thresholds: STATUS_THRESHOLDS,
+    blink_below: MAX_OFFSET / 10,
+    alpha: c::OPAQUE,
+    blend_mode: c::BlendMode::Normal,
+    run_mode: RunMode::Loop,
+    seed: 0,
 };
 
 /// This is an animation trigger struct used for testing
 pub const TRIGGER_TEST: trigger::GlobalParameters = trigger::GlobalParameters {
     rainbow: c::R_ROYGBIV,
     is_rainbow_forward: true,
+    color_space: c::ColorSpace::Rgb,
+    color_order: c::ColorOrder::Sequential,
     duration_ns: 10_000_000_000,
+    alpha: c::OPAQUE,
+    eviction_policy: trigger::EvictionPolicy::RejectNewest,
+    retrigger_interval_ns: 0,
+    retrigger_policy: trigger::RetriggerPolicy::Drop,
+    seed: 0,
 };
 
 /// This animation parameter struct will turn off ALL animations: fg, bg, and trigger.
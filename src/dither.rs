@@ -0,0 +1,44 @@
+//! Temporal dithering of the output. At low brightness there are only a few steps between black
+//! and the dimmest colors, so fades visibly jump from one step to the next. With dithering, the
+//! controller's output corrections are calculated with 16 bits per channel, and each LED switches
+//! between the two nearest 8 bit values from frame to frame so that it averages out to the more
+//! precise color.
+//!
+//! Dithering keeps the leftover error of every channel of every LED, so it needs a buffer of one
+//! RGB16 per LED on the strip, see `LightingController::enable_dithering()`.
+
+use crate::output::CHANNEL_STEP;
+use rgb::{RGB16, RGB8};
+
+/// The leftover errors of the dithered LEDs on a strip.
+pub struct Dither<'a> {
+    accumulators: &'a mut [RGB16],
+}
+
+impl<'a> Dither<'a> {
+    /// Makes a ditherer for a strip with one accumulator for each LED.
+    pub fn new(accumulators: &'a mut [RGB16]) -> Self {
+        accumulators.fill(RGB16::default());
+        Dither { accumulators }
+    }
+
+    /// Reduces a 16 bit color to 8 bits for the LED at `index` of the strip, carrying the part
+    /// that was rounded off over to the LED's next frame. LEDs without an accumulator are
+    /// rounded to the nearest 8 bit color.
+    pub fn dither(&mut self, index: usize, color: RGB16) -> RGB8 {
+        let Some(accumulator) = self.accumulators.get_mut(index) else {
+            return crate::output::reduce(color);
+        };
+        let dither = |channel: u16, error: &mut u16| {
+            let total = channel as u32 + *error as u32;
+            let output = (total / CHANNEL_STEP).min(255);
+            *error = (total - output * CHANNEL_STEP).min(CHANNEL_STEP - 1) as u16;
+            output as u8
+        };
+        RGB8::new(
+            dither(color.r, &mut accumulator.r),
+            dither(color.g, &mut accumulator.g),
+            dither(color.b, &mut accumulator.b),
+        )
+    }
+}
@@ -0,0 +1,141 @@
+//! DMX512 output. Frames from the LogicalStrip are packed into DMX universes of up to 512
+//! channels, one channel per color channel of each LED, so the controller can drive DMX fixtures
+//! and LED dimmers as well as addressable strips. LEDs are never split across two universes, so
+//! a frame of RGB LEDs fills 170 LEDs (510 channels) per universe before moving on to the next.
+
+use crate::output::{OutputColor, RGBW8};
+use rgb::RGB8;
+
+/// The start code sent before the channel data of a normal DMX512 packet.
+pub const NULL_START_CODE: u8 = 0x00;
+
+/// The number of channels in a full DMX universe.
+pub const CHANNELS_PER_UNIVERSE: usize = 512;
+
+/// A color type that can be sent as DMX channels.
+pub trait DmxColor: OutputColor {
+    /// The number of DMX channels used by each LED.
+    const CHANNELS: usize;
+
+    /// Writes the channels of the color to the start of `channels`, which is CHANNELS long.
+    fn write_channels(&self, channels: &mut [u8]);
+}
+
+impl DmxColor for RGB8 {
+    const CHANNELS: usize = 3;
+
+    fn write_channels(&self, channels: &mut [u8]) {
+        channels[..Self::CHANNELS].copy_from_slice(&[self.r, self.g, self.b]);
+    }
+}
+
+impl<S: Copy> DmxColor for RGBW8<S>
+where
+    RGBW8<S>: OutputColor,
+{
+    const CHANNELS: usize = 4;
+
+    fn write_channels(&self, channels: &mut [u8]) {
+        channels[..Self::CHANNELS].copy_from_slice(&[self.r, self.g, self.b, self.w]);
+    }
+}
+
+/// The number of LEDs that fit in a single universe.
+pub const fn leds_per_universe<C: DmxColor>() -> usize {
+    CHANNELS_PER_UNIVERSE / C::CHANNELS
+}
+
+/// The number of universes needed to send a frame with `led_count` LEDs.
+pub const fn universes_needed<C: DmxColor>(led_count: usize) -> usize {
+    led_count.div_ceil(leds_per_universe::<C>())
+}
+
+/// A single DMX512 packet: the start code followed by the channel data. Only the channels that
+/// have been written are sent, so packets for short strips are shorter and can be sent faster.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Universe {
+    start_code: u8,
+    channels: [u8; CHANNELS_PER_UNIVERSE],
+    channel_count: usize,
+}
+
+impl Universe {
+    pub const fn new() -> Self {
+        Universe {
+            start_code: NULL_START_CODE,
+            channels: [0; CHANNELS_PER_UNIVERSE],
+            channel_count: 0,
+        }
+    }
+
+    pub fn start_code(&self) -> u8 {
+        self.start_code
+    }
+
+    /// Changes the start code, for fixtures that use an alternate start code.
+    pub fn set_start_code(&mut self, start_code: u8) {
+        self.start_code = start_code;
+    }
+
+    /// The channel data that will be sent, without the start code.
+    pub fn channels(&self) -> &[u8] {
+        &self.channels[..self.channel_count]
+    }
+
+    /// Sets a single channel, numbered from 1 as on DMX fixtures. Channels past the end of the
+    /// universe are ignored, and any unwritten channels before it are sent as 0.
+    pub fn set_channel(&mut self, channel: usize, value: u8) {
+        if channel == 0 || channel > CHANNELS_PER_UNIVERSE {
+            return;
+        }
+        self.channels[channel - 1] = value;
+        self.channel_count = self.channel_count.max(channel);
+    }
+
+    /// Sets every channel back to 0 and sends no channels until more are written.
+    pub fn clear(&mut self) {
+        self.channels = [0; CHANNELS_PER_UNIVERSE];
+        self.channel_count = 0;
+    }
+
+    /// Copies the start code and channel data into `packet`, ready to be sent after the break and
+    /// mark after break. Returns the number of bytes written, or None if `packet` is too short.
+    pub fn write_packet(&self, packet: &mut [u8]) -> Option<usize> {
+        let length = self.channel_count + 1;
+        let packet = packet.get_mut(..length)?;
+        packet[0] = self.start_code;
+        packet[1..].copy_from_slice(self.channels());
+        Some(length)
+    }
+
+    /// Packs the LEDs of a frame that belong to `universe_index` into this universe. Universes
+    /// past the end of the frame are left empty.
+    pub fn encode<C: DmxColor>(&mut self, frame: &[C], universe_index: usize) {
+        self.clear();
+        let per_universe = leds_per_universe::<C>();
+        let leds = frame
+            .chunks(per_universe)
+            .nth(universe_index)
+            .unwrap_or_default();
+        for (led, channels) in leds.iter().zip(self.channels.chunks_exact_mut(C::CHANNELS)) {
+            led.write_channels(channels);
+        }
+        self.channel_count = leds.len() * C::CHANNELS;
+    }
+}
+
+impl Default for Universe {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Packs a whole frame into consecutive universes, starting from the first. Returns the number of
+/// universes used. LEDs that don't fit in the universes given are not sent.
+pub fn encode<C: DmxColor>(frame: &[C], universes: &mut [Universe]) -> usize {
+    let used = universes_needed::<C>(frame.len()).min(universes.len());
+    for (index, universe) in universes.iter_mut().enumerate() {
+        universe.encode(frame, index);
+    }
+    used
+}
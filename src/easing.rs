@@ -0,0 +1,167 @@
+//! Easing curves that change how a progression moves from start to finish. All the curves use
+//! integer math, so they are cheap to calculate on microcontrollers without an FPU.
+
+use crate::utility::Progression;
+
+/// The fixed point value used to represent 1.0 when calculating easing curves.
+pub const EASING_SCALE: u32 = 1 << 16;
+
+/// The curve used to go from the start to the end of a fade or movement.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Easing {
+    /// Moves at a constant speed.
+    Linear,
+
+    /// Starts slowly and speeds up.
+    QuadraticIn,
+
+    /// Starts quickly and slows down.
+    QuadraticOut,
+
+    /// Starts and ends slowly, moving fastest through the middle.
+    QuadraticInOut,
+
+    /// Like QuadraticIn, but with a more pronounced curve.
+    CubicIn,
+
+    /// Like QuadraticOut, but with a more pronounced curve.
+    CubicOut,
+
+    /// Like QuadraticInOut, but with a more pronounced curve.
+    CubicInOut,
+
+    /// Starts and ends slowly following a sine wave.
+    SineInOut,
+
+    /// Starts extremely slowly and speeds up exponentially.
+    ExponentialIn,
+
+    /// Starts extremely quickly and slows down exponentially.
+    ExponentialOut,
+
+    /// Hits the end quickly and bounces back off of it a few times before settling, like a
+    /// dropped ball.
+    BounceOut,
+}
+
+impl Easing {
+    /// Maps a progress value between 0 and EASING_SCALE onto the easing curve. The result is also
+    /// between 0 and EASING_SCALE.
+    pub fn apply(&self, progress: u32) -> u32 {
+        let x = progress.min(EASING_SCALE) as u64;
+        let s = EASING_SCALE as u64;
+        let eased = match self {
+            Easing::Linear => x,
+            Easing::QuadraticIn => x * x / s,
+            Easing::QuadraticOut => s - (s - x) * (s - x) / s,
+            Easing::QuadraticInOut => match x < s / 2 {
+                true => 2 * x * x / s,
+                false => s - 2 * (s - x) * (s - x) / s,
+            },
+            Easing::CubicIn => x * x / s * x / s,
+            Easing::CubicOut => s - (s - x) * (s - x) / s * (s - x) / s,
+            Easing::CubicInOut => match x < s / 2 {
+                true => 4 * x * x / s * x / s,
+                false => s - 4 * (s - x) * (s - x) / s * (s - x) / s,
+            },
+            Easing::SineInOut => {
+                // sin^2(x * pi / 2) using Bhaskara's sine approximation, where pi cancels out:
+                let a = (x / 2) * (s - x / 2) / s;
+                let sine = 16 * a * s / (5 * s - 4 * a);
+                sine * sine / s
+            }
+            Easing::ExponentialIn => exponential_in(x),
+            Easing::ExponentialOut => s - exponential_in(s - x),
+            Easing::BounceOut => bounce_out(x),
+        };
+        eased.min(s) as u32
+    }
+
+    /// Returns a new progression that follows the easing curve, which can be used anywhere a
+    /// progression is used to calculate a fade or movement.
+    pub fn ease(&self, progression: Progression) -> Progression {
+        if *self == Easing::Linear || progression.total == 0 {
+            return progression;
+        }
+        let progress = (progression.get_current() as u64 * EASING_SCALE as u64
+            / progression.total as u64) as u32;
+        let mut eased = Progression::new(EASING_SCALE as usize);
+        eased.set_current(self.apply(progress) as usize);
+        eased
+    }
+}
+
+/// The shape of a repeating wave, used for effects that rise and fall over and over.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Waveform {
+    /// Rises and falls smoothly, spending more time near the top and bottom.
+    Sine,
+
+    /// Rises and falls at a constant speed.
+    Triangle,
+
+    /// Stays at the bottom for the first half of the period and at the top for the second half.
+    Square,
+}
+
+impl Waveform {
+    /// Returns the level of the wave at a point in its period. Both the point and the level are
+    /// between 0 and EASING_SCALE, and every wave starts at its lowest level.
+    pub fn level(&self, phase: u32) -> u32 {
+        let x = phase.min(EASING_SCALE);
+        let half = EASING_SCALE / 2;
+        let triangle = match x < half {
+            true => 2 * x,
+            false => 2 * (EASING_SCALE - x),
+        };
+        match self {
+            Waveform::Sine => Easing::SineInOut.apply(triangle),
+            Waveform::Triangle => triangle,
+            Waveform::Square => match x < half {
+                true => 0,
+                false => EASING_SCALE,
+            },
+        }
+    }
+
+    /// Returns the level of the wave at the current point of a progression through one period.
+    pub fn level_at(&self, progression: Progression) -> u32 {
+        if progression.total == 0 {
+            return 0;
+        }
+        let phase = progression.get_current() as u64 * EASING_SCALE as u64;
+        self.level((phase / progression.total as u64) as u32)
+    }
+}
+
+/// 2^(10x - 10), using a linear approximation between powers of two.
+fn exponential_in(x: u64) -> u64 {
+    let s = EASING_SCALE as u64;
+    if x == 0 {
+        return 0;
+    }
+    let exponent = 10 * x;
+    let whole = exponent / s;
+    let fraction = exponent % s;
+    ((s + fraction) << whole) >> 10
+}
+
+fn bounce_out(x: u64) -> u64 {
+    let s = EASING_SCALE as i64;
+    let x = x as i64;
+    let curve = |x: i64| 121 * x * x / (16 * s);
+    let bounce = if x < s * 4 / 11 {
+        curve(x)
+    } else if x < s * 8 / 11 {
+        curve(x - s * 6 / 11) + s * 3 / 4
+    } else if x < s * 10 / 11 {
+        curve(x - s * 9 / 11) + s * 15 / 16
+    } else {
+        curve(x - s * 21 / 22) + s * 63 / 64
+    };
+    bounce as u64
+}
@@ -0,0 +1,36 @@
+//! An async render loop for [embassy](https://embassy.dev) projects. The loop waits for each frame
+//! on an embassy-time Ticker running at the LightingController's frame rate, updates the
+//! animations, and then hands the finished frame to a FrameSink to be sent to the LEDs.
+
+use crate::output::OutputColor;
+use crate::{LightingController, LogicalStrip};
+use core::future::Future;
+use embassy_time::{Duration, Ticker};
+use embedded_time::fixed_point::FixedPoint;
+
+/// Receives each finished frame from the render loop, such as an async LED driver or a channel
+/// that passes the frame on to another task.
+pub trait FrameSink<C: OutputColor> {
+    /// Sends the colors of every LED on the strip, in physical order.
+    fn flush(&mut self, frame: &[C]) -> impl Future<Output = ()>;
+}
+
+impl<'a, const N_ANI: usize> LightingController<'a, N_ANI> {
+    /// Runs the animations forever at the controller's frame rate, flushing every frame that
+    /// changed to the sink. If rendering and flushing a frame takes longer than the frame period,
+    /// the ticker catches up by starting the next frames straight away.
+    pub async fn run<C, S>(&mut self, logical_strip: &mut LogicalStrip<'_, C>, sink: &mut S) -> !
+    where
+        C: OutputColor,
+        S: FrameSink<C>,
+    {
+        let frame_rate = self.frame_rate().integer().max(1) as u64;
+        let mut ticker = Ticker::every(Duration::from_hz(frame_rate));
+        loop {
+            ticker.next().await;
+            if self.update(logical_strip) {
+                sink.flush(logical_strip.as_slice()).await;
+            }
+        }
+    }
+}
@@ -0,0 +1,79 @@
+//! Fixed-point math for the fractions used in animations, such as how far through a fade or a
+//! rotation an animation is. Working in fixed-point keeps the math fast on microcontrollers
+//! without an FPU, and rounding to the nearest value instead of truncating keeps motion and fades
+//! smooth when there are only a few frames to spread them over.
+
+use crate::utility::Progression;
+
+/// An unsigned fraction from 0 to 1 with 16 fractional bits, so 1.0 is `1 << 16`. A Q16 is also
+/// a fraction of a full turn of an offset, where one turn is `MAX_OFFSET + 1`.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Q16(u32);
+
+impl Q16 {
+    pub const FRACTIONAL_BITS: u32 = 16;
+    pub const ZERO: Q16 = Q16(0);
+    pub const ONE: Q16 = Q16(1 << Self::FRACTIONAL_BITS);
+
+    /// Makes a fraction from its raw bits, clamped to 1.0.
+    pub const fn from_bits(bits: u32) -> Self {
+        match bits > Self::ONE.0 {
+            true => Self::ONE,
+            false => Q16(bits),
+        }
+    }
+
+    pub const fn to_bits(self) -> u32 {
+        self.0
+    }
+
+    /// Returns `numerator / denominator` rounded to the nearest Q16, clamped to 1.0. A
+    /// denominator of 0 gives 0.
+    pub const fn from_ratio(numerator: u64, denominator: u64) -> Self {
+        if denominator == 0 {
+            return Self::ZERO;
+        }
+        let bits = ((numerator << Self::FRACTIONAL_BITS) + denominator / 2) / denominator;
+        match bits > Self::ONE.0 as u64 {
+            true => Self::ONE,
+            false => Q16(bits as u32),
+        }
+    }
+
+    /// Returns how far through a Progression its current frame is.
+    pub fn from_progression(progression: Progression) -> Self {
+        Self::from_ratio(progression.get_current() as u64, progression.total as u64)
+    }
+
+    /// Returns `1.0 - self`.
+    pub const fn complement(self) -> Self {
+        Q16(Self::ONE.0 - self.0)
+    }
+
+    /// Multiplies a value by the fraction, rounding to the nearest integer.
+    pub const fn scale(self, value: u32) -> u32 {
+        ((value as u64 * self.0 as u64 + (1 << (Self::FRACTIONAL_BITS - 1)))
+            >> Self::FRACTIONAL_BITS) as u32
+    }
+
+    /// Interpolates between two values, rounding to the nearest integer.
+    pub const fn lerp(self, start: i32, end: i32) -> i32 {
+        let distance = (end as i64 - start as i64) * self.0 as i64;
+        start + ((distance + (1 << (Self::FRACTIONAL_BITS - 1))) >> Self::FRACTIONAL_BITS) as i32
+    }
+
+    /// Interpolates between two channel values, rounding to the nearest integer.
+    pub const fn lerp_u8(self, start: u8, end: u8) -> u8 {
+        self.lerp(start as i32, end as i32) as u8
+    }
+
+    /// Interpolates between two 16 bit channel values, rounding to the nearest integer.
+    pub const fn lerp_u16(self, start: u16, end: u16) -> u16 {
+        self.lerp(start as i32, end as i32) as u16
+    }
+
+    /// Converts the fraction into an offset, where 1.0 is a full turn and wraps back around to 0.
+    pub const fn to_offset(self) -> u16 {
+        self.0 as u16
+    }
+}
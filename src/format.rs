@@ -0,0 +1,201 @@
+//! defmt Format implementations for the parameter types that hold colors or rainbows, which can't
+//! derive it because the rgb crate doesn't support defmt. Colors are written as hex codes, such as
+//! `#ff8000`. The simpler types derive Format where they are defined.
+
+use crate::animations::{background, foreground, trigger, CustomAnimation};
+use crate::colors::{RainbowBuf, Tint};
+use defmt::{Format, Formatter};
+use rgb::RGB8;
+
+/// Writes a color as a hex code.
+struct Hex(RGB8);
+
+impl Format for Hex {
+    fn format(&self, f: Formatter) {
+        let RGB8 { r, g, b } = self.0;
+        defmt::write!(f, "#{=u8:02x}{=u8:02x}{=u8:02x}", r, g, b);
+    }
+}
+
+/// Writes the colors of a rainbow as a list of hex codes.
+struct Colors<'c>(&'c [RGB8]);
+
+impl Format for Colors<'_> {
+    fn format(&self, f: Formatter) {
+        defmt::write!(f, "[");
+        for (index, &color) in self.0.iter().enumerate() {
+            match index {
+                0 => defmt::write!(f, "{}", Hex(color)),
+                _ => defmt::write!(f, ", {}", Hex(color)),
+            }
+        }
+        defmt::write!(f, "]");
+    }
+}
+
+impl Format for dyn CustomAnimation + '_ {
+    fn format(&self, f: Formatter) {
+        defmt::write!(f, "CustomAnimation");
+    }
+}
+
+impl<const N: usize> Format for RainbowBuf<N> {
+    fn format(&self, f: Formatter) {
+        defmt::write!(f, "RainbowBuf {}", Colors(&self[..]));
+    }
+}
+
+impl Format for Tint {
+    fn format(&self, f: Formatter) {
+        match *self {
+            Tint::Multiply(color) => defmt::write!(f, "Multiply({})", Hex(color)),
+            Tint::Blend(color, amount) => {
+                defmt::write!(f, "Blend({}, {=u8})", Hex(color), amount)
+            }
+        }
+    }
+}
+
+impl Format for background::Wash<'_> {
+    fn format(&self, f: Formatter) {
+        match *self {
+            background::Wash::None => defmt::write!(f, "None"),
+            background::Wash::Solid(color) => defmt::write!(f, "Solid({})", Hex(color)),
+            background::Wash::Rainbow(rainbow) => {
+                defmt::write!(f, "Rainbow({})", Colors(rainbow))
+            }
+        }
+    }
+}
+
+impl Format for trigger::Mode {
+    fn format(&self, f: Formatter) {
+        use trigger::Mode;
+        match *self {
+            Mode::NoTrigger => defmt::write!(f, "NoTrigger"),
+            Mode::Background => defmt::write!(f, "Background"),
+            Mode::Foreground => defmt::write!(f, "Foreground"),
+            Mode::ColorPulse => defmt::write!(f, "ColorPulse"),
+            Mode::ColorPulseFade => defmt::write!(f, "ColorPulseFade"),
+            Mode::ColorPulseRainbow => defmt::write!(f, "ColorPulseRainbow"),
+            Mode::ColorShot => defmt::write!(f, "ColorShot"),
+            Mode::ColorShotFade => defmt::write!(f, "ColorShotFade"),
+            Mode::ColorShotRainbow => defmt::write!(f, "ColorShotRainbow"),
+            Mode::Comet => defmt::write!(f, "Comet"),
+            Mode::Flash => defmt::write!(f, "Flash"),
+            Mode::FlashFade => defmt::write!(f, "FlashFade"),
+            Mode::FlashRainbow => defmt::write!(f, "FlashRainbow"),
+            Mode::Sparkle => defmt::write!(f, "Sparkle"),
+            Mode::Strobe(color) => defmt::write!(f, "Strobe({})", Hex(color)),
+            Mode::Wipe(color) => defmt::write!(f, "Wipe({})", Hex(color)),
+            Mode::WipeCenterOut(color) => defmt::write!(f, "WipeCenterOut({})", Hex(color)),
+            Mode::Ripple => defmt::write!(f, "Ripple"),
+            Mode::Custom(_) => defmt::write!(f, "Custom"),
+        }
+    }
+}
+
+impl Format for background::Parameters<'_> {
+    fn format(&self, f: Formatter) {
+        defmt::write!(
+            f,
+            "background::Parameters {{ mode: {}, rainbow: {}, direction: {}, is_rainbow_forward: {=bool}, color_space: {}, color_order: {}, easing: {}, duration_ns: {=u64}, subdivisions: {=usize}, waveform: {}, min_brightness: {=u8}, max_brightness: {=u8}, spawn_interval_ns: {=u64}, trail_decay: {=u8}, run_mode: {}, wash: {}, seed: {=u32}, update_divisor: {=u32} }}",
+            self.mode,
+            Colors(self.rainbow),
+            self.direction,
+            self.is_rainbow_forward,
+            self.color_space,
+            self.color_order,
+            self.easing,
+            self.duration_ns,
+            self.subdivisions,
+            self.waveform,
+            self.min_brightness,
+            self.max_brightness,
+            self.spawn_interval_ns,
+            self.trail_decay,
+            self.run_mode,
+            self.wash,
+            self.seed,
+            self.update_divisor,
+        );
+    }
+}
+
+impl Format for foreground::Parameters<'_> {
+    fn format(&self, f: Formatter) {
+        defmt::write!(
+            f,
+            "foreground::Parameters {{ mode: {}, rainbow: {}, direction: {}, is_rainbow_forward: {=bool}, color_space: {}, color_order: {}, easing: {}, duration_ns: {=u64}, step_time_ns: {=u64}, subdivisions: {=usize}, pixels_per_pixel_group: {=usize}, trail_length: {=usize}, spacing: {=usize}, text: {=str}, zone_thresholds: {=[?]}, blink_below: {=u16}, alpha: {=u8}, blend_mode: {}, run_mode: {}, seed: {=u32} }}",
+            self.mode,
+            Colors(self.rainbow),
+            self.direction,
+            self.is_rainbow_forward,
+            self.color_space,
+            self.color_order,
+            self.easing,
+            self.duration_ns,
+            self.step_time_ns,
+            self.subdivisions,
+            self.pixels_per_pixel_group,
+            self.trail_length,
+            self.spacing,
+            self.text,
+            self.zone_thresholds,
+            self.blink_below,
+            self.alpha,
+            self.blend_mode,
+            self.run_mode,
+            self.seed,
+        );
+    }
+}
+
+impl Format for trigger::Parameters {
+    fn format(&self, f: Formatter) {
+        defmt::write!(
+            f,
+            "trigger::Parameters {{ mode: {}, direction: {}, easing: {}, fade_in_time_ns: {=u64}, fade_out_time_ns: {=u64}, decay_time_ns: {=u64}, sustain_level: {=u8}, sustain_time_ns: {=u64}, starting_offset: {=u16}, pixels_per_pixel_group: {=usize}, priority: {=u8}, density: {=u16}, strobe_count: {=u16}, strobe_period_ns: {=u64}, strobe_duty_cycle: {=u8}, leaves_color_behind: {=bool}, ripple_origin: {}, ripple_speed: {=u16}, target: {}, blend_mode: {}, rainbow: {} }}",
+            self.mode,
+            self.direction,
+            self.easing,
+            self.fade_in_time_ns,
+            self.fade_out_time_ns,
+            self.decay_time_ns,
+            self.sustain_level,
+            self.sustain_time_ns,
+            self.starting_offset,
+            self.pixels_per_pixel_group,
+            self.priority,
+            self.density,
+            self.strobe_count,
+            self.strobe_period_ns,
+            self.strobe_duty_cycle,
+            self.leaves_color_behind,
+            self.ripple_origin,
+            self.ripple_speed,
+            self.target,
+            self.blend_mode,
+            self.rainbow.map(Colors),
+        );
+    }
+}
+
+impl Format for trigger::GlobalParameters<'_> {
+    fn format(&self, f: Formatter) {
+        defmt::write!(
+            f,
+            "trigger::GlobalParameters {{ rainbow: {}, is_rainbow_forward: {=bool}, color_space: {}, color_order: {}, duration_ns: {=u64}, alpha: {=u8}, eviction_policy: {}, retrigger_interval_ns: {=u64}, retrigger_policy: {}, seed: {=u32} }}",
+            Colors(self.rainbow),
+            self.is_rainbow_forward,
+            self.color_space,
+            self.color_order,
+            self.duration_ns,
+            self.alpha,
+            self.eviction_policy,
+            self.retrigger_interval_ns,
+            self.retrigger_policy,
+            self.seed,
+        );
+    }
+}
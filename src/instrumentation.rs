@@ -0,0 +1,122 @@
+//! Timing of the work done for each frame, for finding out which effect is blowing the frame
+//! budget. The controller reads a counter supplied by the firmware, such as the Cortex-M DWT cycle
+//! counter or a microsecond timer, before and after each layer is drawn, and keeps the costs of
+//! the last few frames.
+//!
+//! Set the counter with `LightingController::enable_instrumentation()`, then read the costs with
+//! `LightingController::frame_stats()`. The costs are in whatever unit the counter counts in.
+
+/// The number of frames the costs are kept for.
+pub const STATS_WINDOW: usize = 16;
+
+/// A free running counter, such as a cycle counter or a microsecond timer. It is expected to wrap
+/// around, so only the difference between two readings is used.
+pub trait CycleCounter {
+    fn now(&self) -> u32;
+}
+
+/// The costs of the last STATS_WINDOW frames of a single stage of the update.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Stats {
+    samples: [u32; STATS_WINDOW],
+    next: usize,
+    count: usize,
+}
+
+impl Stats {
+    /// Adds the cost of a frame, replacing the oldest cost once the window is full.
+    pub fn record(&mut self, cost: u32) {
+        self.samples[self.next] = cost;
+        self.next = (self.next + 1) % STATS_WINDOW;
+        self.count = (self.count + 1).min(STATS_WINDOW);
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Returns the costs that are in the window, oldest first when it isn't full yet.
+    fn samples(&self) -> &[u32] {
+        &self.samples[..self.count]
+    }
+
+    /// Returns the lowest cost in the window, or 0 before any frames are recorded.
+    pub fn min(&self) -> u32 {
+        self.samples().iter().copied().min().unwrap_or(0)
+    }
+
+    /// Returns the highest cost in the window, or 0 before any frames are recorded.
+    pub fn max(&self) -> u32 {
+        self.samples().iter().copied().max().unwrap_or(0)
+    }
+
+    /// Returns the mean cost in the window, or 0 before any frames are recorded.
+    pub fn average(&self) -> u32 {
+        let total: u64 = self.samples().iter().map(|&cost| cost as u64).sum();
+        (total / self.count.max(1) as u64) as u32
+    }
+}
+
+/// The costs of a single frame of an animation, split by layer.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct LayerCosts {
+    pub background: u32,
+    pub foreground: u32,
+    pub triggers: u32,
+}
+
+impl LayerCosts {
+    /// Adds the costs of another frame or animation onto these.
+    pub fn add(&mut self, other: LayerCosts) {
+        self.background = self.background.saturating_add(other.background);
+        self.foreground = self.foreground.saturating_add(other.foreground);
+        self.triggers = self.triggers.saturating_add(other.triggers);
+    }
+}
+
+/// The rolling costs of each stage of the controller's updates. The layer costs add up every
+/// animation, and `output` is the cost of correcting the colors and writing them to the strip.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct FrameStats {
+    pub background: Stats,
+    pub foreground: Stats,
+    pub triggers: Stats,
+    pub output: Stats,
+}
+
+impl FrameStats {
+    /// Records the costs of one update of the controller.
+    pub fn record(&mut self, layers: LayerCosts, output: u32) {
+        self.background.record(layers.background);
+        self.foreground.record(layers.foreground);
+        self.triggers.record(layers.triggers);
+        self.output.record(output);
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+}
+
+/// Measures the time between readings of a counter.
+pub(crate) struct Stopwatch<'c> {
+    counter: &'c dyn CycleCounter,
+    last: u32,
+}
+
+impl<'c> Stopwatch<'c> {
+    pub(crate) fn start(counter: &'c dyn CycleCounter) -> Self {
+        Stopwatch {
+            counter,
+            last: counter.now(),
+        }
+    }
+
+    /// Returns the counts since the stopwatch was started or last lapped.
+    pub(crate) fn lap(&mut self) -> u32 {
+        let now = self.counter.now();
+        let elapsed = now.wrapping_sub(self.last);
+        self.last = now;
+        elapsed
+    }
+}
@@ -1,44 +1,241 @@
 #![no_std]
 
+#[cfg(feature = "wled")]
+extern crate alloc;
+#[cfg(feature = "std")]
+extern crate std;
+
+/// Logs a state change at the trace level with the `defmt` feature, and does nothing without it.
+/// This is defined before the modules so that they can all use it.
+macro_rules! trace {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "defmt")]
+        defmt::trace!($($arg)*);
+    };
+}
+
+#[cfg(feature = "smart-leds")]
+pub mod adapter;
 pub mod animations;
+pub mod apa102;
+pub mod artnet;
 pub mod colors;
+pub mod commands;
 pub mod default_animations;
+#[cfg(feature = "dithering")]
+pub mod dither;
+pub mod dmx;
+pub mod easing;
+#[cfg(feature = "embassy")]
+pub mod embassy;
+pub mod fixed;
+#[cfg(feature = "defmt")]
+mod format;
+#[cfg(feature = "instrumentation")]
+pub mod instrumentation;
+pub mod mapping;
+pub mod midi;
+pub mod output;
+pub mod pacing;
+pub mod power;
+pub mod presets;
+pub mod protocol;
+#[cfg(feature = "sacn")]
+pub mod sacn;
+pub mod schedule;
+pub mod sequencer;
+#[cfg(feature = "std")]
+pub mod simulator;
+pub mod snapshot;
+pub mod split;
+pub mod state;
+pub mod supersample;
+pub mod sync;
+pub mod text;
 pub mod utility;
+#[cfg(feature = "wled")]
+pub mod wled;
 
-use crate::animations::{Animatable, AnimationType};
-use crate::colors::ManipulatableColor;
+use crate::animations::{Animatable, AnimationType, Direction, LayerObserver};
+use crate::colors::{GammaTable, ManipulatableColor, FULL_BRIGHTNESS};
+use crate::commands::{Command, Consumer, TriggerSource};
+#[cfg(feature = "instrumentation")]
+use crate::instrumentation::{CycleCounter, FrameStats, LayerCosts, Stopwatch};
+use crate::output::{OutputColor, PostProcessor, MAX_POST_PROCESSORS};
+use crate::power::PowerBudget;
+use crate::presets::PresetBank;
+use crate::split::SplitStrip;
+use crate::state::{ControllerState, StateError};
+use arrayvec::ArrayVec;
+use embedded_time::duration::Nanoseconds;
+use embedded_time::fixed_point::FixedPoint;
 use embedded_time::rate::Hertz;
 use rgb::RGB8;
 
-pub struct LogicalStrip<'a> {
-    pub color_buffer: &'a mut [RGB8],
+/// The colors of every LED on a physical strip. The color type defaults to RGB8, but can be any
+/// OutputColor, such as `output::RGBW8` for strips with a white channel.
+pub struct LogicalStrip<'a, C: OutputColor = RGB8> {
+    pub color_buffer: &'a mut [C],
 }
 
-impl<'a> LogicalStrip<'a> {
-    pub fn new(color_buffer: &'a mut [RGB8]) -> Self {
+impl<'a, C: OutputColor> LogicalStrip<'a, C> {
+    pub fn new(color_buffer: &'a mut [C]) -> Self {
         LogicalStrip { color_buffer }
     }
 
-    pub fn get_color_at_index(&self, index: usize) -> RGB8 {
+    pub fn get_color_at_index(&self, index: usize) -> C {
         self.color_buffer[index]
     }
 
     // this sets the color value in the color array at index:
     pub fn set_color_at_index(&mut self, index: usize, color: RGB8) {
-        self.color_buffer[index].set_color(color);
+        self.color_buffer[index] = C::from_rgb8(color);
+    }
+
+    // this is the flattened color data for every led on the strip, ready to send to a driver:
+    pub fn as_slice(&self) -> &[C] {
+        self.color_buffer
+    }
+
+    pub fn iter(&self) -> core::slice::Iter<'_, C> {
+        self.color_buffer.iter()
     }
 
     // this fills the entire strip with a single color:
     pub fn set_strip_to_solid_color(&mut self, color: RGB8) {
+        let color = C::from_rgb8(color);
         for c in &mut self.color_buffer.iter_mut() {
-            c.set_color(color);
+            *c = color;
         }
     }
 }
 
+/// A pair of LogicalStrips for drivers that send the LEDs out with DMA while the next frame is
+/// drawn. The controller updates the back strip, and the front strip is left alone for the DMA
+/// to read until `swap()` is called, so long strips don't show half of one frame and half of the
+/// next:
+///
+/// - take both strips with `let (front, back) = buffers.split()`,
+/// - call `controller.update(back)` to draw the next frame while the DMA sends `front`,
+/// - wait for the DMA to finish, then call `swap()` and start sending the new front.
+pub struct DoubleBuffer<'a, C: OutputColor = RGB8> {
+    strips: [LogicalStrip<'a, C>; 2],
+    front: usize,
+}
+
+impl<'a, C: OutputColor> DoubleBuffer<'a, C> {
+    /// Makes a double buffer from two color buffers of the same length.
+    pub fn new(first: &'a mut [C], second: &'a mut [C]) -> Self {
+        DoubleBuffer {
+            strips: [LogicalStrip::new(first), LogicalStrip::new(second)],
+            front: 0,
+        }
+    }
+
+    /// Returns the strip that the next frame is drawn into.
+    pub fn back(&mut self) -> &mut LogicalStrip<'a, C> {
+        &mut self.strips[1 - self.front]
+    }
+
+    /// Returns the last frame that was swapped to the front. It doesn't change until the next
+    /// call to `swap()`.
+    pub fn front(&self) -> &[C] {
+        self.strips[self.front].as_slice()
+    }
+
+    /// Returns the front frame and the back strip at the same time, so the next frame can be
+    /// drawn while the front one is being sent.
+    pub fn split(&mut self) -> (&[C], &mut LogicalStrip<'a, C>) {
+        let [first, second] = &mut self.strips;
+        match self.front {
+            0 => (first.as_slice(), second),
+            _ => (second.as_slice(), first),
+        }
+    }
+
+    /// Brings the back strip to the front by exchanging the two strips, without copying any
+    /// colors. The new back strip still holds the frame before last, so what `update()` returns
+    /// for it compares the new frame with that one rather than with the front frame. Swap after
+    /// every frame drawn instead of only when an update reports a change.
+    pub fn swap(&mut self) {
+        self.front = 1 - self.front;
+    }
+}
+
+/// Sets the color of an LED on the strip, and returns whether it changed. LEDs past the end of the
+/// strip, such as from tiled copies that don't fit, are left out.
+fn replace_color<C: OutputColor>(
+    logical_strip: &mut LogicalStrip<C>,
+    index: usize,
+    color: C,
+) -> bool {
+    match logical_strip.color_buffer.get_mut(index) {
+        Some(slot) => core::mem::replace(slot, color) != color,
+        None => false,
+    }
+}
+
+/// The number of LEDs `LightingController::iter_pixels()` works out at a time. Each chunk goes
+/// through every animation once, so bigger chunks are faster for long strips but take more stack.
+pub const PIXEL_CHUNK: usize = 32;
+
+/// An iterator over the output colors of a frame, see `LightingController::iter_pixels()`.
+pub struct Pixels<'c, 'a, const N_ANI: usize> {
+    controller: &'c LightingController<'a, N_ANI>,
+    chunk: [RGB8; PIXEL_CHUNK],
+    next: usize,
+    led_count: usize,
+    power_scale: u8,
+}
+
+impl<const N_ANI: usize> Iterator for Pixels<'_, '_, N_ANI> {
+    type Item = RGB8;
+
+    fn next(&mut self) -> Option<RGB8> {
+        if self.next >= self.led_count {
+            return None;
+        }
+        let offset = self.next % PIXEL_CHUNK;
+        if offset == 0 {
+            let length = (self.led_count - self.next).min(PIXEL_CHUNK);
+            let chunk = &mut self.chunk[..length];
+            let channel_order = self.controller.channel_order;
+            self.controller
+                .render_chunk(self.next, chunk, self.power_scale, channel_order);
+        }
+        self.next += 1;
+        Some(self.chunk[offset])
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.led_count.saturating_sub(self.next);
+        (remaining, Some(remaining))
+    }
+}
+
+impl<const N_ANI: usize> ExactSizeIterator for Pixels<'_, '_, N_ANI> {}
+
 pub struct LightingController<'a, const N_ANI: usize> {
     animations: [&'a mut dyn Animatable<'a>; N_ANI],
     frame_rate: Hertz,
+    gamma: Option<GammaTable>,
+    brightness: u8,
+    power_budget: Option<PowerBudget>,
+    color_corrections: [RGB8; N_ANI],
+    temperature: RGB8,
+    tint: Option<colors::Tint>,
+    channel_order: output::ChannelOrder,
+    preset_ids: [Option<u8>; N_ANI],
+    output_level: output::LevelFade,
+    trigger_source: Option<&'a mut dyn TriggerSource>,
+    post_frame: Option<&'a mut [RGB8]>,
+    post_processors: ArrayVec<&'a mut dyn PostProcessor, MAX_POST_PROCESSORS>,
+    #[cfg(feature = "dithering")]
+    dither: Option<dither::Dither<'a>>,
+    #[cfg(feature = "instrumentation")]
+    cycle_counter: Option<&'a dyn CycleCounter>,
+    #[cfg(feature = "instrumentation")]
+    frame_stats: FrameStats,
 }
 
 impl<'a, const N_ANI: usize> LightingController<'a, N_ANI> {
@@ -51,32 +248,1100 @@ impl<'a, const N_ANI: usize> LightingController<'a, N_ANI> {
         LightingController {
             animations,
             frame_rate,
+            gamma: None,
+            brightness: FULL_BRIGHTNESS,
+            power_budget: None,
+            color_corrections: [colors::UNCORRECTED; N_ANI],
+            temperature: colors::UNCORRECTED,
+            tint: None,
+            channel_order: output::ChannelOrder::Rgb,
+            preset_ids: [None; N_ANI],
+            output_level: output::LevelFade::steady(FULL_BRIGHTNESS),
+            trigger_source: None,
+            post_frame: None,
+            post_processors: ArrayVec::new(),
+            #[cfg(feature = "dithering")]
+            dither: None,
+            #[cfg(feature = "instrumentation")]
+            cycle_counter: None,
+            #[cfg(feature = "instrumentation")]
+            frame_stats: FrameStats::default(),
         }
     }
 
-    pub fn update(&mut self, logical_strip: &mut LogicalStrip) {
+    /// Sets the color temperature when building the controller, see `set_temperature()`.
+    pub fn with_temperature(mut self, kelvin: u16) -> Self {
+        self.set_temperature(kelvin);
+        self
+    }
+
+    /// Updates every animation and writes the new frame to the strip. Returns whether any LED on
+    /// the strip changed, so the firmware can skip sending identical frames to the LEDs and sleep
+    /// for longer while the animations are static.
+    pub fn update<C: OutputColor>(&mut self, logical_strip: &mut LogicalStrip<C>) -> bool {
+        self.update_animations();
+        self.finish_frame(logical_strip)
+    }
+
+    /// Updates every animation by one frame without writing to a strip, for reading the frame
+    /// out with `iter_pixels()` instead.
+    pub fn update_animations(&mut self) {
+        self.fire_queued_triggers();
         for animation in self.animations.iter_mut() {
             animation.update();
+        }
+        let frame_ns = 1_000_000_000 / self.frame_rate.integer().max(1) as u64;
+        self.output_level.advance(frame_ns);
+    }
+
+    /// Updates the animations based on the actual time since the last call rather than the frame
+    /// rate, so animation speeds stay correct when the main loop runs at an uneven rate. Each
+    /// animation runs as many frames as fit in the elapsed time, and carries over the remainder.
+    /// Returns whether any LED on the strip changed, like `update()`.
+    pub fn update_with_elapsed<C: OutputColor>(
+        &mut self,
+        elapsed: Nanoseconds<u64>,
+        logical_strip: &mut LogicalStrip<C>,
+    ) -> bool {
+        self.update_animations_with_elapsed(elapsed);
+        self.finish_frame(logical_strip)
+    }
+
+    /// The same as `update_animations()`, but timed by the elapsed time like
+    /// `update_with_elapsed()`.
+    pub fn update_animations_with_elapsed(&mut self, elapsed: Nanoseconds<u64>) {
+        self.fire_queued_triggers();
+        for animation in self.animations.iter_mut() {
+            animation.update_with_elapsed(elapsed);
+        }
+        self.output_level.advance(elapsed.integer());
+    }
+
+    /// Returns the colors of the current frame for a strip of `led_count` LEDs, in physical
+    /// order, with the translation arrays and every output correction applied. The colors are
+    /// worked out as they are read, a few LEDs at a time, so drivers that take an iterator can
+    /// be written to without a LogicalStrip holding the whole frame. LEDs that no animation
+    /// covers are black.
+    ///
+    /// Dithering and post processing need the whole frame at once, so they are left out here.
+    pub fn iter_pixels(&self, led_count: usize) -> Pixels<'_, 'a, N_ANI> {
+        Pixels {
+            controller: self,
+            chunk: [RGB8::default(); PIXEL_CHUNK],
+            next: 0,
+            led_count,
+            power_scale: self.power_limiting_brightness(led_count),
+        }
+    }
+
+    /// Freezes an animation on its current frame, such as while a menu is open. The frozen frame
+    /// is still written to the strip by `update()`.
+    pub fn pause(&mut self, animation_index: usize) {
+        self.animations[animation_index].pause();
+    }
+
+    pub fn resume(&mut self, animation_index: usize) {
+        self.animations[animation_index].resume();
+    }
+
+    pub fn is_paused(&self, animation_index: usize) -> bool {
+        self.animations[animation_index].is_paused()
+    }
+
+    /// Advances an animation by one frame, even while it is paused. The new frame is written to
+    /// the strip by the next `update()`.
+    pub fn step_one_frame(&mut self, animation_index: usize) {
+        self.animations[animation_index].step_one_frame();
+    }
+
+    pub fn pause_all(&mut self) {
+        for animation in self.animations.iter_mut() {
+            animation.pause();
+        }
+    }
+
+    pub fn resume_all(&mut self) {
+        for animation in self.animations.iter_mut() {
+            animation.resume();
+        }
+    }
+
+    /// Writes the frame to the strip, recording the costs of the update when instrumentation is
+    /// enabled.
+    fn finish_frame<C: OutputColor>(&mut self, logical_strip: &mut LogicalStrip<C>) -> bool {
+        #[cfg(feature = "instrumentation")]
+        if let Some(counter) = self.cycle_counter {
+            let mut stopwatch = Stopwatch::start(counter);
+            let has_changed = self.write_to_strip(logical_strip);
+            let output = stopwatch.lap();
+            let mut layers = LayerCosts::default();
+            for animation in self.animations.iter_mut() {
+                layers.add(animation.take_layer_costs());
+            }
+            self.frame_stats.record(layers, output);
+            return has_changed;
+        }
+        self.write_to_strip(logical_strip)
+    }
+
+    /// Copies the colors of every animation onto the strip with the controller-wide corrections
+    /// and returns whether any of the strip's colors changed.
+    fn write_to_strip<C: OutputColor>(&mut self, logical_strip: &mut LogicalStrip<C>) -> bool {
+        let power_scale = self.power_limiting_brightness(logical_strip.color_buffer.len());
+        let mut post_frame = self.post_frame.take();
+        if let Some(frame) = &mut post_frame {
+            frame.fill(RGB8::default());
+        }
+        #[cfg(feature = "dithering")]
+        let mut ditherer = self.dither.take();
 
+        let mut has_changed = false;
+        let corrections = self.color_corrections.iter();
+        for (animation, &correction) in self.animations.iter().zip(corrections) {
             let segment = animation.segment();
             let translater = animation.translation_array();
-            let translated = translater.iter().zip(segment.iter());
+            let tiling = animation.tiling();
 
-            for (&index, &color) in translated {
-                logical_strip.set_color_at_index(index, color);
+            for (led, &color) in segment.iter().enumerate().take(translater.len()) {
+                // post processed frames are collected first and written to the strip afterwards:
+                if let Some(frame) = &mut post_frame {
+                    let color = self
+                        .output_color(color)
+                        .corrected_by(correction)
+                        .scaled_by(power_scale);
+                    for copy in 0..tiling.copies() {
+                        let index = tiling.physical_index(translater, led, copy);
+                        if let Some(frame_color) = frame.get_mut(index) {
+                            *frame_color = color;
+                        }
+                    }
+                    continue;
+                }
+
+                #[cfg(feature = "color16")]
+                let precise = match animation.segment16().get(led) {
+                    Some(&precise) => precise,
+                    None => output::expand(color),
+                };
+                #[cfg(not(feature = "color16"))]
+                let precise = output::expand(color);
+
+                // precise colors keep the extra bits themselves, so they aren't dithered:
+                if C::IS_PRECISE {
+                    let color = output::corrected_by16(self.output_color16(precise), correction);
+                    let color = C::from_rgb16(output::scaled_by16(color, power_scale))
+                        .with_channel_order(self.channel_order);
+                    for copy in 0..tiling.copies() {
+                        let index = tiling.physical_index(translater, led, copy);
+                        has_changed |= replace_color(logical_strip, index, color);
+                    }
+                    continue;
+                }
+
+                #[cfg(feature = "dithering")]
+                if let Some(ditherer) = &mut ditherer {
+                    let color = output::corrected_by16(self.output_color16(precise), correction);
+                    let color = output::scaled_by16(color, power_scale);
+                    for copy in 0..tiling.copies() {
+                        let index = tiling.physical_index(translater, led, copy);
+                        let color = C::from_rgb8(ditherer.dither(index, color))
+                            .with_channel_order(self.channel_order);
+                        has_changed |= replace_color(logical_strip, index, color);
+                    }
+                    continue;
+                }
+
+                // the correction balances the light output of the LEDs, so it goes after gamma:
+                let color = self
+                    .output_color(color)
+                    .corrected_by(correction)
+                    .scaled_by(power_scale);
+                let color = C::from_rgb8(color).with_channel_order(self.channel_order);
+                for copy in 0..tiling.copies() {
+                    let index = tiling.physical_index(translater, led, copy);
+                    has_changed |= replace_color(logical_strip, index, color);
+                }
+            }
+        }
+
+        if let Some(frame) = &mut post_frame {
+            for processor in self.post_processors.iter_mut() {
+                processor.process(frame);
+            }
+            // a frame longer than the strip only has its first LEDs written:
+            for (slot, &color) in logical_strip.color_buffer.iter_mut().zip(frame.iter()) {
+                let color = C::from_rgb8(color).with_channel_order(self.channel_order);
+                has_changed |= core::mem::replace(slot, color) != color;
             }
         }
+
+        self.post_frame = post_frame;
+        #[cfg(feature = "dithering")]
+        {
+            self.dither = ditherer;
+        }
+        has_changed
+    }
+
+    /// Renders `len` LEDs of the current frame into the start of `out`, starting from the LED at
+    /// `start` in physical order, with the same corrections as `iter_pixels()`. Very long strips
+    /// can be sent to the driver a piece at a time this way, with only a buffer for one piece.
+    /// Returns the number of LEDs rendered, which is less than `len` when `out` is too short.
+    ///
+    /// The strip is taken to end at the last LED covered by an animation when working out the
+    /// power budget, since the controller doesn't know its length.
+    pub fn render_range(&self, start: usize, len: usize, out: &mut [RGB8]) -> usize {
+        let length = len.min(out.len());
+        let power_scale = self.power_limiting_brightness(self.covered_led_count());
+        let channel_order = self.channel_order;
+        self.render_chunk(start, &mut out[..length], power_scale, channel_order);
+        length
+    }
+
+    /// Updates every animation and writes the new frame to a strip that is split across several
+    /// outputs, see `split::SplitStrip`. Returns whether any LED of any channel changed.
+    pub fn update_split<const N_CHANNELS: usize, C: OutputColor>(
+        &mut self,
+        split: &mut SplitStrip<N_CHANNELS, C>,
+    ) -> bool {
+        self.update_animations();
+        self.write_split(split)
+    }
+
+    /// Writes the current frame to the channels of a split strip, such as after
+    /// `update_animations_with_elapsed()`. Each channel gets the LEDs of its range with the
+    /// same corrections as `iter_pixels()`, in its own channel order. Precise output colors get
+    /// the 16 bit colors of the animations, as they do from `update()`. Like `iter_pixels()`,
+    /// the frame is worked out a few LEDs at a time, so it is neither dithered nor post
+    /// processed.
+    pub fn write_split<const N_CHANNELS: usize, C: OutputColor>(
+        &self,
+        split: &mut SplitStrip<N_CHANNELS, C>,
+    ) -> bool {
+        let power_scale = self.power_limiting_brightness(split.led_count());
+        let mut chunk = [RGB8::default(); PIXEL_CHUNK];
+        let mut chunk16 = [rgb::RGB16::default(); PIXEL_CHUNK];
+        let mut has_changed = false;
+        for channel in split.channels_mut() {
+            for chunk_start in (0..channel.len()).step_by(PIXEL_CHUNK) {
+                let length = (channel.len() - chunk_start).min(PIXEL_CHUNK);
+                let start = channel.start() + chunk_start;
+                if C::IS_PRECISE {
+                    let chunk = &mut chunk16[..length];
+                    self.render_chunk16(start, chunk, power_scale);
+                    for (led, &color) in chunk.iter().enumerate() {
+                        has_changed |= channel.write(chunk_start + led, C::from_rgb16(color));
+                    }
+                    continue;
+                }
+                let chunk = &mut chunk[..length];
+                // the channel moves its own channels, so the chunk is kept in RGB order:
+                self.render_chunk(start, chunk, power_scale, output::ChannelOrder::Rgb);
+                for (led, &color) in chunk.iter().enumerate() {
+                    has_changed |= channel.write(chunk_start + led, C::from_rgb8(color));
+                }
+            }
+        }
+        has_changed
+    }
+
+    /// Returns the number of LEDs up to and including the last one any animation draws on.
+    fn covered_led_count(&self) -> usize {
+        self.animations
+            .iter()
+            .filter_map(|animation| {
+                let translater = animation.translation_array();
+                let tiling = animation.tiling();
+                let last_copy = tiling.copies().checked_sub(1)?;
+                (0..translater.len())
+                    .map(|led| tiling.physical_index(translater, led, last_copy))
+                    .max()
+            })
+            .max()
+            .map_or(0, |last| last + 1)
+    }
+
+    /// Fills `chunk` with the corrected colors of the LEDs from `start` onwards, with their
+    /// channels in `channel_order`. Animations are drawn in order, so the later ones cover the
+    /// earlier ones like on a LogicalStrip.
+    fn render_chunk(
+        &self,
+        start: usize,
+        chunk: &mut [RGB8],
+        power_scale: u8,
+        channel_order: output::ChannelOrder,
+    ) {
+        chunk.fill(RGB8::default());
+        let corrections = self.color_corrections.iter();
+        for (animation, &correction) in self.animations.iter().zip(corrections) {
+            let translater = animation.translation_array();
+            let tiling = animation.tiling();
+            let segment = animation.segment();
+            for (led, &color) in segment.iter().enumerate().take(translater.len()) {
+                for copy in 0..tiling.copies() {
+                    let index = tiling.physical_index(translater, led, copy);
+                    let Some(pixel) = index.checked_sub(start).and_then(|i| chunk.get_mut(i))
+                    else {
+                        continue;
+                    };
+                    let color = self
+                        .output_color(color)
+                        .corrected_by(correction)
+                        .scaled_by(power_scale);
+                    *pixel = channel_order.apply(color);
+                }
+            }
+        }
+    }
+
+    /// The same as `render_chunk()`, but keeping 16 bits per channel for precise output colors,
+    /// with the channels left in RGB order.
+    fn render_chunk16(&self, start: usize, chunk: &mut [rgb::RGB16], power_scale: u8) {
+        chunk.fill(rgb::RGB16::default());
+        let corrections = self.color_corrections.iter();
+        for (animation, &correction) in self.animations.iter().zip(corrections) {
+            let translater = animation.translation_array();
+            let tiling = animation.tiling();
+            let segment = animation.segment();
+            for (led, &color) in segment.iter().enumerate().take(translater.len()) {
+                #[cfg(feature = "color16")]
+                let precise = match animation.segment16().get(led) {
+                    Some(&precise) => precise,
+                    None => output::expand(color),
+                };
+                #[cfg(not(feature = "color16"))]
+                let precise = output::expand(color);
+                for copy in 0..tiling.copies() {
+                    let index = tiling.physical_index(translater, led, copy);
+                    let Some(pixel) = index.checked_sub(start).and_then(|i| chunk.get_mut(i))
+                    else {
+                        continue;
+                    };
+                    let color = output::corrected_by16(self.output_color16(precise), correction);
+                    *pixel = output::scaled_by16(color, power_scale);
+                }
+            }
+        }
+    }
+
+    /// Applies the controller-wide corrections to a color from an animation
+    fn output_color(&self, color: RGB8) -> RGB8 {
+        let color = self
+            .tinted(color)
+            .scaled_by(self.brightness)
+            .scaled_by(self.output_level.level())
+            .corrected_by(self.temperature);
+        match &self.gamma {
+            Some(table) => table.correct(color),
+            None => color,
+        }
+    }
+
+    /// The same as `output_color()`, but keeping 16 bits per channel for dithering and precise
+    /// output colors.
+    fn output_color16(&self, color: rgb::RGB16) -> rgb::RGB16 {
+        // tints are worked out in 8 bits, so tinted colors lose the extra precision:
+        let color = match self.tint {
+            Some(_) => output::expand(self.tinted(output::reduce(color))),
+            None => color,
+        };
+        let color = output::scaled_by16(color, self.brightness);
+        let color = output::scaled_by16(color, self.output_level.level());
+        let color = output::corrected_by16(color, self.temperature);
+        match &self.gamma {
+            Some(table) => output::gamma_corrected16(color, table),
+            None => color,
+        }
+    }
+
+    fn tinted(&self, color: RGB8) -> RGB8 {
+        match &self.tint {
+            Some(tint) => tint.apply(color),
+            None => color,
+        }
+    }
+
+    /// Estimates the current draw of the next frame and returns how much it needs to be dimmed to
+    /// stay within the power budget. LEDs shared by more than one animation are counted for each.
+    fn power_limiting_brightness(&self, led_count: usize) -> u8 {
+        let budget = match &self.power_budget {
+            Some(budget) => budget,
+            None => return FULL_BRIGHTNESS,
+        };
+        let channel_milliamps = self
+            .animations
+            .iter()
+            .map(|animation| {
+                let colors = animation.segment().iter().map(|&c| self.output_color(c));
+                // every copy of a tiled animation draws the same current:
+                budget.estimate_channel_milliamps(colors) * animation.tiling().copies() as u32
+            })
+            .sum();
+        budget.limiting_brightness(channel_milliamps, led_count)
+    }
+
+    /// Sets the order the strip's LEDs expect their color channels in, which the colors written
+    /// to the strip, `iter_pixels()` and `render_range()` are all put into. Post processors still
+    /// see the frame in RGB order.
+    pub fn set_channel_order(&mut self, order: output::ChannelOrder) {
+        self.channel_order = order;
+    }
+
+    /// Dims the entire output of the controller. This is applied after the brightness of the
+    /// individual animations.
+    pub fn set_brightness(&mut self, brightness: u8) {
+        self.brightness = brightness;
+    }
+
+    /// Makes the LEDs of an animation fade out gradually instead of turning off straight away, so
+    /// that moving effects leave trails. `persistence` is how much of its brightness each LED
+    /// keeps every frame, and 0 turns it off.
+    pub fn set_persistence(&mut self, animation_index: usize, persistence: u8) {
+        self.animations[animation_index].set_persistence(persistence);
+    }
+
+    /// Sets the level shown by foreground modes that display an external value, such as
+    /// `foreground::Mode::LevelMeter`.
+    pub fn set_input_level(&mut self, animation_index: usize, level: u8) {
+        self.animations[animation_index].set_input_level(level);
+    }
+
+    /// Sets how full the bar of `foreground::Mode::ProgressBar` is, from 0 for empty to
+    /// `animations::MAX_OFFSET` for full. The bar moves smoothly to the new progress.
+    pub fn set_progress(&mut self, animation_index: usize, progress: u16) {
+        self.animations[animation_index].set_progress(progress);
+    }
+
+    /// Sets how much each LED of an animation is let through after all its layers are drawn, from
+    /// 0 to block the LED completely up to 255 to leave it untouched. This can black out broken
+    /// LEDs, or be changed every frame for reveal effects. LEDs past the end of `mask` are left as
+    /// they are.
+    pub fn set_mask(&mut self, animation_index: usize, mask: &[u8]) {
+        self.animations[animation_index].set_mask(mask);
+    }
+
+    /// Sets the mask level of a single LED of an animation. See `set_mask()`.
+    pub fn set_mask_level(&mut self, animation_index: usize, led_index: usize, level: u8) {
+        self.animations[animation_index].set_mask_level(led_index, level);
+    }
+
+    /// Removes the mask of an animation, letting every LED through at full brightness.
+    pub fn clear_mask(&mut self, animation_index: usize) {
+        self.animations[animation_index].clear_mask();
+    }
+
+    /// Gives a key of an animation its own fixed color, such as to highlight the keys of a
+    /// keyboard layer. The key is looked up in the animation's keymap, see
+    /// `Animation::set_keymap()`. Keys with their own color cover the background and foreground,
+    /// but triggers are still drawn over them. If MAX_KEY_COLORS keys already have their own
+    /// color, the color is dropped.
+    pub fn set_key_color(&mut self, animation_index: usize, key: usize, color: RGB8) {
+        self.animations[animation_index].set_key_color(key, color);
+    }
+
+    /// Sets the colors of many keys of an animation at once. See `set_key_color()`.
+    pub fn set_key_colors(&mut self, animation_index: usize, keys: &[(usize, RGB8)]) {
+        self.animations[animation_index].set_key_colors(keys);
+    }
+
+    /// Lets a key of an animation show the animation's layers again.
+    pub fn clear_key_color(&mut self, animation_index: usize, key: usize) {
+        self.animations[animation_index].clear_key_color(key);
+    }
+
+    /// Lets every key of an animation show the animation's layers again.
+    pub fn clear_key_colors(&mut self, animation_index: usize) {
+        self.animations[animation_index].clear_key_colors();
+    }
+
+    pub fn set_animation_brightness(&mut self, animation_index: usize, brightness: u8) {
+        self.animations[animation_index].set_brightness(brightness);
+    }
+
+    pub fn set_layer_brightness(
+        &mut self,
+        animation_index: usize,
+        a_type: AnimationType,
+        brightness: u8,
+    ) {
+        self.animations[animation_index].set_layer_brightness(a_type, brightness);
+    }
+
+    /// Runs a layer of an animation at `numerator / denominator` times its normal speed.
+    pub fn set_speed(
+        &mut self,
+        animation_index: usize,
+        a_type: AnimationType,
+        numerator: u32,
+        denominator: u32,
+    ) {
+        self.animations[animation_index].set_speed(a_type, numerator, denominator);
+    }
+
+    /// Starts the current cycle of a layer of an animation over from its first frame.
+    pub fn restart_cycle(&mut self, animation_index: usize, a_type: AnimationType) {
+        self.animations[animation_index].restart_cycle(a_type);
+    }
+
+    /// Starts a layer of an animation over from the first cycle of its run.
+    pub fn restart_run(&mut self, animation_index: usize, a_type: AnimationType) {
+        self.animations[animation_index].restart_run(a_type);
+    }
+
+    /// Returns whether a layer of an animation has finished the cycles its run mode asks for.
+    pub fn is_run_complete(&self, animation_index: usize, a_type: AnimationType) -> bool {
+        self.animations[animation_index].is_run_complete(a_type)
+    }
+
+    /// Limits the output of the controller to stay within the current budget of the power supply.
+    pub fn set_power_budget(&mut self, budget: PowerBudget) {
+        self.power_budget = Some(budget);
+    }
+
+    pub fn disable_power_budget(&mut self) {
+        self.power_budget = None;
+    }
+
+    /// Gamma corrects all colors as they are written to the LogicalStrip.
+    pub fn enable_gamma(&mut self, gamma: f32) {
+        self.gamma = Some(GammaTable::new(gamma));
+    }
+
+    /// Like `enable_gamma()`, but uses a table that was already built, such as a const table.
+    pub fn set_gamma_table(&mut self, table: GammaTable) {
+        self.gamma = Some(table);
+    }
+
+    pub fn disable_gamma(&mut self) {
+        self.gamma = None;
+    }
+
+    /// Dithers the output so that dim colors and slow fades look smooth, see the `dither` module.
+    /// There should be one accumulator for each LED on the LogicalStrip.
+    #[cfg(feature = "dithering")]
+    pub fn enable_dithering(&mut self, accumulators: &'a mut [rgb::RGB16]) {
+        self.dither = Some(dither::Dither::new(accumulators));
+    }
+
+    #[cfg(feature = "dithering")]
+    pub fn disable_dithering(&mut self) {
+        self.dither = None;
+    }
+
+    /// Times each layer of every update with the counter, such as a cycle counter or a
+    /// microsecond timer, so that the costs can be read with `frame_stats()`.
+    #[cfg(feature = "instrumentation")]
+    pub fn enable_instrumentation(&mut self, counter: &'a dyn CycleCounter) {
+        self.cycle_counter = Some(counter);
+        for animation in self.animations.iter_mut() {
+            animation.set_cycle_counter(Some(counter));
+        }
+        self.frame_stats.clear();
+    }
+
+    #[cfg(feature = "instrumentation")]
+    pub fn disable_instrumentation(&mut self) {
+        self.cycle_counter = None;
+        for animation in self.animations.iter_mut() {
+            animation.set_cycle_counter(None);
+        }
+    }
+
+    /// Returns the rolling costs of the last few updates, split by layer. The background,
+    /// foreground and trigger costs add up every animation.
+    #[cfg(feature = "instrumentation")]
+    pub fn frame_stats(&self) -> &FrameStats {
+        &self.frame_stats
+    }
+
+    /// Hands each layer of an animation's frames to the observer before they are composited, see
+    /// `animations::LayerObserver`. Pass None to stop.
+    pub fn set_layer_observer(
+        &mut self,
+        animation_index: usize,
+        observer: Option<&'a mut dyn LayerObserver>,
+    ) {
+        self.animations[animation_index].set_layer_observer(observer);
+    }
+
+    /// Runs the post processors on every frame, using `frame` to collect the frame before it is
+    /// written to the strip. There should be one color in `frame` for each LED on the
+    /// LogicalStrip; any extra colors are not written, and LEDs that no animation covers are
+    /// black. Post processed frames are
+    /// calculated with 8 bits per channel, so they aren't dithered, and precise output colors are
+    /// made from the 8 bit colors.
+    pub fn enable_post_processing(&mut self, frame: &'a mut [RGB8]) {
+        self.post_frame = Some(frame);
+    }
+
+    pub fn disable_post_processing(&mut self) {
+        self.post_frame = None;
+    }
+
+    /// Adds a stage to the end of the post processing, see `output::PostProcessor`. The
+    /// processors only run while post processing is enabled. Returns false and drops the
+    /// processor if MAX_POST_PROCESSORS have already been added.
+    pub fn add_post_processor(&mut self, processor: &'a mut dyn PostProcessor) -> bool {
+        self.post_processors.try_push(processor).is_ok()
+    }
+
+    pub fn clear_post_processors(&mut self) {
+        self.post_processors.clear();
+    }
+
+    /// Balances the red, green and blue channels of an animation's LEDs as they are written to
+    /// the strip, for strips with a color tint. See `colors::TYPICAL_LED_STRIP` and the other
+    /// corrections next to it for some common values.
+    pub fn set_color_correction(&mut self, animation_index: usize, correction: RGB8) {
+        self.color_corrections[animation_index] = correction;
+    }
+
+    pub fn clear_color_correction(&mut self, animation_index: usize) {
+        self.color_corrections[animation_index] = colors::UNCORRECTED;
+    }
+
+    /// Tints the output of every animation toward the white of a color temperature in kelvin,
+    /// such as 2700 for a warm look or 8000 for a cool one. The animations and their rainbows are
+    /// unchanged, so the tint can be changed or cleared at any time.
+    pub fn set_temperature(&mut self, kelvin: u16) {
+        self.temperature = colors::temperature_tint(kelvin);
+    }
+
+    pub fn clear_temperature(&mut self) {
+        self.temperature = colors::UNCORRECTED;
+    }
+
+    /// Lays a color over the output of every animation, such as blending everything toward red
+    /// to show an error. The tint is applied before the brightness and other corrections, and the
+    /// animations keep running beneath it until it is cleared. Change the amount of a blend every
+    /// frame to fade the tint in or out.
+    pub fn set_tint(&mut self, tint: colors::Tint) {
+        self.tint = Some(tint);
+    }
+
+    pub fn clear_tint(&mut self) {
+        self.tint = None;
+    }
+
+    /// Fades the whole output down to off over `duration_ms`, such as to save power or for a
+    /// dramatic cue. The animations keep running while the output is dark, and `restore()` fades
+    /// them back in from wherever they have got to.
+    pub fn blackout(&mut self, duration_ms: u64) {
+        self.output_level.fade_to(0, duration_ms * 1_000_000);
+    }
+
+    /// Fades the output back up to full over `duration_ms` after a `blackout()`.
+    pub fn restore(&mut self, duration_ms: u64) {
+        self.output_level
+            .fade_to(FULL_BRIGHTNESS, duration_ms * 1_000_000);
+    }
+
+    /// Returns whether a blackout has finished fading the output all the way to off.
+    pub fn is_blacked_out(&self) -> bool {
+        self.output_level.level() == 0
+    }
+
+    pub fn frame_rate(&self) -> Hertz {
+        self.frame_rate
+    }
+
+    /// Changes the rate that `update()` is called at. Every animation is rescaled so that anything
+    /// in progress keeps running at the same speed.
+    pub fn set_frame_rate(&mut self, frame_rate: impl Into<Hertz>) {
+        self.frame_rate = frame_rate.into();
+        for animation in self.animations.iter_mut() {
+            animation.set_frame_rate(self.frame_rate);
+        }
     }
 
     pub fn trigger(&mut self, animation_index: usize, params: &animations::trigger::Parameters) {
         self.animations[animation_index].trigger(params, self.frame_rate);
     }
 
+    /// Fires a trigger like `trigger()`, but returns an error if it was dropped because the
+    /// animation had no room for another running trigger.
+    pub fn try_trigger(
+        &mut self,
+        animation_index: usize,
+        params: &animations::trigger::Parameters,
+    ) -> Result<(), animations::trigger::TriggerError> {
+        self.animations[animation_index].try_trigger(params, self.frame_rate)
+    }
+
+    /// Returns the oldest event of an animation that hasn't been polled yet, such as a trigger
+    /// finishing. Poll each animation after every update so its queue doesn't fill up.
+    pub fn poll_event(&mut self, animation_index: usize) -> Option<animations::Event> {
+        self.animations[animation_index].poll_event()
+    }
+
+    /// Fires a sequence of triggers one after another, each after the delay set in its step.
+    pub fn trigger_sequence(
+        &mut self,
+        animation_index: usize,
+        steps: &[animations::trigger::SequenceStep],
+    ) {
+        self.animations[animation_index].trigger_sequence(steps, self.frame_rate);
+    }
+
+    /// Stops every running trigger of an animation, such as when the state they were showing has
+    /// changed, along with any sequence steps that haven't fired yet.
+    pub fn clear_triggers(&mut self, animation_index: usize) {
+        self.animations[animation_index].clear_triggers();
+    }
+
+    /// Stops the running triggers of an animation that have the given mode.
+    pub fn cancel_triggers_of_mode(
+        &mut self,
+        animation_index: usize,
+        mode: animations::trigger::Mode,
+    ) {
+        self.animations[animation_index].cancel_triggers_of_mode(mode);
+    }
+
+    /// Returns the number of triggers running on an animation.
+    pub fn active_trigger_count(&self, animation_index: usize) -> usize {
+        self.animations[animation_index].active_trigger_count()
+    }
+
     pub fn set_offset(&mut self, animation_index: usize, a_type: AnimationType, offset: u16) {
         self.animations[animation_index].set_offset(a_type, offset);
     }
 
+    /// Changes the direction of a layer of an animation while it runs.
+    pub fn set_direction(
+        &mut self,
+        animation_index: usize,
+        a_type: AnimationType,
+        direction: Direction,
+    ) {
+        self.animations[animation_index].set_direction(a_type, direction);
+    }
+
+    pub fn set_parameters(
+        &mut self,
+        animation_index: usize,
+        parameters: &animations::AnimationParameters<'a>,
+    ) {
+        self.animations[animation_index].set_parameters(parameters);
+        self.preset_ids[animation_index] = None;
+    }
+
+    /// Applies the currently selected preset of a bank to an animation.
+    pub fn apply_preset<const N: usize>(
+        &mut self,
+        animation_index: usize,
+        bank: &PresetBank<'a, N>,
+    ) {
+        self.set_parameters(animation_index, &bank.current().parameters);
+        self.preset_ids[animation_index] = Some(bank.current().id);
+    }
+
+    /// Returns the ID of the preset last applied to an animation, or None if its parameters have
+    /// been set some other way since.
+    pub fn preset_id(&self, animation_index: usize) -> Option<u8> {
+        self.preset_ids[animation_index]
+    }
+
+    /// Selects the next preset of a bank and applies it to an animation.
+    pub fn next_preset<const N: usize>(
+        &mut self,
+        animation_index: usize,
+        bank: &mut PresetBank<'a, N>,
+    ) {
+        bank.select_next();
+        self.apply_preset(animation_index, bank);
+    }
+
+    /// Selects the previous preset of a bank and applies it to an animation.
+    pub fn previous_preset<const N: usize>(
+        &mut self,
+        animation_index: usize,
+        bank: &mut PresetBank<'a, N>,
+    ) {
+        bank.select_previous();
+        self.apply_preset(animation_index, bank);
+    }
+
+    /// Selects the preset with a matching ID and applies it to an animation. Does nothing if the
+    /// bank has no preset with that ID.
+    pub fn select_preset<const N: usize>(
+        &mut self,
+        animation_index: usize,
+        bank: &mut PresetBank<'a, N>,
+        id: u8,
+    ) {
+        if bank.select_id(id).is_some() {
+            self.apply_preset(animation_index, bank);
+        }
+    }
+
+    /// Writes the brightness, the applied presets, and the offsets and rainbow positions of every
+    /// animation to a buffer of at least `state::state_len(N_ANI)` bytes, see the `state` module
+    /// for the layout. Returns the number of bytes written.
+    pub fn save_state(&self, out: &mut [u8]) -> Result<usize, StateError> {
+        let state = ControllerState {
+            brightness: self.brightness,
+            preset_ids: self.preset_ids,
+            animations: core::array::from_fn(|index| self.animations[index].state()),
+        };
+        state.write(out)
+    }
+
+    /// Restores a state written by `save_state()`. The presets are only restored by their IDs,
+    /// since the controller doesn't own the banks, so the parameters of each animation are left
+    /// as they are: use `restore_state_with_presets()` to apply them as well. Nothing is changed
+    /// if the state can't be read.
+    pub fn restore_state(&mut self, data: &[u8]) -> Result<(), StateError> {
+        let state = ControllerState::<N_ANI>::read(data)?;
+        self.apply_state(&state);
+        Ok(())
+    }
+
+    /// Restores a state written by `save_state()`, first selecting and applying the saved preset
+    /// of each animation from a bank. Saved IDs that the bank doesn't have are skipped.
+    pub fn restore_state_with_presets<const N: usize>(
+        &mut self,
+        data: &[u8],
+        bank: &mut PresetBank<'a, N>,
+    ) -> Result<(), StateError> {
+        let state = ControllerState::<N_ANI>::read(data)?;
+        for (animation_index, preset_id) in state.preset_ids.iter().enumerate() {
+            if let Some(id) = *preset_id {
+                self.select_preset(animation_index, bank, id);
+            }
+        }
+        self.apply_state(&state);
+        Ok(())
+    }
+
+    fn apply_state(&mut self, state: &ControllerState<N_ANI>) {
+        self.brightness = state.brightness;
+        self.preset_ids = state.preset_ids;
+        for (animation, animation_state) in self.animations.iter_mut().zip(state.animations.iter())
+        {
+            animation.restore_state(animation_state);
+        }
+    }
+
+    pub fn update_bg_parameters(
+        &mut self,
+        animation_index: usize,
+        parameters: &animations::background::Parameters<'a>,
+    ) {
+        self.animations[animation_index].update_bg_parameters(parameters);
+    }
+
+    pub fn update_fg_parameters(
+        &mut self,
+        animation_index: usize,
+        parameters: &animations::foreground::Parameters<'a>,
+    ) {
+        self.animations[animation_index].update_fg_parameters(parameters);
+    }
+
+    pub fn update_trigger_parameters(
+        &mut self,
+        animation_index: usize,
+        parameters: &animations::trigger::GlobalParameters<'a>,
+    ) {
+        self.animations[animation_index].update_trigger_parameters(parameters);
+    }
+
+    pub fn transition_to(
+        &mut self,
+        animation_index: usize,
+        parameters: &animations::AnimationParameters<'a>,
+        duration_ms: u64,
+    ) {
+        self.animations[animation_index].transition_to(parameters, duration_ms);
+        self.preset_ids[animation_index] = None;
+    }
+
+    pub fn set_transition_style(
+        &mut self,
+        animation_index: usize,
+        style: animations::transition::Style,
+    ) {
+        self.animations[animation_index].set_transition_style(style);
+    }
+
+    /// Sets where the controller takes triggers from during every update, such as the consumer of
+    /// a `commands::TriggerQueue` that is filled by interrupt handlers. Triggers for animation
+    /// indexes the controller doesn't have are dropped.
+    pub fn set_trigger_source(&mut self, source: &'a mut dyn TriggerSource) {
+        self.trigger_source = Some(source);
+    }
+
+    pub fn clear_trigger_source(&mut self) {
+        self.trigger_source = None;
+    }
+
+    fn fire_queued_triggers(&mut self) {
+        let Some(source) = &mut self.trigger_source else {
+            return;
+        };
+        while let Some(queued) = source.next_trigger() {
+            if let Some(animation) = self.animations.get_mut(queued.animation_index) {
+                animation.trigger(&queued.parameters, self.frame_rate);
+            }
+        }
+    }
+
+    /// Applies a command that was passed in through a CommandQueue.
+    pub fn apply_command(&mut self, command: Command<'a>) {
+        match command {
+            Command::Trigger {
+                animation_index,
+                parameters,
+            } => self.trigger(animation_index, &parameters),
+            Command::SetOffset {
+                animation_index,
+                animation_type,
+                offset,
+            } => self.set_offset(animation_index, animation_type, offset),
+            Command::SetInputLevel {
+                animation_index,
+                level,
+            } => self.set_input_level(animation_index, level),
+            Command::SetBrightness(brightness) => self.set_brightness(brightness),
+            Command::SetAnimationBrightness {
+                animation_index,
+                brightness,
+            } => self.set_animation_brightness(animation_index, brightness),
+            Command::SetMaskLevel {
+                animation_index,
+                led_index,
+                level,
+            } => self.set_mask_level(animation_index, led_index, level),
+            Command::SetParameters {
+                animation_index,
+                parameters,
+            } => self.set_parameters(animation_index, parameters),
+        }
+    }
+
+    /// Applies every command waiting in a CommandQueue, in the order they were pushed. Call this
+    /// before `update()` so the commands take effect in the next frame.
+    pub fn apply_commands<const N: usize>(&mut self, consumer: &mut Consumer<'_, 'a, N>) {
+        while let Some(command) = consumer.pop() {
+            self.apply_command(command);
+        }
+    }
+
     pub fn replace_animation(&mut self, index: usize, new_anim: &'a mut dyn Animatable<'a>) {
         self.animations[index] = new_anim;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::animations::{background, Animation, AnimationParameters, Tiling};
+    use crate::default_animations::{ANI_TEST, BG_TEST};
+    use crate::state::state_len;
+    use embedded_time::rate::Extensions;
+
+    /// A still rainbow, so that every LED of the animation has a different color.
+    const ANI_STILL_RAINBOW: AnimationParameters = AnimationParameters {
+        bg: background::Parameters {
+            mode: background::Mode::FillRainbow,
+            ..BG_TEST
+        },
+        ..ANI_TEST
+    };
+
+    fn draw_tiled(tiling: Tiling, strip: &mut [RGB8]) {
+        let animation = &mut Animation::<4>::new(ANI_STILL_RAINBOW, 60.Hz()).set_tiling(tiling);
+        let animations: [&mut dyn Animatable; 1] = [animation];
+        let mut controller = LightingController::new(animations, 60.Hz());
+        controller.update(&mut LogicalStrip::new(strip));
+    }
+
+    #[test]
+    fn repeated_copies_stop_at_the_end_of_the_strip() {
+        let strip = &mut [RGB8::default(); 10];
+        draw_tiled(Tiling::Repeat(3), strip);
+        assert_eq!(strip[..4], strip[4..8]);
+        assert_eq!(strip[..2], strip[8..]);
+        assert_ne!(strip[0], strip[1]);
+    }
+
+    #[test]
+    fn mirrored_copies_stop_at_the_end_of_the_strip() {
+        let strip = &mut [RGB8::default(); 10];
+        draw_tiled(Tiling::Mirror(3), strip);
+        for led in 0..4 {
+            assert_eq!(strip[led], strip[7 - led]);
+        }
+        assert_eq!(strip[..2], strip[8..]);
+        assert_ne!(strip[0], strip[1]);
+    }
+
+    #[test]
+    fn copies_past_a_short_strip_are_left_out() {
+        let strip = &mut [RGB8::default(); 3];
+        draw_tiled(Tiling::Repeat(2), strip);
+        let full_strip = &mut [RGB8::default(); 8];
+        draw_tiled(Tiling::Repeat(2), full_strip);
+        assert_eq!(strip[..], full_strip[..3]);
+    }
+
+    #[test]
+    fn post_processing_frame_longer_than_the_strip() {
+        let frame = &mut [RGB8::default(); 8];
+        let animation = &mut Animation::<4>::new(ANI_STILL_RAINBOW, 60.Hz());
+        let animations: [&mut dyn Animatable; 1] = [animation];
+        let mut controller = LightingController::new(animations, 60.Hz());
+        controller.enable_post_processing(frame);
+        let strip = &mut [RGB8::default(); 4];
+        assert!(controller.update(&mut LogicalStrip::new(strip)));
+        assert_ne!(strip[0], RGB8::default());
+    }
+
+    #[test]
+    fn restores_a_saved_state() {
+        let first = &mut Animation::<4>::new(ANI_TEST, 60.Hz());
+        let second = &mut Animation::<4>::new(ANI_TEST, 60.Hz());
+        let animations: [&mut dyn Animatable; 2] = [first, second];
+        let mut controller = LightingController::new(animations, 60.Hz());
+        controller.set_brightness(77);
+        controller.set_animation_brightness(1, 88);
+        controller.set_layer_brightness(0, AnimationType::Foreground, 99);
+        controller.set_offset(1, AnimationType::Background, 12345);
+        let mut saved = [0; state_len(2)];
+        assert_eq!(controller.save_state(&mut saved), Ok(saved.len()));
+
+        let first = &mut Animation::<4>::new(ANI_TEST, 60.Hz());
+        let second = &mut Animation::<4>::new(ANI_TEST, 60.Hz());
+        let animations: [&mut dyn Animatable; 2] = [first, second];
+        let mut restored = LightingController::new(animations, 60.Hz());
+        assert_eq!(restored.restore_state(&saved), Ok(()));
+        let mut resaved = [0; state_len(2)];
+        restored.save_state(&mut resaved).unwrap();
+        assert_eq!(saved, resaved);
+
+        let state = ControllerState::<2>::read(&resaved).unwrap();
+        assert_eq!(state.brightness, 77);
+        assert_eq!(state.animations[1].brightness, 88);
+        assert_eq!(state.animations[0].fg_brightness, 99);
+        assert_eq!(state.animations[1].bg_offset, 12345);
+    }
+
+    #[test]
+    fn restoring_a_damaged_state_changes_nothing() {
+        let animation = &mut Animation::<4>::new(ANI_TEST, 60.Hz());
+        let animations: [&mut dyn Animatable; 1] = [animation];
+        let mut controller = LightingController::new(animations, 60.Hz());
+        controller.set_brightness(77);
+        let mut saved = [0; state_len(1)];
+        controller.save_state(&mut saved).unwrap();
+        saved[2] = 10;
+        controller.set_brightness(55);
+        assert_eq!(
+            controller.restore_state(&saved),
+            Err(StateError::BadChecksum)
+        );
+        controller.save_state(&mut saved).unwrap();
+        assert_eq!(saved[2], 55);
+    }
+}
@@ -0,0 +1,174 @@
+//! Utilities for mapping animations onto the physical wiring of the LEDs. A TranslationBuilder
+//! describes strips with reversed runs, interleaved strips and dead pixels.
+//!
+//! Animations on a matrix treat their segment as a grid of `width * height` LEDs in row-major
+//! order, so LED `x + y * width` of the segment is always at position (x, y). The physical wiring
+//! of the matrix is then handled by the translation array generated from the matrix's wiring.
+
+/// Describes how the LEDs of a matrix are physically chained together.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Wiring {
+    /// Every row is wired in the same direction, starting at x = 0.
+    Progressive,
+
+    /// Every other row is wired in the opposite direction, snaking back and forth across the
+    /// matrix. This is the common wiring for flexible WS2812 panels.
+    Serpentine,
+}
+
+/// The geometry and wiring of an LED matrix.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Matrix {
+    pub width: usize,
+    pub height: usize,
+    pub wiring: Wiring,
+}
+
+impl Matrix {
+    pub const fn new(width: usize, height: usize, wiring: Wiring) -> Self {
+        Matrix {
+            width,
+            height,
+            wiring,
+        }
+    }
+
+    pub fn led_count(&self) -> usize {
+        self.width * self.height
+    }
+
+    /// Returns the (x, y) position of an LED index in the animation's segment.
+    pub fn coordinates(&self, index: usize) -> (usize, usize) {
+        (index % self.width, index / self.width)
+    }
+
+    /// Returns the index in the animation's segment of the LED at position (x, y).
+    pub fn index(&self, x: usize, y: usize) -> usize {
+        y * self.width + x
+    }
+
+    /// Returns the physical index on the strip of the LED at position (x, y), not including any
+    /// starting offset.
+    pub fn physical_index(&self, x: usize, y: usize) -> usize {
+        match self.wiring {
+            Wiring::Serpentine if y % 2 == 1 => y * self.width + (self.width - 1 - x),
+            _ => y * self.width + x,
+        }
+    }
+
+    /// Returns a translation array for an animation covering the matrix, where the first LED of
+    /// the matrix is at index `start_at` of the LogicalStrip. SIZE should be `width * height`.
+    pub fn translation_array<const SIZE: usize>(&self, start_at: usize) -> [usize; SIZE] {
+        let mut result: [usize; SIZE] = [0; SIZE];
+        for (index, value) in result.iter_mut().enumerate() {
+            let (x, y) = self.coordinates(index);
+            *value = start_at + self.physical_index(x, y);
+        }
+        result
+    }
+}
+
+/// Builds a translation array from a description of how the LEDs are wired, instead of writing out
+/// every index by hand. The builder keeps track of the next physical LED on the strip, and each
+/// call maps the next LEDs of the animation onto the LEDs that follow it. For example, a strip
+/// wired forwards for 10 LEDs, with a dead LED, and then back along the other side for 10 LEDs is
+/// `TranslationBuilder::new(0).run(10).skip(1).reversed_run(10).build()`.
+///
+/// Any LEDs of the animation left over once the description ends continue on from the next
+/// physical LED, and anything past the end of the array is ignored.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct TranslationBuilder<const SIZE: usize> {
+    translation_array: [usize; SIZE],
+    len: usize,
+    next_index: usize,
+}
+
+impl<const SIZE: usize> TranslationBuilder<SIZE> {
+    /// Starts a builder with the first LED of the animation at index `start_at` of the
+    /// LogicalStrip.
+    pub const fn new(start_at: usize) -> Self {
+        TranslationBuilder {
+            translation_array: [0; SIZE],
+            len: 0,
+            next_index: start_at,
+        }
+    }
+
+    /// Maps the next `length` LEDs in order.
+    pub fn run(mut self, length: usize) -> Self {
+        for offset in 0..length {
+            self.push(self.next_index + offset);
+        }
+        self.next_index += length;
+        self
+    }
+
+    /// Maps the next `length` LEDs in reverse, for runs of LEDs wired in the opposite direction.
+    pub fn reversed_run(mut self, length: usize) -> Self {
+        for offset in (0..length).rev() {
+            self.push(self.next_index + offset);
+        }
+        self.next_index += length;
+        self
+    }
+
+    /// Skips over the next `count` physical LEDs, so dead or hidden pixels are left dark.
+    pub fn skip(mut self, count: usize) -> Self {
+        self.next_index += count;
+        self
+    }
+
+    /// Moves to index `index` of the LogicalStrip, for runs that aren't wired one after another.
+    pub fn jump_to(mut self, index: usize) -> Self {
+        self.next_index = index;
+        self
+    }
+
+    /// Maps the next `strip_count * length` LEDs of the animation across `strip_count` strips of
+    /// `length` LEDs that are wired one after another, but laid side by side. LEDs of the
+    /// animation alternate between the strips, so an animation drawn across them runs along
+    /// all of them at once.
+    pub fn interleaved(mut self, strip_count: usize, length: usize) -> Self {
+        for position in 0..length {
+            for strip in 0..strip_count {
+                self.push(self.next_index + strip * length + position);
+            }
+        }
+        self.next_index += strip_count * length;
+        self
+    }
+
+    /// Maps the next LEDs of the animation onto the given indices of the LogicalStrip. The next
+    /// physical LED is then the one after the last index.
+    pub fn indices(mut self, indices: &[usize]) -> Self {
+        for &index in indices {
+            self.push(index);
+        }
+        if let Some(&last) = indices.last() {
+            self.next_index = last + 1;
+        }
+        self
+    }
+
+    /// Returns the finished translation array.
+    pub fn build(mut self) -> [usize; SIZE] {
+        while self.len < SIZE {
+            self.push(self.next_index);
+            self.next_index += 1;
+        }
+        self.translation_array
+    }
+
+    fn push(&mut self, index: usize) {
+        if let Some(value) = self.translation_array.get_mut(self.len) {
+            *value = index;
+            self.len += 1;
+        }
+    }
+}
+
+impl<const SIZE: usize> Default for TranslationBuilder<SIZE> {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
@@ -0,0 +1,352 @@
+//! MIDI input, so triggers can be fired from a keyboard or sequencer. A Parser turns raw MIDI
+//! bytes, such as those from a UART at 31250 baud or a USB MIDI class driver, into Messages. A
+//! Mapper then turns note-on messages into triggers, with the note choosing where the trigger is
+//! drawn and the velocity choosing how fast it fades, and control changes into brightness, input
+//! level and offset Commands.
+//!
+//! Triggers run for their full fade once fired, so note-off messages are parsed but not mapped.
+
+use crate::animations::{trigger, AnimationType, MAX_OFFSET};
+use crate::commands::Command;
+
+/// The highest value of a MIDI data byte, used for notes, velocities and controller values.
+pub const MAX_DATA_VALUE: u8 = 127;
+
+const NOTE_OFF: u8 = 0x80;
+const NOTE_ON: u8 = 0x90;
+const CONTROL_CHANGE: u8 = 0xb0;
+const PROGRAM_CHANGE: u8 = 0xc0;
+const CHANNEL_PRESSURE: u8 = 0xd0;
+const SYSTEM_MESSAGE: u8 = 0xf0;
+const REAL_TIME_MESSAGE: u8 = 0xf8;
+
+/// The channel messages used by the Mapper. Channels are numbered from 0 to 15.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Message {
+    NoteOn {
+        channel: u8,
+        note: u8,
+        velocity: u8,
+    },
+    NoteOff {
+        channel: u8,
+        note: u8,
+        velocity: u8,
+    },
+    ControlChange {
+        channel: u8,
+        controller: u8,
+        value: u8,
+    },
+}
+
+/// Assembles Messages from a stream of MIDI bytes, including running status, where the status
+/// byte is left out of messages that repeat the previous one. Real-time bytes such as the clock
+/// can be interleaved anywhere and are skipped, as are system exclusive messages.
+#[derive(Default)]
+pub struct Parser {
+    status: Option<u8>,
+    first_data: Option<u8>,
+}
+
+impl Parser {
+    pub const fn new() -> Self {
+        Parser {
+            status: None,
+            first_data: None,
+        }
+    }
+
+    /// Adds the next byte of the stream, returning a Message if it completed one.
+    pub fn push(&mut self, byte: u8) -> Option<Message> {
+        if byte >= REAL_TIME_MESSAGE {
+            return None;
+        }
+        if byte >= SYSTEM_MESSAGE {
+            // system messages cancel running status:
+            self.status = None;
+            self.first_data = None;
+            return None;
+        }
+        if byte & 0x80 != 0 {
+            self.status = Some(byte);
+            self.first_data = None;
+            return None;
+        }
+
+        let status = self.status?;
+        let message_type = status & 0xf0;
+        let channel = status & 0x0f;
+        if let PROGRAM_CHANGE | CHANNEL_PRESSURE = message_type {
+            // these have a single data byte and aren't used:
+            return None;
+        }
+        let first_data = match self.first_data.take() {
+            Some(first_data) => first_data,
+            None => {
+                self.first_data = Some(byte);
+                return None;
+            }
+        };
+
+        match message_type {
+            NOTE_ON if byte == 0 => Some(Message::NoteOff {
+                channel,
+                note: first_data,
+                velocity: 0,
+            }),
+            NOTE_ON => Some(Message::NoteOn {
+                channel,
+                note: first_data,
+                velocity: byte,
+            }),
+            NOTE_OFF => Some(Message::NoteOff {
+                channel,
+                note: first_data,
+                velocity: byte,
+            }),
+            CONTROL_CHANGE => Some(Message::ControlChange {
+                channel,
+                controller: first_data,
+                value: byte,
+            }),
+            // polyphonic pressure and pitch bend aren't used:
+            _ => None,
+        }
+    }
+}
+
+/// How the notes of a NoteMap choose where their trigger is drawn.
+#[derive(Copy, Clone)]
+pub enum NoteLayout {
+    /// The notes are spread across the full range of starting offsets, for modes that move or
+    /// place the trigger by its offset, such as ColorShot and ColorPulse.
+    Offset,
+
+    /// Each note is limited to its own range of LEDs, like keys along a keyboard, for segments of
+    /// the given length.
+    Target { segment_length: usize },
+}
+
+/// Fires a trigger for every note-on message in a range of notes.
+#[derive(Copy, Clone)]
+pub struct NoteMap<'a> {
+    /// The MIDI channel to listen to, or None to listen to every channel.
+    pub channel: Option<u8>,
+    pub animation_index: usize,
+    /// The trigger to fire. Its starting offset or target is set by the note, and its fade times
+    /// are stretched by up to double for the softest notes.
+    pub parameters: &'a trigger::Parameters,
+    pub lowest_note: u8,
+    pub highest_note: u8,
+    pub layout: NoteLayout,
+}
+
+impl<'a> NoteMap<'a> {
+    /// Returns the trigger parameters for a note, or None if the note is outside the map.
+    pub fn parameters_for(&self, note: u8, velocity: u8) -> Option<trigger::Parameters> {
+        if note < self.lowest_note || note > self.highest_note {
+            return None;
+        }
+        let note_index = (note - self.lowest_note) as usize;
+        let note_count = (self.highest_note - self.lowest_note) as usize + 1;
+
+        let mut parameters = *self.parameters;
+        match self.layout {
+            NoteLayout::Offset => {
+                parameters.starting_offset = (MAX_OFFSET as usize * note_index / note_count) as u16;
+            }
+            NoteLayout::Target { segment_length } => {
+                let start = segment_length * note_index / note_count;
+                let end = segment_length * (note_index + 1) / note_count;
+                parameters.target = Some(trigger::PixelRange::new(start, end - start));
+            }
+        }
+
+        // harder notes fade faster, from the original fade times up to twice as long:
+        let velocity = velocity.min(MAX_DATA_VALUE) as u64;
+        let stretch =
+            |time_ns: u64| time_ns * (2 * MAX_DATA_VALUE as u64 - velocity) / MAX_DATA_VALUE as u64;
+        parameters.fade_in_time_ns = stretch(parameters.fade_in_time_ns);
+        parameters.fade_out_time_ns = stretch(parameters.fade_out_time_ns);
+        parameters.decay_time_ns = stretch(parameters.decay_time_ns);
+        Some(parameters)
+    }
+}
+
+/// What a control change sets. The controller's 0 to 127 range is scaled to the full range of
+/// the setting.
+#[derive(Copy, Clone)]
+pub enum ControlAction {
+    Brightness,
+    AnimationBrightness {
+        animation_index: usize,
+    },
+    InputLevel {
+        animation_index: usize,
+    },
+    Offset {
+        animation_index: usize,
+        animation_type: AnimationType,
+    },
+}
+
+/// Maps a single controller number to a ControlAction.
+#[derive(Copy, Clone)]
+pub struct ControlMap {
+    /// The MIDI channel to listen to, or None to listen to every channel.
+    pub channel: Option<u8>,
+    pub controller: u8,
+    pub action: ControlAction,
+}
+
+/// Turns MIDI messages into Commands for the LightingController.
+#[derive(Copy, Clone)]
+pub struct Mapper<'a> {
+    pub notes: &'a [NoteMap<'a>],
+    pub controls: &'a [ControlMap],
+}
+
+impl<'a> Mapper<'a> {
+    pub const fn new(notes: &'a [NoteMap<'a>], controls: &'a [ControlMap]) -> Self {
+        Mapper { notes, controls }
+    }
+
+    /// Calls `apply` with a Command for every map that matches the message.
+    pub fn handle(&self, message: Message, mut apply: impl FnMut(Command<'a>)) {
+        match message {
+            Message::NoteOn {
+                channel,
+                note,
+                velocity,
+            } => {
+                let maps = self.notes.iter().filter(|m| listens_to(m.channel, channel));
+                for map in maps {
+                    if let Some(parameters) = map.parameters_for(note, velocity) {
+                        apply(Command::Trigger {
+                            animation_index: map.animation_index,
+                            parameters,
+                        });
+                    }
+                }
+            }
+            Message::ControlChange {
+                channel,
+                controller,
+                value,
+            } => {
+                let maps = self
+                    .controls
+                    .iter()
+                    .filter(|m| listens_to(m.channel, channel) && m.controller == controller);
+                let level = (value.min(MAX_DATA_VALUE) as u16 * u8::MAX as u16
+                    / MAX_DATA_VALUE as u16) as u8;
+                for map in maps {
+                    apply(match map.action {
+                        ControlAction::Brightness => Command::SetBrightness(level),
+                        ControlAction::AnimationBrightness { animation_index } => {
+                            Command::SetAnimationBrightness {
+                                animation_index,
+                                brightness: level,
+                            }
+                        }
+                        ControlAction::InputLevel { animation_index } => Command::SetInputLevel {
+                            animation_index,
+                            level,
+                        },
+                        ControlAction::Offset {
+                            animation_index,
+                            animation_type,
+                        } => Command::SetOffset {
+                            animation_index,
+                            animation_type,
+                            offset: (value.min(MAX_DATA_VALUE) as u32 * MAX_OFFSET as u32
+                                / MAX_DATA_VALUE as u32) as u16,
+                        },
+                    });
+                }
+            }
+            Message::NoteOff { .. } => {}
+        }
+    }
+}
+
+fn listens_to(map_channel: Option<u8>, channel: u8) -> bool {
+    map_channel.is_none_or(|map_channel| map_channel == channel)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Pushes every byte into the parser, returning the last message completed, if any.
+    fn push_all(parser: &mut Parser, bytes: &[u8]) -> Option<Message> {
+        bytes.iter().filter_map(|&byte| parser.push(byte)).last()
+    }
+
+    #[test]
+    fn parses_note_on_with_running_status() {
+        let mut parser = Parser::new();
+        let first = Message::NoteOn {
+            channel: 2,
+            note: 60,
+            velocity: 100,
+        };
+        let second = Message::NoteOn {
+            channel: 2,
+            note: 64,
+            velocity: 90,
+        };
+        assert_eq!(push_all(&mut parser, &[NOTE_ON | 2, 60, 100]), Some(first));
+        assert_eq!(push_all(&mut parser, &[64, 90]), Some(second));
+    }
+
+    #[test]
+    fn note_on_without_velocity_is_a_note_off() {
+        let mut parser = Parser::new();
+        let message = Message::NoteOff {
+            channel: 0,
+            note: 60,
+            velocity: 0,
+        };
+        assert_eq!(push_all(&mut parser, &[NOTE_ON, 60, 0]), Some(message));
+    }
+
+    #[test]
+    fn skips_real_time_bytes_inside_a_message() {
+        let mut parser = Parser::new();
+        let message = Message::ControlChange {
+            channel: 1,
+            controller: 7,
+            value: 64,
+        };
+        let bytes = [CONTROL_CHANGE | 1, 0xf8, 7, 0xfe, 64];
+        assert_eq!(push_all(&mut parser, &bytes), Some(message));
+    }
+
+    #[test]
+    fn ignores_data_bytes_without_a_status() {
+        let mut parser = Parser::new();
+        assert_eq!(push_all(&mut parser, &[60, 100, 60, 100]), None);
+    }
+
+    #[test]
+    fn drops_messages_cut_short() {
+        let mut parser = Parser::new();
+        // a new status byte and a system message both drop the half finished message:
+        assert_eq!(push_all(&mut parser, &[NOTE_ON, 60, NOTE_OFF]), None);
+        assert_eq!(
+            push_all(&mut parser, &[60, SYSTEM_MESSAGE, 0x7e, 0xf7]),
+            None
+        );
+        // and system messages also end running status:
+        assert_eq!(push_all(&mut parser, &[60, 100]), None);
+    }
+
+    #[test]
+    fn ignores_messages_that_arent_mapped() {
+        let mut parser = Parser::new();
+        assert_eq!(push_all(&mut parser, &[PROGRAM_CHANGE, 5, 6]), None);
+        assert_eq!(push_all(&mut parser, &[0xe0, 0, 64]), None);
+    }
+}
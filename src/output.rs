@@ -0,0 +1,264 @@
+//! Output color types for the LogicalStrip. Animations are always calculated in RGB8, and colors
+//! are converted into the strip's output color type as they are written to it. This lets the same
+//! animations drive RGB strips as well as RGBW strips like the SK6812.
+//!
+//! Output types for LEDs with more than 8 bits of precision can ask for the controller's output
+//! corrections to be calculated with 16 bits per channel, using the RGB16 helpers below.
+
+use crate::colors::GammaTable;
+use crate::fixed::Q16;
+use core::marker::PhantomData;
+use rgb::{RGB16, RGB8};
+
+/// The step between each 8 bit value when it is spread over the 16 bit range.
+pub(crate) const CHANNEL_STEP: u32 = 257;
+
+/// A color type that can be written to a LogicalStrip.
+pub trait OutputColor: Copy + PartialEq {
+    /// Set to true for colors that are made from the more precise output of `from_rgb16()`.
+    const IS_PRECISE: bool = false;
+
+    fn from_rgb8(color: RGB8) -> Self;
+
+    /// Makes a color from the output of the controller calculated with 16 bits per channel. This
+    /// is only used when IS_PRECISE is true.
+    fn from_rgb16(color: RGB16) -> Self {
+        Self::from_rgb8(reduce(color))
+    }
+
+    /// Moves the red, green and blue channels into the order the LEDs expect them in, see
+    /// `ChannelOrder`. Types without separate color channels can leave this as it is.
+    fn with_channel_order(self, _order: ChannelOrder) -> Self {
+        self
+    }
+}
+
+/// The order an LED chip expects its red, green and blue values in, such as GRB for the WS2812.
+/// The controller moves the channels of each output color into this order, so that the `r` field
+/// holds the value sent first, `g` the one sent second and `b` the one sent last. Leave it as RGB
+/// for drivers that already reorder the channels themselves.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ChannelOrder {
+    #[default]
+    Rgb,
+    Rbg,
+    Grb,
+    Gbr,
+    Brg,
+    Bgr,
+}
+
+impl ChannelOrder {
+    /// Returns the channels of a color in this order.
+    pub fn apply<T>(&self, color: rgb::RGB<T>) -> rgb::RGB<T> {
+        let rgb::RGB { r, g, b } = color;
+        let [first, second, third] = match self {
+            ChannelOrder::Rgb => [r, g, b],
+            ChannelOrder::Rbg => [r, b, g],
+            ChannelOrder::Grb => [g, r, b],
+            ChannelOrder::Gbr => [g, b, r],
+            ChannelOrder::Brg => [b, r, g],
+            ChannelOrder::Bgr => [b, g, r],
+        };
+        rgb::RGB::new(first, second, third)
+    }
+}
+
+/// The most post processors that can be added to a LightingController.
+pub const MAX_POST_PROCESSORS: usize = 4;
+
+/// A custom stage run on every frame after the controller's own output corrections, such as a
+/// different gamma curve, a fix for a quirk of the LEDs, or diagnostics. See
+/// `LightingController::add_post_processor()`.
+pub trait PostProcessor {
+    /// Changes the colors of a finished frame, which holds one color for each LED on the strip in
+    /// physical order.
+    fn process(&mut self, frame: &mut [RGB8]);
+}
+
+/// Spreads an 8 bit color over the full 16 bit range, so 255 becomes 65535.
+pub fn expand(color: RGB8) -> RGB16 {
+    let expand = |channel: u8| channel as u16 * CHANNEL_STEP as u16;
+    RGB16::new(expand(color.r), expand(color.g), expand(color.b))
+}
+
+/// Rounds a 16 bit color to the nearest 8 bit color.
+pub fn reduce(color: RGB16) -> RGB8 {
+    let reduce = |channel: u16| ((channel as u32 + CHANNEL_STEP / 2) / CHANNEL_STEP) as u8;
+    RGB8::new(reduce(color.r), reduce(color.g), reduce(color.b))
+}
+
+/// Scales every channel of a 16 bit color by `brightness`, the same as `scaled_by()` does for 8
+/// bit colors.
+pub fn scaled_by16(color: RGB16, brightness: u8) -> RGB16 {
+    corrected_by16(color, RGB8::new(brightness, brightness, brightness))
+}
+
+/// Scales each channel of a 16 bit color by the matching channel of the correction, the same as
+/// `corrected_by()` does for 8 bit colors.
+pub fn corrected_by16(color: RGB16, correction: RGB8) -> RGB16 {
+    let scale = |channel: u16, factor: u8| (channel as u32 * factor as u32 / 255) as u16;
+    RGB16::new(
+        scale(color.r, correction.r),
+        scale(color.g, correction.g),
+        scale(color.b, correction.b),
+    )
+}
+
+/// Gamma corrects a 16 bit color by interpolating between the entries of the gamma table.
+pub fn gamma_corrected16(color: RGB16, table: &GammaTable) -> RGB16 {
+    let correct = |channel: u16| {
+        let index = channel as u32 / CHANNEL_STEP;
+        let fraction = channel as u32 % CHANNEL_STEP;
+        let start = table.correct_channel(index as u8) as u32 * CHANNEL_STEP;
+        let end = table.correct_channel((index + 1).min(255) as u8) as u32 * CHANNEL_STEP;
+        (start + (end - start) * fraction / CHANNEL_STEP) as u16
+    };
+    RGB16::new(correct(color.r), correct(color.g), correct(color.b))
+}
+
+/// A level of the whole output that fades from one value to another over time, such as for a
+/// blackout.
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct LevelFade {
+    start: u8,
+    end: u8,
+    duration_ns: u64,
+    elapsed_ns: u64,
+}
+
+impl LevelFade {
+    /// Makes a level that stays where it is until it is faded.
+    pub const fn steady(level: u8) -> Self {
+        LevelFade {
+            start: level,
+            end: level,
+            duration_ns: 0,
+            elapsed_ns: 0,
+        }
+    }
+
+    /// Starts fading from the current level to a new one. A fade already in progress turns
+    /// around from wherever it has reached.
+    pub fn fade_to(&mut self, level: u8, duration_ns: u64) {
+        self.start = self.level();
+        self.end = level;
+        self.duration_ns = duration_ns;
+        self.elapsed_ns = 0;
+    }
+
+    pub fn advance(&mut self, elapsed_ns: u64) {
+        self.elapsed_ns = self
+            .elapsed_ns
+            .saturating_add(elapsed_ns)
+            .min(self.duration_ns);
+    }
+
+    pub fn level(&self) -> u8 {
+        let factor = Q16::from_ratio(self.elapsed_ns, self.duration_ns);
+        match self.elapsed_ns >= self.duration_ns {
+            true => self.end,
+            false => factor.lerp_u8(self.start, self.end),
+        }
+    }
+}
+
+impl OutputColor for RGB8 {
+    fn from_rgb8(color: RGB8) -> Self {
+        color
+    }
+
+    fn with_channel_order(self, order: ChannelOrder) -> Self {
+        order.apply(self)
+    }
+}
+
+/// Decides how much of an RGB color is moved onto the white channel of an RGBW LED. Returns the
+/// remaining RGB color and the value of the white channel.
+pub trait WhiteExtraction {
+    fn extract(color: RGB8) -> (RGB8, u8);
+}
+
+/// Moves the part of the color shared by all three channels onto the white channel. This assumes
+/// the white LED is the same color as the RGB LEDs at full brightness.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct MinOfRgb;
+
+impl WhiteExtraction for MinOfRgb {
+    fn extract(color: RGB8) -> (RGB8, u8) {
+        let w = color.r.min(color.g).min(color.b);
+        (RGB8::new(color.r - w, color.g - w, color.b - w), w)
+    }
+}
+
+/// Moves as much of the color as possible onto a white LED with the tint given by R, G and B, as
+/// measured against the RGB LEDs. This keeps colors accurate on strips with warm or cool white
+/// LEDs, see `WarmWhite3000K`, `NeutralWhite4000K` and `CoolWhite5000K` below for some common
+/// ones.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct WhitePoint<const R: u8, const G: u8, const B: u8>;
+
+impl<const R: u8, const G: u8, const B: u8> WhiteExtraction for WhitePoint<R, G, B> {
+    fn extract(color: RGB8) -> (RGB8, u8) {
+        // the largest amount of white that doesn't overshoot any of the channels:
+        let fit = |channel: u8, tint: u8| match tint {
+            0 => u8::MAX as u16,
+            _ => channel as u16 * u8::MAX as u16 / tint as u16,
+        };
+        let w = fit(color.r, R)
+            .min(fit(color.g, G))
+            .min(fit(color.b, B))
+            .min(u8::MAX as u16);
+
+        let remove = |channel: u8, tint: u8| channel - (w * tint as u16 / u8::MAX as u16) as u8;
+        let remaining = RGB8::new(remove(color.r, R), remove(color.g, G), remove(color.b, B));
+        (remaining, w as u8)
+    }
+}
+
+/// White LEDs with a color temperature of roughly 3000K
+pub type WarmWhite3000K = WhitePoint<255, 180, 107>;
+
+/// White LEDs with a color temperature of roughly 4000K
+pub type NeutralWhite4000K = WhitePoint<255, 209, 163>;
+
+/// White LEDs with a color temperature of roughly 5000K
+pub type CoolWhite5000K = WhitePoint<255, 228, 206>;
+
+/// An RGBW color for strips with a separate white LED, such as the SK6812. The white channel is
+/// calculated from the animation colors using the WhiteExtraction strategy `S`.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct RGBW8<S = MinOfRgb> {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub w: u8,
+    strategy: PhantomData<S>,
+}
+
+impl<S> RGBW8<S> {
+    pub const fn new(r: u8, g: u8, b: u8, w: u8) -> Self {
+        RGBW8 {
+            r,
+            g,
+            b,
+            w,
+            strategy: PhantomData,
+        }
+    }
+}
+
+impl<S: WhiteExtraction + Copy + PartialEq> OutputColor for RGBW8<S> {
+    fn from_rgb8(color: RGB8) -> Self {
+        let (rgb, w) = S::extract(color);
+        RGBW8::new(rgb.r, rgb.g, rgb.b, w)
+    }
+
+    /// Reorders the color channels, leaving white as the last channel.
+    fn with_channel_order(self, order: ChannelOrder) -> Self {
+        let rgb = order.apply(RGB8::new(self.r, self.g, self.b));
+        RGBW8::new(rgb.r, rgb.g, rgb.b, self.w)
+    }
+}
@@ -0,0 +1,81 @@
+//! Frame timing for the main loop. A FramePacer reads an `embedded_time::Clock` to decide when
+//! the next frame of the LightingController is due, so firmware doesn't need its own timing glue.
+//! Lateness from slow renders or a busy main loop is carried over to the next frame, so the
+//! average frame rate stays correct even when individual frames are late.
+
+use core::convert::TryFrom;
+use embedded_time::clock;
+use embedded_time::duration::Nanoseconds;
+use embedded_time::fixed_point::FixedPoint;
+use embedded_time::rate::Hertz;
+use embedded_time::{Clock, Instant};
+
+pub struct FramePacer<'c, C: Clock> {
+    clock: &'c C,
+    frame_ns: u64,
+    last_poll: Option<Instant<C>>,
+    lag_ns: u64,
+    since_frame_ns: u64,
+}
+
+impl<'c, C: Clock> FramePacer<'c, C>
+where
+    u64: TryFrom<C::T>,
+{
+    pub fn new(clock: &'c C, frame_rate: impl Into<Hertz>) -> Self {
+        FramePacer {
+            clock,
+            frame_ns: frame_period_ns(frame_rate.into()),
+            last_poll: None,
+            lag_ns: 0,
+            since_frame_ns: 0,
+        }
+    }
+
+    /// Changes the frame rate. This should match the rate set on the LightingController.
+    pub fn set_frame_rate(&mut self, frame_rate: impl Into<Hertz>) {
+        self.frame_ns = frame_period_ns(frame_rate.into());
+    }
+
+    /// Checks whether the next frame is due. When it is, this returns the actual time since the
+    /// previous frame, which can be passed to `LightingController::update_with_elapsed()`. The
+    /// first call is always due. If the loop has fallen more than a frame behind, the missed
+    /// frames are skipped rather than rendered back to back.
+    pub fn poll(&mut self) -> Result<Option<Nanoseconds<u64>>, clock::Error> {
+        self.advance()?;
+        if self.lag_ns < self.frame_ns {
+            return Ok(None);
+        }
+        self.lag_ns = (self.lag_ns - self.frame_ns) % self.frame_ns;
+        let elapsed = core::mem::take(&mut self.since_frame_ns);
+        Ok(Some(Nanoseconds(elapsed)))
+    }
+
+    /// Returns how long the caller can sleep before the next frame is due. This is zero when a
+    /// frame is already due.
+    pub fn time_until_next_frame(&mut self) -> Result<Nanoseconds<u64>, clock::Error> {
+        self.advance()?;
+        Ok(Nanoseconds(self.frame_ns.saturating_sub(self.lag_ns)))
+    }
+
+    /// Adds the time since the last poll to the running totals.
+    fn advance(&mut self) -> Result<(), clock::Error> {
+        let now = self.clock.try_now()?;
+        let elapsed_ns = match &self.last_poll {
+            Some(last_poll) => now
+                .checked_duration_since(last_poll)
+                .and_then(|elapsed| Nanoseconds::<u64>::try_from(elapsed).ok())
+                .map_or(0, |elapsed| elapsed.integer()),
+            // the first frame is due straight away:
+            None => self.frame_ns,
+        };
+        self.last_poll = Some(now);
+        self.lag_ns += elapsed_ns;
+        self.since_frame_ns += elapsed_ns;
+        Ok(())
+    }
+}
+
+fn frame_period_ns(frame_rate: Hertz) -> u64 {
+    (1_000_000_000 / frame_rate.integer().max(1) as u64).max(1)
+}
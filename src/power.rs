@@ -0,0 +1,50 @@
+//! Power limiting for LED strips. Every color channel of an addressable LED draws current in
+//! proportion to its value, so a long strip showing bright white can easily draw more current than
+//! its power supply is able to provide. A PowerBudget estimates the current a frame will draw and
+//! calculates how much the frame needs to be dimmed to stay within the budget.
+
+use crate::colors::FULL_BRIGHTNESS;
+use rgb::RGB8;
+
+/// Approximate current drawn by a single WS2812 color channel at full brightness, in milliamps.
+pub const WS2812_MILLIAMPS_PER_CHANNEL: u32 = 20;
+
+/// Approximate current drawn by a single WS2812 LED when it is completely off, in milliamps.
+pub const WS2812_IDLE_MILLIAMPS_PER_LED: u32 = 1;
+
+#[derive(Copy, Clone, Debug)]
+pub struct PowerBudget {
+    pub max_milliamps: u32,
+    pub milliamps_per_channel: u32,
+    pub idle_milliamps_per_led: u32,
+}
+
+impl PowerBudget {
+    /// Creates a budget using the typical current draw of WS2812 LEDs.
+    pub const fn new(max_milliamps: u32) -> Self {
+        PowerBudget {
+            max_milliamps,
+            milliamps_per_channel: WS2812_MILLIAMPS_PER_CHANNEL,
+            idle_milliamps_per_led: WS2812_IDLE_MILLIAMPS_PER_LED,
+        }
+    }
+
+    /// Estimates the current used by the color channels of all the colors, not including the idle
+    /// current of the LEDs.
+    pub fn estimate_channel_milliamps(&self, colors: impl Iterator<Item = RGB8>) -> u32 {
+        let channel_total: u32 = colors.map(|c| c.r as u32 + c.g as u32 + c.b as u32).sum();
+        channel_total * self.milliamps_per_channel / FULL_BRIGHTNESS as u32
+    }
+
+    /// Returns the brightness the frame needs to be scaled by to stay within the budget, given the
+    /// estimated channel current of the frame and the total number of LEDs on the strip.
+    pub fn limiting_brightness(&self, channel_milliamps: u32, led_count: usize) -> u8 {
+        let idle_milliamps = self.idle_milliamps_per_led * led_count as u32;
+        let available_milliamps = self.max_milliamps.saturating_sub(idle_milliamps);
+
+        if channel_milliamps <= available_milliamps {
+            return FULL_BRIGHTNESS;
+        }
+        (available_milliamps * FULL_BRIGHTNESS as u32 / channel_milliamps) as u8
+    }
+}
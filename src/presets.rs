@@ -0,0 +1,77 @@
+//! Numbered presets of animation parameters. A PresetBank holds the effects that a device offers,
+//! such as "effect 1" to "effect N" on a button or a menu, and keeps track of which one is
+//! selected so the firmware can cycle through them or jump straight to one by its ID.
+
+use crate::animations::AnimationParameters;
+
+/// A set of background, foreground and trigger parameters with an ID to select it by.
+pub struct Preset<'a> {
+    pub id: u8,
+    pub parameters: AnimationParameters<'a>,
+}
+
+impl<'a> Preset<'a> {
+    pub const fn new(id: u8, parameters: AnimationParameters<'a>) -> Self {
+        Preset { id, parameters }
+    }
+}
+
+/// A fixed list of presets and the one that is currently selected.
+pub struct PresetBank<'a, const N: usize> {
+    presets: [Preset<'a>; N],
+    current: usize,
+}
+
+impl<'a, const N: usize> PresetBank<'a, N> {
+    /// Makes a bank with the first preset selected. A bank needs at least one preset to select,
+    /// so an empty bank fails to compile.
+    pub const fn new(presets: [Preset<'a>; N]) -> Self {
+        const { assert!(N > 0, "a PresetBank needs at least one preset") };
+        PresetBank {
+            presets,
+            current: 0,
+        }
+    }
+
+    /// Returns the currently selected preset.
+    pub fn current(&self) -> &Preset<'a> {
+        &self.presets[self.current]
+    }
+
+    pub fn current_index(&self) -> usize {
+        self.current
+    }
+
+    pub fn presets(&self) -> &[Preset<'a>] {
+        &self.presets
+    }
+
+    /// Selects the next preset, wrapping back around to the first one after the last.
+    pub fn select_next(&mut self) -> &Preset<'a> {
+        self.current = (self.current + 1) % N;
+        self.current()
+    }
+
+    /// Selects the previous preset, wrapping around to the last one before the first.
+    pub fn select_previous(&mut self) -> &Preset<'a> {
+        self.current = (self.current + N - 1) % N;
+        self.current()
+    }
+
+    /// Selects the preset at a position in the bank. Returns None and keeps the current selection
+    /// if the index is out of range.
+    pub fn select_index(&mut self, index: usize) -> Option<&Preset<'a>> {
+        if index >= N {
+            return None;
+        }
+        self.current = index;
+        Some(self.current())
+    }
+
+    /// Selects the preset with a matching ID. Returns None and keeps the current selection if no
+    /// preset has that ID.
+    pub fn select_id(&mut self, id: u8) -> Option<&Preset<'a>> {
+        let index = self.presets.iter().position(|p| p.id == id)?;
+        self.select_index(index)
+    }
+}
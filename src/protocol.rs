@@ -0,0 +1,301 @@
+//! A compact binary protocol for controlling the LightingController at runtime over a serial
+//! port, USB or radio link. Each message is sent as a frame:
+//!
+//! | byte         | contents                                       |
+//! |--------------|------------------------------------------------|
+//! | 0            | FRAME_START (0x7E)                             |
+//! | 1            | the length of the message ID and payload       |
+//! | 2            | the message ID                                 |
+//! | 3..          | the payload                                    |
+//! | last         | CRC-8 of everything after FRAME_START          |
+//!
+//! Multi-byte values are little endian. Frames with a bad length or checksum are dropped, and the
+//! Parser resynchronizes on the next FRAME_START, so a receiver can join a stream at any point.
+
+use crate::animations::{trigger, AnimationType};
+use crate::colors::RainbowBuf;
+use crate::commands::Command;
+use crate::presets::Preset;
+use arrayvec::ArrayVec;
+use rgb::RGB8;
+
+/// The first byte of every frame.
+pub const FRAME_START: u8 = 0x7e;
+
+/// The most colors that can be sent in a single UploadPalette message.
+pub const MAX_PALETTE_COLORS: usize = 16;
+
+/// The longest payload of any message, which is an UploadPalette with every color.
+pub const MAX_PAYLOAD_SIZE: usize = 2 + MAX_PALETTE_COLORS * 3;
+
+/// The longest frame of any message, including the start byte, length and checksum.
+pub const MAX_FRAME_SIZE: usize = MAX_PAYLOAD_SIZE + 4;
+
+const SELECT_PRESET: u8 = 0x01;
+const SET_BRIGHTNESS: u8 = 0x02;
+const SET_ANIMATION_BRIGHTNESS: u8 = 0x03;
+const SET_OFFSET: u8 = 0x04;
+const SET_INPUT_LEVEL: u8 = 0x05;
+const FIRE_TRIGGER: u8 = 0x06;
+const UPLOAD_PALETTE: u8 = 0x07;
+
+/// The messages of the protocol. Animations, triggers and palettes are referred to by their index
+/// in the firmware's own lists, and presets by their ID.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Message {
+    SelectPreset {
+        animation_index: u8,
+        preset_id: u8,
+    },
+    SetBrightness(u8),
+    SetAnimationBrightness {
+        animation_index: u8,
+        brightness: u8,
+    },
+    SetOffset {
+        animation_index: u8,
+        animation_type: AnimationType,
+        offset: u16,
+    },
+    SetInputLevel {
+        animation_index: u8,
+        level: u8,
+    },
+    FireTrigger {
+        animation_index: u8,
+        trigger_index: u8,
+    },
+    /// Replaces the colors of one of the firmware's palettes.
+    UploadPalette {
+        palette_index: u8,
+        colors: RainbowBuf<MAX_PALETTE_COLORS>,
+    },
+}
+
+impl Message {
+    /// Writes the message as a complete frame. Returns the length of the frame, or None if
+    /// `frame` is too short to hold it.
+    pub fn encode(&self, frame: &mut [u8]) -> Option<usize> {
+        let mut payload = ArrayVec::<u8, MAX_PAYLOAD_SIZE>::new();
+        let id = match self {
+            Message::SelectPreset {
+                animation_index,
+                preset_id,
+            } => {
+                payload.extend([*animation_index, *preset_id]);
+                SELECT_PRESET
+            }
+            Message::SetBrightness(brightness) => {
+                payload.push(*brightness);
+                SET_BRIGHTNESS
+            }
+            Message::SetAnimationBrightness {
+                animation_index,
+                brightness,
+            } => {
+                payload.extend([*animation_index, *brightness]);
+                SET_ANIMATION_BRIGHTNESS
+            }
+            Message::SetOffset {
+                animation_index,
+                animation_type,
+                offset,
+            } => {
+                let [low, high] = offset.to_le_bytes();
+                let animation_type = match animation_type {
+                    AnimationType::Background => 0,
+                    AnimationType::Foreground => 1,
+                    AnimationType::Trigger => 2,
+                };
+                payload.extend([*animation_index, animation_type, low, high]);
+                SET_OFFSET
+            }
+            Message::SetInputLevel {
+                animation_index,
+                level,
+            } => {
+                payload.extend([*animation_index, *level]);
+                SET_INPUT_LEVEL
+            }
+            Message::FireTrigger {
+                animation_index,
+                trigger_index,
+            } => {
+                payload.extend([*animation_index, *trigger_index]);
+                FIRE_TRIGGER
+            }
+            Message::UploadPalette {
+                palette_index,
+                colors,
+            } => {
+                payload.extend([*palette_index, colors.len() as u8]);
+                for color in colors.iter() {
+                    payload.extend([color.r, color.g, color.b]);
+                }
+                UPLOAD_PALETTE
+            }
+        };
+
+        let length = payload.len() + 1;
+        let frame = frame.get_mut(..length + 3)?;
+        frame[0] = FRAME_START;
+        frame[1] = length as u8;
+        frame[2] = id;
+        frame[3..length + 2].copy_from_slice(&payload);
+        frame[length + 2] = crc8(&frame[1..length + 2]);
+        Some(length + 3)
+    }
+
+    /// Decodes a message from its ID and payload, or returns None if they aren't valid.
+    fn decode(id: u8, payload: &[u8]) -> Option<Self> {
+        let message = match (id, payload) {
+            (SELECT_PRESET, &[animation_index, preset_id]) => Message::SelectPreset {
+                animation_index,
+                preset_id,
+            },
+            (SET_BRIGHTNESS, &[brightness]) => Message::SetBrightness(brightness),
+            (SET_ANIMATION_BRIGHTNESS, &[animation_index, brightness]) => {
+                Message::SetAnimationBrightness {
+                    animation_index,
+                    brightness,
+                }
+            }
+            (SET_OFFSET, &[animation_index, animation_type, low, high]) => Message::SetOffset {
+                animation_index,
+                animation_type: match animation_type {
+                    0 => AnimationType::Background,
+                    1 => AnimationType::Foreground,
+                    2 => AnimationType::Trigger,
+                    _ => return None,
+                },
+                offset: u16::from_le_bytes([low, high]),
+            },
+            (SET_INPUT_LEVEL, &[animation_index, level]) => Message::SetInputLevel {
+                animation_index,
+                level,
+            },
+            (FIRE_TRIGGER, &[animation_index, trigger_index]) => Message::FireTrigger {
+                animation_index,
+                trigger_index,
+            },
+            (UPLOAD_PALETTE, &[palette_index, count, ref colors @ ..]) => {
+                if count as usize > MAX_PALETTE_COLORS || colors.len() != count as usize * 3 {
+                    return None;
+                }
+                Message::UploadPalette {
+                    palette_index,
+                    colors: colors
+                        .chunks_exact(3)
+                        .map(|rgb| RGB8::new(rgb[0], rgb[1], rgb[2]))
+                        .collect(),
+                }
+            }
+            _ => return None,
+        };
+        Some(message)
+    }
+
+    /// Converts the message into a Command for the LightingController, looking up triggers and
+    /// presets in the firmware's lists. Returns None for UploadPalette, which the firmware stores
+    /// itself, and for indexes and IDs that aren't in the lists.
+    pub fn to_command<'a>(
+        &self,
+        triggers: &[trigger::Parameters],
+        presets: &'a [Preset<'a>],
+    ) -> Option<Command<'a>> {
+        let command = match *self {
+            Message::SelectPreset {
+                animation_index,
+                preset_id,
+            } => Command::SetParameters {
+                animation_index: animation_index as usize,
+                parameters: &presets.iter().find(|p| p.id == preset_id)?.parameters,
+            },
+            Message::SetBrightness(brightness) => Command::SetBrightness(brightness),
+            Message::SetAnimationBrightness {
+                animation_index,
+                brightness,
+            } => Command::SetAnimationBrightness {
+                animation_index: animation_index as usize,
+                brightness,
+            },
+            Message::SetOffset {
+                animation_index,
+                animation_type,
+                offset,
+            } => Command::SetOffset {
+                animation_index: animation_index as usize,
+                animation_type,
+                offset,
+            },
+            Message::SetInputLevel {
+                animation_index,
+                level,
+            } => Command::SetInputLevel {
+                animation_index: animation_index as usize,
+                level,
+            },
+            Message::FireTrigger {
+                animation_index,
+                trigger_index,
+            } => Command::Trigger {
+                animation_index: animation_index as usize,
+                parameters: *triggers.get(trigger_index as usize)?,
+            },
+            Message::UploadPalette { .. } => return None,
+        };
+        Some(command)
+    }
+}
+
+/// Assembles Messages from a stream of bytes, one byte at a time.
+#[derive(Default)]
+pub struct Parser {
+    frame: ArrayVec<u8, MAX_FRAME_SIZE>,
+}
+
+impl Parser {
+    pub const fn new() -> Self {
+        Parser {
+            frame: ArrayVec::new_const(),
+        }
+    }
+
+    /// Adds the next byte of the stream, returning a Message if it completed a valid frame.
+    pub fn push(&mut self, byte: u8) -> Option<Message> {
+        if self.frame.is_empty() {
+            // skip anything between frames:
+            if byte == FRAME_START {
+                self.frame.push(byte);
+            }
+            return None;
+        }
+
+        self.frame.push(byte);
+        let length = self.frame[1] as usize;
+        if length == 0 || length > MAX_PAYLOAD_SIZE + 1 {
+            self.frame.clear();
+            return None;
+        }
+        if self.frame.len() < length + 3 {
+            return None;
+        }
+
+        let is_valid = crc8(&self.frame[1..length + 2]) == self.frame[length + 2];
+        let message = is_valid
+            .then(|| Message::decode(self.frame[2], &self.frame[3..length + 2]))
+            .flatten();
+        self.frame.clear();
+        message
+    }
+}
+
+/// CRC-8 with the polynomial 0x07, as used by SMBus.
+fn crc8(bytes: &[u8]) -> u8 {
+    bytes.iter().fold(0, |crc, byte| {
+        (0..8).fold(crc ^ byte, |crc, _| match crc & 0x80 {
+            0 => crc << 1,
+            _ => (crc << 1) ^ 0x07,
+        })
+    })
+}
@@ -0,0 +1,204 @@
+//! E1.31 (sACN) output. A Source wraps the DMX universes from the `dmx` module in E1.31 data
+//! packets, so devices with a network connection can stream the controller's output to sACN LED
+//! receivers. The packets are written into byte buffers, ready to be sent over UDP to port 5568,
+//! either to the receiver's address or to the universe's multicast address.
+
+use crate::dmx::{Universe, CHANNELS_PER_UNIVERSE};
+
+/// The UDP port that sACN receivers listen on.
+pub const PORT: u16 = 5568;
+
+/// The largest possible data packet, with a full universe of 512 channels.
+pub const MAX_PACKET_SIZE: usize = HEADER_SIZE + CHANNELS_PER_UNIVERSE;
+
+/// The priority used by sources that don't set one.
+pub const DEFAULT_PRIORITY: u8 = 100;
+
+/// The highest priority allowed by E1.31.
+pub const MAX_PRIORITY: u8 = 200;
+
+/// Universe numbers must be between 1 and 63999.
+pub const MAX_UNIVERSE: u16 = 63999;
+
+const HEADER_SIZE: usize = 126;
+const SOURCE_NAME_SIZE: usize = 64;
+const ACN_PACKET_IDENTIFIER: [u8; 12] = *b"ASC-E1.17\0\0\0";
+const VECTOR_ROOT_E131_DATA: u32 = 0x0000_0004;
+const VECTOR_E131_DATA_PACKET: u32 = 0x0000_0002;
+const VECTOR_DMP_SET_PROPERTY: u8 = 0x02;
+const DMP_ADDRESS_AND_DATA_TYPE: u8 = 0xa1;
+const FLAGS: u16 = 0x7000;
+
+// offsets of the fields that change between packets:
+const ROOT_LENGTH_OFFSET: usize = 16;
+const FRAMING_LENGTH_OFFSET: usize = 38;
+const SEQUENCE_OFFSET: usize = 111;
+const UNIVERSE_OFFSET: usize = 113;
+const DMP_LENGTH_OFFSET: usize = 115;
+const PROPERTY_COUNT_OFFSET: usize = 123;
+
+/// Returns the IPv4 multicast address that receivers listen on for a universe.
+pub fn multicast_address(universe: u16) -> [u8; 4] {
+    let [high, low] = universe.to_be_bytes();
+    [239, 255, high, low]
+}
+
+/// An sACN source sending N_UNIVERSES consecutive universes, starting at `first_universe`. Each
+/// universe keeps its own sequence number so receivers can spot lost or reordered packets.
+pub struct Source<const N_UNIVERSES: usize> {
+    cid: [u8; 16],
+    source_name: [u8; SOURCE_NAME_SIZE],
+    priority: u8,
+    first_universe: u16,
+    sequence_numbers: [u8; N_UNIVERSES],
+}
+
+impl<const N_UNIVERSES: usize> Source<N_UNIVERSES> {
+    /// Creates a new source. The CID is a UUID that should be unique to each device and stay the
+    /// same across restarts. Source names longer than 63 bytes are cut short.
+    pub fn new(cid: [u8; 16], source_name: &str, first_universe: u16) -> Self {
+        let mut name = [0; SOURCE_NAME_SIZE];
+        // the name must always end with a null byte:
+        let length = source_name.len().min(SOURCE_NAME_SIZE - 1);
+        name[..length].copy_from_slice(&source_name.as_bytes()[..length]);
+        Source {
+            cid,
+            source_name: name,
+            priority: DEFAULT_PRIORITY,
+            first_universe: first_universe.clamp(1, MAX_UNIVERSE),
+            sequence_numbers: [0; N_UNIVERSES],
+        }
+    }
+
+    pub fn priority(&self) -> u8 {
+        self.priority
+    }
+
+    /// Sets the priority of the packets, from 0 to 200. Receivers with more than one source use
+    /// the one with the highest priority.
+    pub fn set_priority(&mut self, priority: u8) {
+        self.priority = priority.min(MAX_PRIORITY);
+    }
+
+    /// The universe number that `universe_index` is sent to.
+    pub fn universe_number(&self, universe_index: usize) -> u16 {
+        self.first_universe.saturating_add(universe_index as u16)
+    }
+
+    /// Writes an E1.31 data packet for the DMX universe at `universe_index` into `packet`, and
+    /// moves on to the next sequence number for that universe. Returns the length of the packet,
+    /// or None if `packet` is too short or the index is past the last universe of this source.
+    pub fn write_packet(
+        &mut self,
+        universe_index: usize,
+        universe: &Universe,
+        packet: &mut [u8],
+    ) -> Option<usize> {
+        let universe_number = self.universe_number(universe_index);
+        let sequence_number = self.sequence_numbers.get_mut(universe_index)?;
+        let channels = universe.channels();
+        let length = HEADER_SIZE + channels.len();
+        let packet = packet.get_mut(..length)?;
+
+        // root layer:
+        packet[0..2].copy_from_slice(&0x0010_u16.to_be_bytes());
+        packet[2..4].copy_from_slice(&0x0000_u16.to_be_bytes());
+        packet[4..16].copy_from_slice(&ACN_PACKET_IDENTIFIER);
+        write_flags_and_length(packet, ROOT_LENGTH_OFFSET);
+        packet[18..22].copy_from_slice(&VECTOR_ROOT_E131_DATA.to_be_bytes());
+        packet[22..38].copy_from_slice(&self.cid);
+
+        // framing layer:
+        write_flags_and_length(packet, FRAMING_LENGTH_OFFSET);
+        packet[40..44].copy_from_slice(&VECTOR_E131_DATA_PACKET.to_be_bytes());
+        packet[44..108].copy_from_slice(&self.source_name);
+        packet[108] = self.priority;
+        // no synchronization universe:
+        packet[109..111].copy_from_slice(&0_u16.to_be_bytes());
+        packet[SEQUENCE_OFFSET] = *sequence_number;
+        // no options set:
+        packet[112] = 0;
+        packet[UNIVERSE_OFFSET..UNIVERSE_OFFSET + 2]
+            .copy_from_slice(&universe_number.to_be_bytes());
+
+        // DMP layer:
+        write_flags_and_length(packet, DMP_LENGTH_OFFSET);
+        packet[117] = VECTOR_DMP_SET_PROPERTY;
+        packet[118] = DMP_ADDRESS_AND_DATA_TYPE;
+        // first property address, then address increment:
+        packet[119..121].copy_from_slice(&0_u16.to_be_bytes());
+        packet[121..123].copy_from_slice(&1_u16.to_be_bytes());
+        // the property values are the start code followed by the channels:
+        let property_count = (channels.len() + 1) as u16;
+        packet[PROPERTY_COUNT_OFFSET..PROPERTY_COUNT_OFFSET + 2]
+            .copy_from_slice(&property_count.to_be_bytes());
+        packet[125] = universe.start_code();
+        packet[HEADER_SIZE..].copy_from_slice(channels);
+
+        *sequence_number = sequence_number.wrapping_add(1);
+        Some(length)
+    }
+}
+
+/// Each layer starts with its length from that point to the end of the packet, in the low 12 bits
+/// of a u16 that has the flags in the top 4 bits.
+fn write_flags_and_length(packet: &mut [u8], offset: usize) {
+    let length = (packet.len() - offset) as u16;
+    packet[offset..offset + 2].copy_from_slice(&(FLAGS | length).to_be_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CID: [u8; 16] = [7; 16];
+
+    fn universe_of(channel_count: usize) -> Universe {
+        let mut universe = Universe::new();
+        for channel in 1..=channel_count {
+            universe.set_channel(channel, channel as u8);
+        }
+        universe
+    }
+
+    #[test]
+    fn writes_a_data_packet() {
+        let mut source = Source::<2>::new(CID, "test", 5);
+        let universe = universe_of(3);
+        let mut packet = [0; HEADER_SIZE + 3];
+        assert_eq!(
+            source.write_packet(1, &universe, &mut packet),
+            Some(packet.len())
+        );
+        assert_eq!(&packet[4..16], &ACN_PACKET_IDENTIFIER);
+        assert_eq!(&packet[22..38], &CID);
+        assert_eq!(&packet[44..49], b"test\0");
+        assert_eq!(packet[SEQUENCE_OFFSET], 0);
+        assert_eq!(
+            &packet[UNIVERSE_OFFSET..UNIVERSE_OFFSET + 2],
+            &6_u16.to_be_bytes()
+        );
+        assert_eq!(&packet[HEADER_SIZE..], &[1, 2, 3]);
+
+        // each universe counts its own packets:
+        source.write_packet(1, &universe, &mut packet).unwrap();
+        assert_eq!(packet[SEQUENCE_OFFSET], 1);
+        source.write_packet(0, &universe, &mut packet).unwrap();
+        assert_eq!(packet[SEQUENCE_OFFSET], 0);
+    }
+
+    #[test]
+    fn rejects_short_buffers_and_unknown_universes() {
+        let mut source = Source::<1>::new(CID, "test", 1);
+        let universe = universe_of(3);
+        let mut packet = [0; HEADER_SIZE + 3];
+        assert_eq!(
+            source.write_packet(0, &universe, &mut packet[..HEADER_SIZE + 2]),
+            None
+        );
+        assert_eq!(source.write_packet(1, &universe, &mut packet), None);
+        // a rejected packet doesn't use up a sequence number:
+        source.write_packet(0, &universe, &mut packet).unwrap();
+        assert_eq!(packet[SEQUENCE_OFFSET], 0);
+    }
+}
@@ -0,0 +1,124 @@
+//! Time of day scheduling for ambient installations, such as dimming the lights at night and
+//! switching to a calmer effect in the early morning. A Scheduler reads the time from a real time
+//! clock supplied by the firmware and applies the rule for the part of the day it is in, using the
+//! same actions as the cues of a `sequencer::Sequencer`.
+//!
+//! Each rule starts at a time of day and lasts until the next rule starts, with the last rule of
+//! the day carrying on past midnight until the first. Call `update()` every so often, such as once
+//! a second or every frame: the actions of a rule are applied once when its part of the day
+//! starts, and straight away for the rule that is already running when the scheduler starts.
+
+use crate::sequencer::{self, Action};
+use crate::LightingController;
+
+const MINUTES_PER_HOUR: u16 = 60;
+const MINUTES_PER_DAY: u16 = 24 * MINUTES_PER_HOUR;
+
+/// A time of day to the minute, from 00:00 to 23:59.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct TimeOfDay {
+    minutes: u16,
+}
+
+impl TimeOfDay {
+    pub const MIDNIGHT: TimeOfDay = TimeOfDay { minutes: 0 };
+
+    /// Makes a time from the hour, from 0 to 23, and the minute. Larger values wrap around.
+    pub const fn new(hour: u8, minute: u8) -> Self {
+        Self::from_minutes(hour as u16 * MINUTES_PER_HOUR + minute as u16)
+    }
+
+    /// Makes a time from the number of minutes since midnight.
+    pub const fn from_minutes(minutes: u16) -> Self {
+        TimeOfDay {
+            minutes: minutes % MINUTES_PER_DAY,
+        }
+    }
+
+    pub const fn hour(&self) -> u8 {
+        (self.minutes / MINUTES_PER_HOUR) as u8
+    }
+
+    pub const fn minute(&self) -> u8 {
+        (self.minutes % MINUTES_PER_HOUR) as u8
+    }
+
+    pub const fn minutes_since_midnight(&self) -> u16 {
+        self.minutes
+    }
+}
+
+/// A wall clock, such as an RTC chip or a clock kept in sync over the network.
+pub trait Rtc {
+    fn time_of_day(&self) -> TimeOfDay;
+}
+
+/// The actions to apply at the start of a part of the day.
+#[derive(Copy, Clone)]
+pub struct Rule<'a> {
+    pub start: TimeOfDay,
+    pub actions: &'a [Action<'a>],
+}
+
+impl<'a> Rule<'a> {
+    pub const fn new(start: TimeOfDay, actions: &'a [Action<'a>]) -> Self {
+        Rule { start, actions }
+    }
+}
+
+/// Applies a list of rules as the day goes by. The rules have to be sorted by their start times.
+pub struct Scheduler<'s, 'a> {
+    rules: &'s [Rule<'a>],
+    active: Option<usize>,
+}
+
+impl<'s, 'a> Scheduler<'s, 'a> {
+    pub const fn new(rules: &'s [Rule<'a>]) -> Self {
+        Scheduler {
+            rules,
+            active: None,
+        }
+    }
+
+    /// Reads the time and applies the actions of the rule for it if it is different from the
+    /// last rule applied. Returns whether a rule was applied.
+    pub fn update<R: Rtc, const N_ANI: usize>(
+        &mut self,
+        clock: &R,
+        controller: &mut LightingController<'a, N_ANI>,
+    ) -> bool {
+        let Some(index) = self.rule_index_at(clock.time_of_day()) else {
+            return false;
+        };
+        if self.active == Some(index) {
+            return false;
+        }
+        self.active = Some(index);
+        for &action in self.rules[index].actions {
+            sequencer::apply(action, controller);
+        }
+        true
+    }
+
+    /// Returns the rule that was applied last.
+    pub fn active_rule(&self) -> Option<&Rule<'a>> {
+        self.rules.get(self.active?)
+    }
+
+    /// Makes the next `update()` apply the current rule again, such as after the lights were
+    /// changed by hand and should go back to the schedule.
+    pub fn reapply(&mut self) {
+        self.active = None;
+    }
+
+    /// Returns the index of the rule in effect at a time of day: the last one that started at or
+    /// before it, or the last rule of the day before if no rule has started yet today.
+    fn rule_index_at(&self, time: TimeOfDay) -> Option<usize> {
+        let started = self.rules.iter().filter(|rule| rule.start <= time).count();
+        match started {
+            0 => self.rules.len().checked_sub(1),
+            count => Some(count - 1),
+        }
+    }
+}
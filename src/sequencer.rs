@@ -0,0 +1,156 @@
+//! Choreographed shows for standalone installations. A Sequencer plays a list of cues, each of
+//! which changes the LightingController at a set time from the start of the show, such as
+//! switching to a new effect at 0s, firing a trigger at 2.5s and blacking out at 10s. The cue list
+//! can be a const array, so the whole show lives in flash and runs without a host.
+//!
+//! The sequencer keeps time by the frames of the controller: call `tick()` before every
+//! `update()`, or `advance()` with the same elapsed time as `update_with_elapsed()`.
+
+use crate::animations::AnimationParameters;
+use crate::commands::Command;
+use crate::LightingController;
+use embedded_time::duration::Nanoseconds;
+use embedded_time::fixed_point::FixedPoint;
+
+const NS_PER_MS: u64 = 1_000_000;
+
+/// A change made to the controller by a cue.
+#[derive(Copy, Clone)]
+pub enum Action<'a> {
+    /// Applies a command, such as a trigger, a brightness change or new parameters, the same way
+    /// as `LightingController::apply_command()`.
+    Command(Command<'a>),
+
+    /// Fades an animation over to new parameters, see `LightingController::transition_to()`.
+    TransitionTo {
+        animation_index: usize,
+        parameters: &'a AnimationParameters<'a>,
+        duration_ms: u64,
+    },
+
+    /// Fades the whole output to off, see `LightingController::blackout()`.
+    Blackout { duration_ms: u64 },
+
+    /// Fades the output back up after a blackout, see `LightingController::restore()`.
+    Restore { duration_ms: u64 },
+}
+
+/// An action and the time from the start of the show that it happens at.
+#[derive(Copy, Clone)]
+pub struct Cue<'a> {
+    pub at_ms: u64,
+    pub action: Action<'a>,
+}
+
+impl<'a> Cue<'a> {
+    pub const fn new(at_ms: u64, action: Action<'a>) -> Self {
+        Cue { at_ms, action }
+    }
+}
+
+/// Plays a list of cues in order. The cues have to be sorted by their times, and cues at the same
+/// time are applied in the order they are listed.
+pub struct Sequencer<'s, 'a> {
+    cues: &'s [Cue<'a>],
+    next: usize,
+    elapsed_ns: u64,
+    loop_ns: Option<u64>,
+}
+
+impl<'s, 'a> Sequencer<'s, 'a> {
+    pub const fn new(cues: &'s [Cue<'a>]) -> Self {
+        Sequencer {
+            cues,
+            next: 0,
+            elapsed_ns: 0,
+            loop_ns: None,
+        }
+    }
+
+    /// Starts the show over from the beginning every `length_ms`, so it runs for as long as the
+    /// installation is on. Cues after the end of the loop are never applied. A length of 0 plays
+    /// the show once.
+    pub const fn with_loop(mut self, length_ms: u64) -> Self {
+        self.loop_ns = match length_ms {
+            0 => None,
+            _ => Some(length_ms * NS_PER_MS),
+        };
+        self
+    }
+
+    /// Moves the show forward by one frame of the controller, applying the cues that are due.
+    pub fn tick<const N_ANI: usize>(&mut self, controller: &mut LightingController<'a, N_ANI>) {
+        let frame_ns = 1_000_000_000 / controller.frame_rate().integer().max(1) as u64;
+        self.advance(Nanoseconds(frame_ns), controller);
+    }
+
+    /// Moves the show forward by the elapsed time, applying every cue that is due in the order
+    /// they are listed, even if several came due since the last call.
+    pub fn advance<const N_ANI: usize>(
+        &mut self,
+        elapsed: Nanoseconds<u64>,
+        controller: &mut LightingController<'a, N_ANI>,
+    ) {
+        self.elapsed_ns += elapsed.integer();
+        loop {
+            self.apply_due_cues(controller);
+            match self.loop_ns {
+                Some(loop_ns) if self.elapsed_ns >= loop_ns => {
+                    self.elapsed_ns -= loop_ns;
+                    self.next = 0;
+                }
+                _ => return,
+            }
+        }
+    }
+
+    fn apply_due_cues<const N_ANI: usize>(
+        &mut self,
+        controller: &mut LightingController<'a, N_ANI>,
+    ) {
+        let end_ns = match self.loop_ns {
+            Some(loop_ns) => self.elapsed_ns.min(loop_ns - 1),
+            None => self.elapsed_ns,
+        };
+        while let Some(cue) = self.cues.get(self.next) {
+            if cue.at_ms * NS_PER_MS > end_ns {
+                break;
+            }
+            apply(cue.action, controller);
+            self.next += 1;
+        }
+    }
+
+    /// Goes back to the start of the show. Changes already made to the controller are kept.
+    pub fn restart(&mut self) {
+        self.next = 0;
+        self.elapsed_ns = 0;
+    }
+
+    /// Returns the time since the start of the show, or of the current loop.
+    pub fn elapsed_ms(&self) -> u64 {
+        self.elapsed_ns / NS_PER_MS
+    }
+
+    /// Returns whether every cue has been applied. A looping show is never finished.
+    pub fn is_finished(&self) -> bool {
+        self.loop_ns.is_none() && self.next >= self.cues.len()
+    }
+}
+
+/// Makes the change of an action to the controller.
+pub(crate) fn apply<'a, const N_ANI: usize>(
+    action: Action<'a>,
+    controller: &mut LightingController<'a, N_ANI>,
+) {
+    match action {
+        Action::Command(command) => controller.apply_command(command),
+        Action::TransitionTo {
+            animation_index,
+            parameters,
+            duration_ms,
+        } => controller.transition_to(animation_index, parameters, duration_ms),
+        Action::Blackout { duration_ms } => controller.blackout(duration_ms),
+        Action::Restore { duration_ms } => controller.restore(duration_ms),
+    }
+}
@@ -0,0 +1,144 @@
+//! A desktop preview of the LEDs, for developing and checking animations before flashing them to
+//! hardware. Frames can be drawn in a terminal that supports 24 bit ANSI colors, or saved as PNG
+//! images. Matrices are drawn as grids, and everything else as a single row of LEDs.
+
+use crate::mapping::Matrix;
+use rgb::RGB8;
+use std::io::{self, Write};
+use std::vec::Vec;
+
+/// Moves the cursor back to the top left of the terminal and clears the screen.
+pub const CLEAR_SCREEN: &str = "\x1b[H\x1b[2J";
+
+const RESET_COLORS: &str = "\x1b[0m";
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+const MAX_STORED_BLOCK_SIZE: usize = u16::MAX as usize;
+
+/// The width and height of the image to draw a frame as. Frames without a matrix are drawn as a
+/// single row.
+fn dimensions(frame: &[RGB8], matrix: Option<Matrix>) -> (usize, usize) {
+    match matrix {
+        Some(matrix) => (matrix.width, matrix.height),
+        None => (frame.len(), 1),
+    }
+}
+
+/// Returns the color of the LED at (x, y), where frames from a matrix animation are in row-major
+/// order. LEDs past the end of the frame are black.
+fn color_at(frame: &[RGB8], width: usize, x: usize, y: usize) -> RGB8 {
+    frame.get(x + y * width).copied().unwrap_or_default()
+}
+
+/// Draws a frame in the terminal using 24 bit ANSI colors. Each LED is two characters wide, and
+/// each line of text holds two rows of a matrix, so the LEDs come out roughly square. Write
+/// CLEAR_SCREEN first to draw each frame over the last one.
+pub fn write_ansi(out: &mut impl Write, frame: &[RGB8], matrix: Option<Matrix>) -> io::Result<()> {
+    let (width, height) = dimensions(frame, matrix);
+    for y in (0..height).step_by(2) {
+        for x in 0..width {
+            let top = color_at(frame, width, x, y);
+            if y + 1 < height {
+                // the upper half block shows the top row in the text color, over the bottom row
+                // in the background color:
+                let bottom = color_at(frame, width, x, y + 1);
+                write!(
+                    out,
+                    "\x1b[38;2;{};{};{}m\x1b[48;2;{};{};{}m\u{2580}\u{2580}",
+                    top.r, top.g, top.b, bottom.r, bottom.g, bottom.b
+                )?;
+            } else {
+                write!(
+                    out,
+                    "\x1b[38;2;{};{};{}m\u{2588}\u{2588}",
+                    top.r, top.g, top.b
+                )?;
+            }
+        }
+        writeln!(out, "{}", RESET_COLORS)?;
+    }
+    out.flush()
+}
+
+/// Writes a frame as a PNG image, with each LED drawn as a square of `led_size` pixels.
+pub fn write_png(
+    out: &mut impl Write,
+    frame: &[RGB8],
+    matrix: Option<Matrix>,
+    led_size: usize,
+) -> io::Result<()> {
+    let (width, height) = dimensions(frame, matrix);
+    let led_size = led_size.max(1);
+    let image_width = width * led_size;
+    let image_height = height * led_size;
+
+    // each row of the image starts with its filter type, which is always 0 for no filtering:
+    let mut pixels = Vec::with_capacity((image_width * 3 + 1) * image_height);
+    for y in 0..image_height {
+        pixels.push(0);
+        for x in 0..image_width {
+            let color = color_at(frame, width, x / led_size, y / led_size);
+            pixels.extend_from_slice(&[color.r, color.g, color.b]);
+        }
+    }
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&(image_width as u32).to_be_bytes());
+    header.extend_from_slice(&(image_height as u32).to_be_bytes());
+    // 8 bits per channel, RGB color, and the default compression, filter and interlace methods:
+    header.extend_from_slice(&[8, 2, 0, 0, 0]);
+
+    out.write_all(&PNG_SIGNATURE)?;
+    write_png_chunk(out, b"IHDR", &header)?;
+    write_png_chunk(out, b"IDAT", &zlib_stored(&pixels))?;
+    write_png_chunk(out, b"IEND", &[])?;
+    out.flush()
+}
+
+fn write_png_chunk(out: &mut impl Write, chunk_type: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    out.write_all(&(data.len() as u32).to_be_bytes())?;
+    out.write_all(chunk_type)?;
+    out.write_all(data)?;
+    let crc = crc32(crc32(!0, chunk_type), data);
+    out.write_all(&(!crc).to_be_bytes())
+}
+
+/// Wraps the data in a zlib stream without compressing it. Preview frames are small, so this
+/// keeps the simulator free of a compression dependency.
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let block_count = data.len().div_ceil(MAX_STORED_BLOCK_SIZE).max(1);
+    let mut stream = Vec::with_capacity(data.len() + block_count * 5 + 6);
+    // deflate with a 32K window and no preset dictionary:
+    stream.extend_from_slice(&[0x78, 0x01]);
+    let mut blocks = data.chunks(MAX_STORED_BLOCK_SIZE).peekable();
+    if blocks.peek().is_none() {
+        stream.extend_from_slice(&[1, 0x00, 0x00, 0xff, 0xff]);
+    }
+    while let Some(block) = blocks.next() {
+        let is_final = blocks.peek().is_none();
+        let length = block.len() as u16;
+        stream.push(is_final as u8);
+        stream.extend_from_slice(&length.to_le_bytes());
+        stream.extend_from_slice(&(!length).to_le_bytes());
+        stream.extend_from_slice(block);
+    }
+    stream.extend_from_slice(&adler32(data).to_be_bytes());
+    stream
+}
+
+fn crc32(crc: u32, bytes: &[u8]) -> u32 {
+    bytes.iter().fold(crc, |crc, byte| {
+        (0..8).fold(crc ^ *byte as u32, |crc, _| match crc & 1 {
+            0 => crc >> 1,
+            _ => (crc >> 1) ^ 0xedb8_8320,
+        })
+    })
+}
+
+fn adler32(bytes: &[u8]) -> u32 {
+    const MODULUS: u32 = 65521;
+    let (a, b) = bytes.iter().fold((1, 0), |(a, b), byte| {
+        let a = (a + *byte as u32) % MODULUS;
+        (a, (b + a) % MODULUS)
+    });
+    (b << 16) | a
+}
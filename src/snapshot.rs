@@ -0,0 +1,100 @@
+//! Support for snapshot tests of animations. A Recorder runs an animation for a number of frames
+//! from a fixed random seed, so random effects such as sparkles repeat exactly, and reduces every
+//! frame to a small hash. Comparing the hashes against ones saved from a known good version shows
+//! when a change alters the output of a mode, and `write_frame()` dumps the colors of a frame to
+//! see what changed.
+//!
+//! The random number generator is shared by the whole crate, so tests that record snapshots
+//! shouldn't run in parallel with anything else that uses it.
+
+use crate::animations::Animatable;
+use crate::utility::seed_random;
+use core::fmt;
+use embedded_time::duration::Nanoseconds;
+use rgb::RGB8;
+
+/// The random seed used by a Recorder unless another is set.
+pub const DEFAULT_SEED: u32 = 0x5eed_1234;
+
+const FNV_OFFSET_BASIS: u32 = 0x811c_9dc5;
+const FNV_PRIME: u32 = 0x0100_0193;
+
+/// Returns a 32 bit FNV-1a hash of the colors of a frame.
+pub fn hash_frame(frame: &[RGB8]) -> u32 {
+    frame
+        .iter()
+        .flat_map(|color| [color.r, color.g, color.b])
+        .fold(FNV_OFFSET_BASIS, |hash, byte| {
+            (hash ^ byte as u32).wrapping_mul(FNV_PRIME)
+        })
+}
+
+/// Writes the colors of a frame as a line of hex values, such as `ff0000 00ff00 0000ff`.
+pub fn write_frame(out: &mut impl fmt::Write, frame: &[RGB8]) -> fmt::Result {
+    for (index, color) in frame.iter().enumerate() {
+        if index > 0 {
+            out.write_char(' ')?;
+        }
+        write!(out, "{:02x}{:02x}{:02x}", color.r, color.g, color.b)?;
+    }
+    out.write_char('\n')
+}
+
+/// Runs an animation frame by frame and records a hash of each frame.
+pub struct Recorder {
+    seed: u32,
+    elapsed: Option<Nanoseconds<u64>>,
+}
+
+impl Recorder {
+    pub const fn new() -> Self {
+        Recorder {
+            seed: DEFAULT_SEED,
+            elapsed: None,
+        }
+    }
+
+    /// Sets the random seed used at the start of each recording.
+    pub fn with_seed(mut self, seed: u32) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Runs the animation with `update_with_elapsed()` and a fixed frame time, instead of one
+    /// `update()` per frame.
+    pub fn with_elapsed(mut self, elapsed: Nanoseconds<u64>) -> Self {
+        self.elapsed = Some(elapsed);
+        self
+    }
+
+    /// Updates the animation once for each hash, storing the hash of the segment after each
+    /// update.
+    pub fn record<'a, A: Animatable<'a> + ?Sized>(&self, animation: &mut A, hashes: &mut [u32]) {
+        self.record_with(animation, hashes, |_, _| {});
+    }
+
+    /// The same as `record()`, but calls `before_frame` with the frame number before each update,
+    /// so tests can fire triggers or change the input level at fixed frames.
+    pub fn record_with<'a, A: Animatable<'a> + ?Sized>(
+        &self,
+        animation: &mut A,
+        hashes: &mut [u32],
+        mut before_frame: impl FnMut(usize, &mut A),
+    ) {
+        seed_random(self.seed);
+        for (frame, hash) in hashes.iter_mut().enumerate() {
+            before_frame(frame, animation);
+            let _ = match self.elapsed {
+                Some(elapsed) => animation.update_with_elapsed(elapsed),
+                None => animation.update(),
+            };
+            *hash = hash_frame(animation.segment());
+        }
+    }
+}
+
+impl Default for Recorder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,114 @@
+//! Splitting the LEDs of the controller across several physical outputs, such as a 200 LED strip
+//! that is wired as two runs of 100 LEDs on separate pins, or a few strips of different chips
+//! driven from one controller. The animations are still laid out on a single logical strip as
+//! usual, and each OutputChannel takes a range of it: the first channel might show LEDs 0 to 99
+//! and the second LEDs 100 to 199.
+//!
+//! Each channel has its own buffer, channel order and translation, so a run that is wired
+//! backwards or uses GRB chips next to RGB ones is fixed up per channel. Write the frame with
+//! `LightingController::update_split()`, then send each channel's buffer out on its own pin.
+
+use crate::output::{ChannelOrder, OutputColor};
+use rgb::RGB8;
+
+/// One physical output, showing the LEDs of the logical strip from `start` onwards.
+pub struct OutputChannel<'a, C: OutputColor = RGB8> {
+    buffer: &'a mut [C],
+    start: usize,
+    translation: Option<&'a [usize]>,
+    channel_order: ChannelOrder,
+}
+
+impl<'a, C: OutputColor> OutputChannel<'a, C> {
+    /// Makes a channel that shows as many LEDs as `buffer` holds, starting from the LED at
+    /// `start` on the logical strip.
+    pub fn new(buffer: &'a mut [C], start: usize) -> Self {
+        OutputChannel {
+            buffer,
+            start,
+            translation: None,
+            channel_order: ChannelOrder::Rgb,
+        }
+    }
+
+    /// Writes LED `i` of the channel to `buffer[translation[i]]` instead of `buffer[i]`, the same
+    /// way the translation array of an animation places its LEDs. LEDs past the end of the
+    /// translation, or translated past the end of the buffer, are left alone.
+    pub fn with_translation(mut self, translation: &'a [usize]) -> Self {
+        self.translation = Some(translation);
+        self
+    }
+
+    /// Sets the order the LEDs of this channel expect their channels in, which is used in place
+    /// of the controller's channel order.
+    pub fn with_channel_order(mut self, channel_order: ChannelOrder) -> Self {
+        self.channel_order = channel_order;
+        self
+    }
+
+    /// Returns the first LED of the logical strip that the channel shows.
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    pub fn len(&self) -> usize {
+        self.buffer.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buffer.is_empty()
+    }
+
+    /// Returns the colors to send out on the channel.
+    pub fn as_slice(&self) -> &[C] {
+        self.buffer
+    }
+
+    /// Writes a color in RGB order to LED `led` of the channel, and returns whether it changed.
+    pub(crate) fn write(&mut self, led: usize, color: C) -> bool {
+        let index = match self.translation {
+            Some(translation) => match translation.get(led) {
+                Some(&index) => index,
+                None => return false,
+            },
+            None => led,
+        };
+        let color = color.with_channel_order(self.channel_order);
+        match self.buffer.get_mut(index) {
+            Some(slot) => core::mem::replace(slot, color) != color,
+            None => false,
+        }
+    }
+}
+
+/// The outputs that a logical strip is split across.
+pub struct SplitStrip<'a, const N_CHANNELS: usize, C: OutputColor = RGB8> {
+    channels: [OutputChannel<'a, C>; N_CHANNELS],
+}
+
+impl<'a, const N_CHANNELS: usize, C: OutputColor> SplitStrip<'a, N_CHANNELS, C> {
+    pub fn new(channels: [OutputChannel<'a, C>; N_CHANNELS]) -> Self {
+        SplitStrip { channels }
+    }
+
+    pub fn channel(&self, index: usize) -> &OutputChannel<'a, C> {
+        &self.channels[index]
+    }
+
+    pub fn channels(&self) -> &[OutputChannel<'a, C>] {
+        &self.channels
+    }
+
+    pub(crate) fn channels_mut(&mut self) -> &mut [OutputChannel<'a, C>] {
+        &mut self.channels
+    }
+
+    /// Returns the number of LEDs on the logical strip, up to the end of the last channel.
+    pub fn led_count(&self) -> usize {
+        self.channels
+            .iter()
+            .map(|channel| channel.start + channel.len())
+            .max()
+            .unwrap_or(0)
+    }
+}
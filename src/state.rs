@@ -0,0 +1,235 @@
+//! Saving the state of a LightingController to bytes and restoring it, so that the lights pick up
+//! where they left off after a reboot or a brownout. Only the state that is set from outside is
+//! saved: the preset applied to each animation, the brightnesses, the offsets and the positions
+//! in the rainbows. The frames of the effects themselves start over.
+//!
+//! The layout is fixed for a number of animations, so the firmware can set aside a block of
+//! EEPROM or flash of `state_len(N_ANI)` bytes for it:
+//!
+//! | bytes | contents                                         |
+//! |-------|--------------------------------------------------|
+//! | 1     | `STATE_VERSION`                                  |
+//! | 1     | the number of animations                         |
+//! | 1     | the brightness of the controller                 |
+//! | 13    | the state of each animation, see below           |
+//! | 1     | a checksum of everything before it               |
+//!
+//! The state of an animation is a byte that is 1 if a preset was applied and 0 if not, the ID of
+//! the preset, the brightness of the animation, the brightnesses of its background and
+//! foreground, then the background offset, the foreground offset, the background rainbow position
+//! and the foreground rainbow position as little endian u16s.
+
+/// The version of the layout, which is bumped whenever it changes so that old saves are rejected
+/// instead of being misread.
+pub const STATE_VERSION: u8 = 1;
+
+const HEADER_LEN: usize = 3;
+const ANIMATION_STATE_LEN: usize = 13;
+const CHECKSUM_LEN: usize = 1;
+
+/// Returns the number of bytes the state of a controller with `animation_count` animations takes.
+pub const fn state_len(animation_count: usize) -> usize {
+    HEADER_LEN + animation_count * ANIMATION_STATE_LEN + CHECKSUM_LEN
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum StateError {
+    /// The buffer is shorter than `state_len()` for the number of animations.
+    BufferTooSmall,
+    /// The state was saved with another version of the layout.
+    UnknownVersion(u8),
+    /// The state was saved by a controller with a different number of animations.
+    AnimationCountMismatch(u8),
+    /// The checksum doesn't match, such as when the write was cut off by a power loss.
+    BadChecksum,
+}
+
+/// The saved state of a single animation.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct AnimationState {
+    pub brightness: u8,
+    pub bg_brightness: u8,
+    pub fg_brightness: u8,
+    pub bg_offset: u16,
+    pub fg_offset: u16,
+    /// The index of the current color in the background rainbow.
+    pub bg_rainbow_position: u16,
+    /// The index of the current color in the foreground rainbow.
+    pub fg_rainbow_position: u16,
+}
+
+/// The saved state of a whole controller.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ControllerState<const N_ANI: usize> {
+    pub brightness: u8,
+    /// The ID of the preset last applied to each animation, if any.
+    pub preset_ids: [Option<u8>; N_ANI],
+    pub animations: [AnimationState; N_ANI],
+}
+
+impl<const N_ANI: usize> ControllerState<N_ANI> {
+    /// Writes the state to the start of a buffer. Returns the number of bytes written.
+    pub fn write(&self, out: &mut [u8]) -> Result<usize, StateError> {
+        let len = state_len(N_ANI);
+        let out = out.get_mut(..len).ok_or(StateError::BufferTooSmall)?;
+        out[0] = STATE_VERSION;
+        out[1] = N_ANI as u8;
+        out[2] = self.brightness;
+        let animation_bytes = out[HEADER_LEN..].chunks_exact_mut(ANIMATION_STATE_LEN);
+        for ((bytes, state), preset_id) in animation_bytes
+            .zip(self.animations.iter())
+            .zip(self.preset_ids.iter())
+        {
+            bytes[0] = preset_id.is_some() as u8;
+            bytes[1] = preset_id.unwrap_or(0);
+            bytes[2] = state.brightness;
+            bytes[3] = state.bg_brightness;
+            bytes[4] = state.fg_brightness;
+            bytes[5..7].copy_from_slice(&state.bg_offset.to_le_bytes());
+            bytes[7..9].copy_from_slice(&state.fg_offset.to_le_bytes());
+            bytes[9..11].copy_from_slice(&state.bg_rainbow_position.to_le_bytes());
+            bytes[11..13].copy_from_slice(&state.fg_rainbow_position.to_le_bytes());
+        }
+        out[len - 1] = checksum(&out[..len - 1]);
+        Ok(len)
+    }
+
+    /// Reads a state written by `write()`, checking its version, size and checksum.
+    pub fn read(data: &[u8]) -> Result<Self, StateError> {
+        let len = state_len(N_ANI);
+        let data = data.get(..len).ok_or(StateError::BufferTooSmall)?;
+        if data[0] != STATE_VERSION {
+            return Err(StateError::UnknownVersion(data[0]));
+        }
+        if data[1] as usize != N_ANI {
+            return Err(StateError::AnimationCountMismatch(data[1]));
+        }
+        if checksum(&data[..len - 1]) != data[len - 1] {
+            return Err(StateError::BadChecksum);
+        }
+
+        let mut state = ControllerState {
+            brightness: data[2],
+            preset_ids: [None; N_ANI],
+            animations: [AnimationState::default(); N_ANI],
+        };
+        let animation_bytes = data[HEADER_LEN..].chunks_exact(ANIMATION_STATE_LEN);
+        for ((bytes, animation), preset_id) in animation_bytes
+            .zip(state.animations.iter_mut())
+            .zip(state.preset_ids.iter_mut())
+        {
+            let read_u16 = |at: usize| u16::from_le_bytes([bytes[at], bytes[at + 1]]);
+            *preset_id = (bytes[0] != 0).then_some(bytes[1]);
+            *animation = AnimationState {
+                brightness: bytes[2],
+                bg_brightness: bytes[3],
+                fg_brightness: bytes[4],
+                bg_offset: read_u16(5),
+                fg_offset: read_u16(7),
+                bg_rainbow_position: read_u16(9),
+                fg_rainbow_position: read_u16(11),
+            };
+        }
+        Ok(state)
+    }
+}
+
+/// A rotating sum of the bytes, which catches the zeroed or erased tails left by interrupted
+/// writes as well as flipped bits.
+fn checksum(bytes: &[u8]) -> u8 {
+    bytes
+        .iter()
+        .fold(0xa5, |sum: u8, &byte| sum.rotate_left(1) ^ byte)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STATE: ControllerState<2> = ControllerState {
+        brightness: 200,
+        preset_ids: [Some(3), None],
+        animations: [
+            AnimationState {
+                brightness: 150,
+                bg_brightness: 100,
+                fg_brightness: 50,
+                bg_offset: 0x1234,
+                fg_offset: 0xfedc,
+                bg_rainbow_position: 2,
+                fg_rainbow_position: 300,
+            },
+            AnimationState {
+                brightness: 1,
+                bg_brightness: 2,
+                fg_brightness: 3,
+                bg_offset: 4,
+                fg_offset: 5,
+                bg_rainbow_position: 6,
+                fg_rainbow_position: 7,
+            },
+        ],
+    };
+
+    #[test]
+    fn round_trips_through_bytes() {
+        let mut bytes = [0; state_len(2) + 4];
+        assert_eq!(STATE.write(&mut bytes), Ok(state_len(2)));
+        assert_eq!(ControllerState::<2>::read(&bytes), Ok(STATE));
+    }
+
+    #[test]
+    fn rejects_short_buffers() {
+        let mut bytes = [0; state_len(2)];
+        assert_eq!(
+            STATE.write(&mut bytes[..state_len(2) - 1]),
+            Err(StateError::BufferTooSmall)
+        );
+        STATE.write(&mut bytes).unwrap();
+        assert_eq!(
+            ControllerState::<2>::read(&bytes[..state_len(2) - 1]),
+            Err(StateError::BufferTooSmall)
+        );
+    }
+
+    #[test]
+    fn rejects_other_layouts() {
+        let mut bytes = [0; state_len(2)];
+        STATE.write(&mut bytes).unwrap();
+
+        let mut other_version = bytes;
+        other_version[0] = STATE_VERSION + 1;
+        assert_eq!(
+            ControllerState::<2>::read(&other_version),
+            Err(StateError::UnknownVersion(STATE_VERSION + 1))
+        );
+        assert_eq!(
+            ControllerState::<1>::read(&bytes),
+            Err(StateError::AnimationCountMismatch(2))
+        );
+    }
+
+    #[test]
+    fn rejects_damaged_states() {
+        let mut bytes = [0; state_len(2)];
+        STATE.write(&mut bytes).unwrap();
+
+        let mut flipped = bytes;
+        flipped[7] ^= 0x10;
+        assert_eq!(
+            ControllerState::<2>::read(&flipped),
+            Err(StateError::BadChecksum)
+        );
+
+        // a write cut off by a power loss leaves the erased value of the flash behind:
+        let mut cut_off = bytes;
+        cut_off[HEADER_LEN + 5..].fill(0xff);
+        assert_eq!(
+            ControllerState::<2>::read(&cut_off),
+            Err(StateError::BadChecksum)
+        );
+    }
+}
@@ -0,0 +1,301 @@
+//! Supersampling for short strips. On a strip of a few dozen LEDs, a slow foreground or trigger
+//! moves one whole LED at a time, so it steps along instead of gliding. A Supersampled animation
+//! draws an ordinary animation at FACTOR times as many virtual LEDs, and averages each run of
+//! FACTOR virtual LEDs down to one physical LED, so that something between two LEDs lights both
+//! of them partly and slow movements fade smoothly from one LED to the next.
+//!
+//! The inner animation is made with `N_LED * FACTOR` LEDs and wrapped, and the wrapper is handed
+//! to the LightingController in its place:
+//!
+//! - `let mut virtual_leds = Animation::<240>::new(parameters, frame_rate);`
+//! - `let mut animation = Supersampled::<60, 4>::new(&mut virtual_leds);`
+//!
+//! Everything the wrapper passes on to the inner animation works on the virtual LEDs, such as the
+//! indices of masks and key colors and the targets of triggers. The translation array and tiling
+//! of the inner animation are not used; the wrapper has its own for the physical LEDs.
+
+use crate::animations::{
+    background, foreground, transition, trigger, Animatable, AnimationParameters, AnimationType,
+    Direction, Event, LayerObserver, Tiling,
+};
+#[cfg(feature = "instrumentation")]
+use crate::instrumentation::{CycleCounter, LayerCosts};
+use crate::state::AnimationState;
+use crate::utility::default_translation_array;
+use embedded_time::duration::Nanoseconds;
+use embedded_time::rate::Hertz;
+#[cfg(feature = "color16")]
+use rgb::RGB16;
+use rgb::RGB8;
+
+/// An animation drawn at FACTOR times the resolution of its N_LED physical LEDs, see the module
+/// documentation.
+pub struct Supersampled<'a, const N_LED: usize, const FACTOR: usize> {
+    animation: &'a mut dyn Animatable<'a>,
+    segment: [RGB8; N_LED],
+    #[cfg(feature = "color16")]
+    segment16: [RGB16; N_LED],
+    translation_array: [usize; N_LED],
+    tiling: Tiling,
+}
+
+impl<'a, const N_LED: usize, const FACTOR: usize> Supersampled<'a, N_LED, FACTOR> {
+    /// Wraps an animation of `N_LED * FACTOR` virtual LEDs. Physical LEDs past the end of a
+    /// shorter animation stay black.
+    pub fn new(animation: &'a mut dyn Animatable<'a>) -> Self {
+        let mut supersampled = Supersampled {
+            animation,
+            segment: [RGB8::default(); N_LED],
+            #[cfg(feature = "color16")]
+            segment16: [RGB16::default(); N_LED],
+            translation_array: default_translation_array(0),
+            tiling: Tiling::Single,
+        };
+        supersampled.downsample();
+        supersampled
+    }
+
+    /// Replaces the default translation array of the physical LEDs, the same as
+    /// `Animation::set_translation_array()`.
+    pub fn set_translation_array(mut self, translation_array: [usize; N_LED]) -> Self {
+        self.translation_array = translation_array;
+        self
+    }
+
+    /// Draws copies of the physical LEDs along the strip, see `Animation::set_tiling()`.
+    pub fn set_tiling(mut self, tiling: Tiling) -> Self {
+        self.tiling = tiling;
+        self
+    }
+
+    /// Averages each run of FACTOR virtual LEDs into its physical LED with a box filter. Returns
+    /// whether any physical LED changed.
+    fn downsample(&mut self) -> bool {
+        let factor = FACTOR.max(1);
+        let previous_segment = self.segment;
+        let runs = self.animation.segment().chunks(factor);
+        for (led, run) in self.segment.iter_mut().zip(runs) {
+            let average = |channel: fn(&RGB8) -> u8| {
+                let sum: u32 = run.iter().map(|color| channel(color) as u32).sum();
+                ((sum + run.len() as u32 / 2) / run.len() as u32) as u8
+            };
+            *led = RGB8::new(average(|c| c.r), average(|c| c.g), average(|c| c.b));
+        }
+        #[cfg(feature = "color16")]
+        {
+            // the averages keep the fractions of the 8 bit levels that 16 bit outputs can show:
+            let runs = self.animation.segment16().chunks(factor);
+            for (led, run) in self.segment16.iter_mut().zip(runs) {
+                let average = |channel: fn(&RGB16) -> u16| {
+                    let sum: u32 = run.iter().map(|color| channel(color) as u32).sum();
+                    ((sum + run.len() as u32 / 2) / run.len() as u32) as u16
+                };
+                *led = RGB16::new(average(|c| c.r), average(|c| c.g), average(|c| c.b));
+            }
+        }
+        self.segment != previous_segment
+    }
+}
+
+impl<'a, const N_LED: usize, const FACTOR: usize> Animatable<'a>
+    for Supersampled<'a, N_LED, FACTOR>
+{
+    fn update(&mut self) -> bool {
+        self.animation.update() && self.downsample()
+    }
+
+    fn update_with_elapsed(&mut self, elapsed: Nanoseconds<u64>) -> bool {
+        self.animation.update_with_elapsed(elapsed) && self.downsample()
+    }
+
+    fn pause(&mut self) {
+        self.animation.pause();
+    }
+
+    fn resume(&mut self) {
+        self.animation.resume();
+    }
+
+    fn is_paused(&self) -> bool {
+        self.animation.is_paused()
+    }
+
+    fn step_one_frame(&mut self) -> bool {
+        self.animation.step_one_frame() && self.downsample()
+    }
+
+    fn set_frame_rate(&mut self, frame_rate: Hertz) {
+        self.animation.set_frame_rate(frame_rate);
+    }
+
+    fn set_offset(&mut self, a_type: AnimationType, offset: u16) {
+        self.animation.set_offset(a_type, offset);
+    }
+
+    fn set_direction(&mut self, a_type: AnimationType, direction: Direction) {
+        self.animation.set_direction(a_type, direction);
+    }
+
+    fn set_brightness(&mut self, brightness: u8) {
+        self.animation.set_brightness(brightness);
+    }
+
+    fn set_persistence(&mut self, persistence: u8) {
+        self.animation.set_persistence(persistence);
+    }
+
+    fn set_input_level(&mut self, level: u8) {
+        self.animation.set_input_level(level);
+    }
+
+    fn set_progress(&mut self, progress: u16) {
+        self.animation.set_progress(progress);
+    }
+
+    fn set_layer_brightness(&mut self, a_type: AnimationType, brightness: u8) {
+        self.animation.set_layer_brightness(a_type, brightness);
+    }
+
+    fn set_speed(&mut self, a_type: AnimationType, numerator: u32, denominator: u32) {
+        self.animation.set_speed(a_type, numerator, denominator);
+    }
+
+    fn restart_cycle(&mut self, a_type: AnimationType) {
+        self.animation.restart_cycle(a_type);
+    }
+
+    fn restart_run(&mut self, a_type: AnimationType) {
+        self.animation.restart_run(a_type);
+    }
+
+    fn is_run_complete(&self, a_type: AnimationType) -> bool {
+        self.animation.is_run_complete(a_type)
+    }
+
+    fn set_mask(&mut self, mask: &[u8]) {
+        self.animation.set_mask(mask);
+    }
+
+    fn set_mask_level(&mut self, index: usize, level: u8) {
+        self.animation.set_mask_level(index, level);
+    }
+
+    fn clear_mask(&mut self) {
+        self.animation.clear_mask();
+    }
+
+    fn set_key_color(&mut self, key: usize, color: RGB8) {
+        self.animation.set_key_color(key, color);
+    }
+
+    fn set_key_colors(&mut self, keys: &[(usize, RGB8)]) {
+        self.animation.set_key_colors(keys);
+    }
+
+    fn clear_key_color(&mut self, key: usize) {
+        self.animation.clear_key_color(key);
+    }
+
+    fn clear_key_colors(&mut self) {
+        self.animation.clear_key_colors();
+    }
+
+    fn trigger(&mut self, params: &trigger::Parameters, frame_rate: Hertz) {
+        self.animation.trigger(params, frame_rate);
+    }
+
+    fn try_trigger(
+        &mut self,
+        params: &trigger::Parameters,
+        frame_rate: Hertz,
+    ) -> Result<(), trigger::TriggerError> {
+        self.animation.try_trigger(params, frame_rate)
+    }
+
+    fn trigger_sequence(&mut self, steps: &[trigger::SequenceStep], frame_rate: Hertz) {
+        self.animation.trigger_sequence(steps, frame_rate);
+    }
+
+    fn clear_triggers(&mut self) {
+        self.animation.clear_triggers();
+    }
+
+    fn cancel_triggers_of_mode(&mut self, mode: trigger::Mode) {
+        self.animation.cancel_triggers_of_mode(mode);
+    }
+
+    fn active_trigger_count(&self) -> usize {
+        self.animation.active_trigger_count()
+    }
+
+    fn segment(&self) -> &[RGB8] {
+        &self.segment[..]
+    }
+
+    #[cfg(feature = "color16")]
+    fn segment16(&self) -> &[RGB16] {
+        // without 16 bit colors to average, the 8 bit segment is widened by the controller:
+        match self.animation.segment16().is_empty() {
+            true => &[],
+            false => &self.segment16[..],
+        }
+    }
+
+    fn translation_array(&self) -> &[usize] {
+        &self.translation_array[..]
+    }
+
+    fn tiling(&self) -> Tiling {
+        self.tiling
+    }
+
+    fn set_parameters(&mut self, parameters: &AnimationParameters<'a>) {
+        self.animation.set_parameters(parameters);
+    }
+
+    fn update_bg_parameters(&mut self, parameters: &background::Parameters<'a>) {
+        self.animation.update_bg_parameters(parameters);
+    }
+
+    fn update_fg_parameters(&mut self, parameters: &foreground::Parameters<'a>) {
+        self.animation.update_fg_parameters(parameters);
+    }
+
+    fn update_trigger_parameters(&mut self, parameters: &trigger::GlobalParameters<'a>) {
+        self.animation.update_trigger_parameters(parameters);
+    }
+
+    fn transition_to(&mut self, parameters: &AnimationParameters<'a>, duration_ms: u64) {
+        self.animation.transition_to(parameters, duration_ms);
+    }
+
+    fn set_transition_style(&mut self, style: transition::Style) {
+        self.animation.set_transition_style(style);
+    }
+
+    fn poll_event(&mut self) -> Option<Event> {
+        self.animation.poll_event()
+    }
+
+    fn state(&self) -> AnimationState {
+        self.animation.state()
+    }
+
+    fn restore_state(&mut self, state: &AnimationState) {
+        self.animation.restore_state(state);
+    }
+
+    fn set_layer_observer(&mut self, observer: Option<&'a mut dyn LayerObserver>) {
+        self.animation.set_layer_observer(observer);
+    }
+
+    #[cfg(feature = "instrumentation")]
+    fn set_cycle_counter(&mut self, counter: Option<&'a dyn CycleCounter>) {
+        self.animation.set_cycle_counter(counter);
+    }
+
+    #[cfg(feature = "instrumentation")]
+    fn take_layer_costs(&mut self) -> LayerCosts {
+        self.animation.take_layer_costs()
+    }
+}
@@ -0,0 +1,148 @@
+//! Beat syncing for music-reactive installations. A BeatClock keeps time with an external beat,
+//! either set directly with `set_bpm()` or tapped in with `tap_tempo()`, and works out the layer
+//! speeds that make each cycle of an animation last a whole number of beats, so that color
+//! advances and rotations land on the beat.
+//!
+//! The clock is driven by the same elapsed time as the LightingController:
+//!
+//! - call `update()` every frame, which returns true when a new beat starts,
+//! - call `sync_layer()` after changing the tempo or the layer's parameters, and
+//! - call `LightingController::restart_cycle()` on the first beat of each cycle, which can be
+//!   found with `is_downbeat()`, to pull the layer back into phase with the beat.
+
+use crate::animations::AnimationType;
+use crate::LightingController;
+use arrayvec::ArrayVec;
+use embedded_time::duration::Nanoseconds;
+use embedded_time::fixed_point::FixedPoint;
+
+/// The tempo a BeatClock starts at.
+pub const DEFAULT_BPM: u32 = 120;
+
+/// The number of taps averaged together to find the tempo.
+pub const MAX_TAPS: usize = 4;
+
+/// Taps further apart than this start a new tempo instead of adding to the last one.
+pub const TAP_TIMEOUT_NS: u64 = 2_000_000_000;
+
+const NS_PER_MINUTE: u64 = 60_000_000_000;
+const NS_PER_US: u64 = 1_000;
+
+pub struct BeatClock {
+    beat_ns: u64,
+    since_beat_ns: u64,
+    beat_count: u32,
+    since_tap_ns: Option<u64>,
+    tap_intervals: ArrayVec<u64, MAX_TAPS>,
+}
+
+impl BeatClock {
+    pub fn new(bpm: u32) -> Self {
+        BeatClock {
+            beat_ns: bpm_to_beat_ns(bpm),
+            since_beat_ns: 0,
+            beat_count: 0,
+            since_tap_ns: None,
+            tap_intervals: ArrayVec::new(),
+        }
+    }
+
+    /// Sets the tempo in beats per minute. A tempo of 0 is treated as 1 beat per minute.
+    pub fn set_bpm(&mut self, bpm: u32) {
+        self.beat_ns = bpm_to_beat_ns(bpm);
+        self.tap_intervals.clear();
+    }
+
+    /// Returns the tempo in beats per minute, rounded to the nearest beat.
+    pub fn bpm(&self) -> u32 {
+        ((NS_PER_MINUTE + self.beat_ns / 2) / self.beat_ns) as u32
+    }
+
+    /// Returns the length of a beat.
+    pub fn beat_ns(&self) -> u64 {
+        self.beat_ns
+    }
+
+    /// Returns the number of beats that have started since the clock was made.
+    pub fn beat_count(&self) -> u32 {
+        self.beat_count
+    }
+
+    /// Returns whether the current beat is the first of a cycle that is `beats_per_cycle` beats
+    /// long.
+    pub fn is_downbeat(&self, beats_per_cycle: u32) -> bool {
+        self.beat_count.is_multiple_of(beats_per_cycle.max(1))
+    }
+
+    /// Marks a beat, such as from a button pressed in time with the music. The tempo is set from
+    /// the average time between the last few taps, and the beat is moved to line up with the tap.
+    /// The time between taps is measured by `update()`, so it is only as precise as the frame
+    /// rate.
+    pub fn tap_tempo(&mut self) {
+        match self.since_tap_ns {
+            Some(interval) if interval > 0 && interval <= TAP_TIMEOUT_NS => {
+                if self.tap_intervals.is_full() {
+                    self.tap_intervals.remove(0);
+                }
+                self.tap_intervals.push(interval);
+                let total: u64 = self.tap_intervals.iter().sum();
+                self.beat_ns = total / self.tap_intervals.len() as u64;
+            }
+            _ => self.tap_intervals.clear(),
+        }
+        // a tap just after a beat has started lines up that beat, and one before it starts the
+        // next beat early:
+        if self.since_beat_ns >= self.beat_ns / 2 {
+            self.beat_count = self.beat_count.wrapping_add(1);
+        }
+        self.since_tap_ns = Some(0);
+        self.since_beat_ns = 0;
+    }
+
+    /// Moves the clock forward. Returns true when a new beat started during the elapsed time.
+    pub fn update(&mut self, elapsed: Nanoseconds<u64>) -> bool {
+        let elapsed = elapsed.integer();
+        if let Some(since_tap) = &mut self.since_tap_ns {
+            *since_tap = since_tap.saturating_add(elapsed);
+        }
+        self.since_beat_ns += elapsed;
+        let beats = self.since_beat_ns / self.beat_ns;
+        self.since_beat_ns %= self.beat_ns;
+        self.beat_count = self.beat_count.wrapping_add(beats as u32);
+        beats > 0
+    }
+
+    /// Returns the speed, as a numerator and a denominator, that makes a cycle that normally
+    /// takes `duration_ns` last `beats_per_cycle` beats instead.
+    pub fn speed_for(&self, duration_ns: u64, beats_per_cycle: u32) -> (u32, u32) {
+        let cycle_ns = self.beat_ns * beats_per_cycle.max(1) as u64;
+        // microseconds keep both halves of the fraction in range for cycles of over an hour:
+        let to_us = |ns: u64| (ns / NS_PER_US).clamp(1, u32::MAX as u64) as u32;
+        (to_us(duration_ns), to_us(cycle_ns))
+    }
+
+    /// Sets the speed of a layer of an animation so that each of its cycles, which normally take
+    /// `duration_ns`, lasts `beats_per_cycle` beats. `duration_ns` is the duration from the
+    /// layer's parameters.
+    pub fn sync_layer<const N_ANI: usize>(
+        &self,
+        controller: &mut LightingController<'_, N_ANI>,
+        animation_index: usize,
+        a_type: AnimationType,
+        duration_ns: u64,
+        beats_per_cycle: u32,
+    ) {
+        let (numerator, denominator) = self.speed_for(duration_ns, beats_per_cycle);
+        controller.set_speed(animation_index, a_type, numerator, denominator);
+    }
+}
+
+impl Default for BeatClock {
+    fn default() -> Self {
+        Self::new(DEFAULT_BPM)
+    }
+}
+
+fn bpm_to_beat_ns(bpm: u32) -> u64 {
+    NS_PER_MINUTE / bpm.max(1) as u64
+}
@@ -1,20 +1,80 @@
-use crate::colors::ManipulatableColor;
-use crate::{
-    animations::{Direction, MAX_OFFSET},
-    colors::Rainbow,
-};
+use crate::colors::{ColorOrder, ColorSpace, ManipulatableColor};
+use crate::easing::Easing;
+use crate::fixed::Q16;
+use crate::{animations::Direction, colors::Rainbow};
 use core::ops::Index;
+use core::sync::atomic::{AtomicU32, Ordering};
 use embedded_time::rate::*;
+#[cfg(feature = "color16")]
+use rgb::RGB16;
 use rgb::RGB8;
 
 pub fn convert_ns_to_frames(nanos: u64, frame_rate: Hertz) -> usize {
     (nanos * frame_rate.integer() as u64 / 1_000_000_000_u64) as usize
 }
 
+/// Converts a duration to frames in 256ths of a frame, for durations that need to keep the part of
+/// a frame that `convert_ns_to_frames()` rounds off.
+pub fn convert_ns_to_frames_x256(nanos: u64, frame_rate: Hertz) -> usize {
+    (nanos as u128 * frame_rate.integer() as u128 * 256 / 1_000_000_000) as usize
+}
+
+/// Returns whether a duration is longer than zero but too short to last a single frame at the
+/// frame rate, so it rounds down to no frames at all.
+pub fn rounds_to_no_frames(nanos: u64, frame_rate: Hertz) -> bool {
+    nanos > 0 && convert_ns_to_frames(nanos, frame_rate) == 0
+}
+
 pub fn convert_ms_to_frames(millis: u64, frame_rate: Hertz) -> usize {
     (millis * frame_rate.integer() as u64 / 1_000_u64) as usize
 }
 
+/// Converts a number of frames at one frame rate to the number of frames that take the same
+/// amount of time at another frame rate.
+pub fn rescale_frames(frames: usize, old_rate: Hertz, new_rate: Hertz) -> usize {
+    (frames as u64 * new_rate.integer() as u64 / old_rate.integer().max(1) as u64) as usize
+}
+
+/// How fast a layer of an animation runs compared to the durations in its parameters, as a
+/// fraction such as 3/2 to run one and a half times as fast. A numerator or denominator of 0 is
+/// treated as 1.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Speed {
+    numerator: u32,
+    denominator: u32,
+}
+
+impl Speed {
+    pub const NORMAL: Speed = Speed::new(1, 1);
+
+    pub const fn new(numerator: u32, denominator: u32) -> Self {
+        Speed {
+            numerator: if numerator == 0 { 1 } else { numerator },
+            denominator: if denominator == 0 { 1 } else { denominator },
+        }
+    }
+
+    /// Converts a number of frames at normal speed to the number of frames they take at this
+    /// speed.
+    pub fn scale_frames(&self, frames: usize) -> usize {
+        rescale_speed(frames, Speed::NORMAL, *self)
+    }
+}
+
+impl Default for Speed {
+    fn default() -> Self {
+        Speed::NORMAL
+    }
+}
+
+/// Converts a number of frames at one speed to the number of frames that cover the same part of
+/// an animation at another speed.
+pub fn rescale_speed(frames: usize, old_speed: Speed, new_speed: Speed) -> usize {
+    let numerator = frames as u128 * old_speed.numerator as u128 * new_speed.denominator as u128;
+    let denominator = old_speed.denominator as u128 * new_speed.numerator as u128;
+    (numerator / denominator) as usize
+}
+
 /// Returns a translation array beginning with index `start_at` and
 /// incrementing until reaching the desired `SIZE`
 pub fn default_translation_array<const SIZE: usize>(start_at: usize) -> [usize; SIZE] {
@@ -25,31 +85,151 @@ pub fn default_translation_array<const SIZE: usize>(start_at: usize) -> [usize;
     result
 }
 
-/// TODO: Implement an actual random function that's not hardware dependent here
+/// Mixes up the bits of a value so that consecutive values give very different results. This is
+/// useful for giving each LED a repeatable pseudo-random value based on its index.
+pub fn scramble(value: u32) -> u32 {
+    let mut x = value;
+    x ^= x >> 16;
+    x = x.wrapping_mul(0x7feb_352d);
+    x ^= x >> 15;
+    x = x.wrapping_mul(0x846c_a68b);
+    x ^= x >> 16;
+    x
+}
+
+/// The number of fractional bits in the coordinates passed to `noise()`. A coordinate change of
+/// `1 << NOISE_FRACTION_BITS` moves from one random lattice value to the next.
+pub const NOISE_FRACTION_BITS: u32 = 8;
+
+/// Returns smoothly varying 2D value noise from 0 to 255 at the fixed point coordinates `x` and
+/// `y`. Nearby coordinates give similar values, which makes it useful for organic looking color
+/// fields, using one axis for position and the other for time.
+pub fn noise(x: u32, y: u32) -> u8 {
+    let fraction_mask = (1 << NOISE_FRACTION_BITS) - 1;
+    let (cell_x, cell_y) = (x >> NOISE_FRACTION_BITS, y >> NOISE_FRACTION_BITS);
+    let fraction_x = smoothstep(x & fraction_mask);
+    let fraction_y = smoothstep(y & fraction_mask);
+
+    let lattice = |lx: u32, ly: u32| scramble(lx.wrapping_mul(0x9e37_79b9) ^ scramble(ly)) & 0xff;
+    let top = noise_lerp(
+        lattice(cell_x, cell_y),
+        lattice(cell_x.wrapping_add(1), cell_y),
+        fraction_x,
+    );
+    let bottom = noise_lerp(
+        lattice(cell_x, cell_y.wrapping_add(1)),
+        lattice(cell_x.wrapping_add(1), cell_y.wrapping_add(1)),
+        fraction_x,
+    );
+    noise_lerp(top, bottom, fraction_y) as u8
+}
+
+/// Eases a noise coordinate fraction so there are no sharp corners at the lattice points.
+fn smoothstep(fraction: u32) -> u32 {
+    let one = 1 << NOISE_FRACTION_BITS;
+    (fraction * fraction * (3 * one - 2 * fraction)) >> (2 * NOISE_FRACTION_BITS)
+}
+
+fn noise_lerp(start: u32, end: u32, fraction: u32) -> u32 {
+    let one = 1 << NOISE_FRACTION_BITS;
+    (start * (one - fraction) + end * fraction) >> NOISE_FRACTION_BITS
+}
+
+/// The state of the global random number generator.
+static RANDOM_STATE: AtomicU32 = AtomicU32::new(0x2545_f491);
+
+/// Seeds the global random number generator. Use a source of entropy from the hardware, such as
+/// ADC noise or a hardware RNG, so that random effects differ between power cycles.
+pub fn seed_random(seed: u32) {
+    // xorshift gets stuck at 0, so avoid seeding with it:
+    RANDOM_STATE.store(seed.max(1), Ordering::Relaxed);
+}
+
+/// Returns a pseudo-random number from a xorshift generator. This is only meant for visual
+/// effects, and is not suitable for anything that needs real randomness.
+pub fn get_random() -> u32 {
+    // Only loads and stores are used so this works on targets without atomic compare and swap:
+    let x = xorshift(RANDOM_STATE.load(Ordering::Relaxed));
+    RANDOM_STATE.store(x, Ordering::Relaxed);
+    x
+}
+
 pub fn get_random_offset() -> u16 {
-    12
+    (get_random() >> 16) as u16
+}
+
+fn xorshift(mut x: u32) -> u32 {
+    x ^= x << 13;
+    x ^= x >> 17;
+    x ^= x << 5;
+    x
+}
+
+/// A random number generator with its own state, so that the random effects of a layer are
+/// independent of other animations and repeat the same way every time from the same seed. A seed
+/// of 0 uses the global generator instead, which is seeded with `seed_random()`.
+#[derive(Copy, Clone, Debug)]
+pub struct Random {
+    seed: u32,
+    state: u32,
+}
+
+impl Random {
+    pub const fn new(seed: u32) -> Self {
+        Random { seed, state: seed }
+    }
+
+    pub fn seed(&self) -> u32 {
+        self.seed
+    }
+
+    /// Starts over from a new seed, unless the generator already has that seed.
+    pub fn set_seed(&mut self, seed: u32) {
+        if self.seed != seed {
+            *self = Random::new(seed);
+        }
+    }
+
+    /// Returns the next pseudo-random number, like `get_random()`.
+    pub fn next_u32(&mut self) -> u32 {
+        if self.seed == 0 {
+            return get_random();
+        }
+        self.state = xorshift(self.state);
+        self.state
+    }
+
+    pub fn next_offset(&mut self) -> u16 {
+        (self.next_u32() >> 16) as u16
+    }
 }
 
+/// Moves an offset around by the fraction of a full turn that the frames have progressed through,
+/// rounded to the nearest step of the offset.
 pub fn shift_offset(starting_offset: u16, frames: Progression, direction: Direction) -> u16 {
     if frames.total == 0 {
         return starting_offset;
     }
-    let max_offset = MAX_OFFSET as usize;
-    let starting_offset = starting_offset as usize;
+    let progress = Q16::from_progression(frames);
     let offset_shift = match direction {
-        Direction::Positive => max_offset * frames.get_current() / frames.total,
-        Direction::Negative => max_offset * (frames.total - frames.get_current()) / frames.total,
+        Direction::Positive => progress.to_offset(),
+        Direction::Negative => progress.complement().to_offset(),
         Direction::Stopped => 0,
     };
-    (starting_offset + offset_shift) as u16
+    starting_offset.wrapping_add(offset_shift)
 }
 
+#[derive(Clone)]
 pub struct ReversibleRainbow<'a> {
     backer: Rainbow<'a>,
     is_forward: bool,
 }
 
 impl<'a> ReversibleRainbow<'a> {
+    pub fn is_forward(&self) -> bool {
+        self.is_forward
+    }
+
     pub fn len(&self) -> usize {
         self.backer.len()
     }
@@ -74,6 +254,10 @@ pub trait FadeRainbow {
     fn rainbow(&self) -> &StatefulRainbow;
     fn frames(&self) -> &Progression;
 
+    fn easing(&self) -> Easing {
+        Easing::Linear
+    }
+
     fn calculate_fade_color(&self) -> RGB8 {
         let (rainbow, frames) = (self.rainbow(), self.frames());
 
@@ -82,7 +266,23 @@ pub trait FadeRainbow {
             return current_color;
         }
         let next_color = rainbow.peek_next_color();
-        current_color.lerp_with(next_color, *frames)
+        let factor = self.easing().ease(*frames);
+        current_color.lerp_in_space_with(next_color, factor, rainbow.color_space)
+    }
+
+    /// The same as `calculate_fade_color()` with 16 bits per channel. Fades through the HSV color
+    /// space are worked out in 8 bits and widened afterwards.
+    #[cfg(feature = "color16")]
+    fn calculate_fade_color16(&self) -> RGB16 {
+        let (rainbow, frames) = (self.rainbow(), self.frames());
+        if frames.total == 0 || rainbow.color_space != ColorSpace::Rgb {
+            return crate::output::expand(self.calculate_fade_color());
+        }
+        let current_color = crate::output::expand(rainbow.current_color());
+        let next_color = crate::output::expand(rainbow.peek_next_color());
+        let factor = self.easing().ease(*frames);
+        let (current, total) = (factor.get_current() as i32, factor.total as i32);
+        crate::colors::color_lerp16(current, 0, total, current_color, next_color)
     }
 
     fn current_fade_color(&self) -> RGB8 {
@@ -114,6 +314,7 @@ pub struct TimedRainbows<'a, 'b> {
     pub fade_rainbow: &'b mut StatefulRainbow<'a>,
     pub incremental_rainbow: &'b mut StatefulRainbow<'a>,
     pub frames: &'b mut Progression,
+    pub random: &'b mut Random,
 }
 
 impl<'a, 'b> FadeRainbow for TimedRainbows<'a, 'b> {
@@ -143,9 +344,15 @@ impl<'a, 'b> MarchingRainbowMut<'a> for TimedRainbows<'a, 'b> {
     }
 }
 
+#[derive(Clone)]
 pub struct StatefulRainbow<'a> {
     pub backer: ReversibleRainbow<'a>,
     pub position: Progression,
+    pub color_space: ColorSpace,
+    order: ColorOrder<'a>,
+    // the random orders pick the next color ahead of time, so fades know where they are going:
+    next: usize,
+    random: Random,
 }
 
 impl<'a> StatefulRainbow<'a> {
@@ -155,7 +362,27 @@ impl<'a> StatefulRainbow<'a> {
             backer: rainbow,
             is_forward,
         };
-        Self { backer, position }
+        Self {
+            backer,
+            next: position.peek_next(),
+            position,
+            color_space: ColorSpace::Rgb,
+            order: ColorOrder::Sequential,
+            random: Random::new(0),
+        }
+    }
+
+    /// Seeds the random orders of the rainbow, see `Random`.
+    pub fn set_seed(&mut self, seed: u32) {
+        self.random.set_seed(seed);
+    }
+
+    /// Changes how the color after the current one is picked.
+    pub fn set_order(&mut self, order: ColorOrder<'a>) {
+        if self.order != order {
+            self.order = order;
+            self.next = self.pick_next();
+        }
     }
 
     pub fn current_color(&self) -> RGB8 {
@@ -164,14 +391,19 @@ impl<'a> StatefulRainbow<'a> {
 
     pub fn decrement(&mut self) {
         self.position.decrement();
+        self.next = self.pick_next();
     }
 
     pub fn increment(&mut self) {
-        self.position.increment();
+        match self.order {
+            ColorOrder::Sequential => self.position.increment(),
+            _ => self.position.set_current(self.next),
+        }
+        self.next = self.pick_next();
     }
 
     pub fn peek_next_color(&self) -> RGB8 {
-        self.backer[self.position.peek_next() as usize]
+        self.backer[self.next]
     }
 
     pub fn peek_last_color(&self) -> RGB8 {
@@ -180,14 +412,88 @@ impl<'a> StatefulRainbow<'a> {
 
     pub fn reset(&mut self) {
         self.position.reset();
+        self.next = self.pick_next();
+    }
+
+    /// Jumps to a color of the rainbow by its index, wrapped to the length of the rainbow.
+    pub fn set_position(&mut self, index: usize) {
+        self.position.set_current(index);
+        self.next = self.pick_next();
+    }
+
+    /// Swaps in a new rainbow while keeping the current position, wrapped to the new length. A
+    /// randomly picked next color is kept if it is still in the rainbow.
+    pub fn set_rainbow(&mut self, rainbow: &'a [RGB8], is_forward: bool) {
+        self.backer = ReversibleRainbow {
+            backer: rainbow,
+            is_forward,
+        };
+        self.position.set_total(rainbow.len());
+        let is_next_valid = self.next < rainbow.len() && self.next != self.position.get_current();
+        if matches!(self.order, ColorOrder::Sequential) || !is_next_valid {
+            self.next = self.pick_next();
+        }
+    }
+
+    /// Picks the index of the color after the current one, following the rainbow's order.
+    fn pick_next(&mut self) -> usize {
+        let (current, color_count) = (self.position.get_current(), self.backer.len());
+        if color_count < 2 {
+            return self.position.peek_next();
+        }
+        match self.order {
+            ColorOrder::Sequential => self.position.peek_next(),
+            ColorOrder::Random => {
+                // pick from the other colors by skipping over the current one:
+                let pick = self.random.next_u32() as usize % (color_count - 1);
+                match pick >= current {
+                    true => pick + 1,
+                    false => pick,
+                }
+            }
+            ColorOrder::Weighted(weights) => {
+                let weight = |index: usize| {
+                    let rainbow_index = match self.backer.is_forward {
+                        true => index,
+                        false => color_count - 1 - index,
+                    };
+                    match index == current {
+                        true => 0,
+                        false => weights.get(rainbow_index).copied().unwrap_or(0) as u32,
+                    }
+                };
+                let total: u32 = (0..color_count).map(weight).sum();
+                if total == 0 {
+                    return self.position.peek_next();
+                }
+                let mut pick = self.random.next_u32() % total;
+                for index in 0..color_count {
+                    if pick < weight(index) {
+                        return index;
+                    }
+                    pick -= weight(index);
+                }
+                self.position.peek_next()
+            }
+        }
     }
 }
 
+/// Passes shorter than this many frames are split into this many positions by
+/// `Progression::set_length_x256()`, so that they still move when a pass takes less than a frame.
+pub const SUBFRAME_POSITIONS: usize = 16;
+
 #[derive(Default, Debug, Copy, Clone)]
 pub struct Progression {
     current: usize,
     pub total: usize,
     pub is_forward: bool,
+    /// The length of a pass in 256ths of a frame when it is set with `set_length_x256()`, or 0
+    /// when each increment moves by one position.
+    length_x256: usize,
+    /// The progress towards the next position carried over between increments, out of
+    /// `length_x256`.
+    carry: usize,
 }
 
 impl Progression {
@@ -196,9 +502,103 @@ impl Progression {
             current: 0,
             total,
             is_forward: true,
+            length_x256: 0,
+            carry: 0,
+        }
+    }
+
+    /// Makes a progression that takes `length_x256` 256ths of a frame per pass, see
+    /// `set_length_x256()`.
+    pub fn from_length_x256(length_x256: usize) -> Self {
+        let mut progression = Self::new(0);
+        progression.set_length_x256(length_x256);
+        progression
+    }
+
+    /// Sets how long a pass takes in 256ths of a frame, keeping the current value at the same
+    /// fraction of the way through. Each increment moves by the part of a pass that a frame
+    /// takes, and the part of a position left over is carried over to the next increment, so a
+    /// pass that isn't a whole number of frames keeps its length instead of being rounded off.
+    /// Passes shorter than two frames are split into SUBFRAME_POSITIONS positions, several of
+    /// which are skipped every frame, and a length of 0 stands still.
+    pub fn set_length_x256(&mut self, length_x256: usize) {
+        let total = match length_x256 / 256 {
+            _ if length_x256 == 0 => 0,
+            0 | 1 => SUBFRAME_POSITIONS,
+            frames => frames,
+        };
+        self.rescale(total);
+        self.length_x256 = length_x256;
+        self.carry = 0;
+    }
+
+    /// Returns the number of whole frames a pass takes, which can be fewer than the positions of
+    /// a progression set with `set_length_x256()`.
+    pub fn frames_per_pass(&self) -> usize {
+        match self.length_x256 {
+            0 => self.total,
+            length => length / 256,
         }
     }
 
+    /// Changes the total while keeping the current value, wrapped to fit the new total. Each
+    /// increment moves by one position afterwards, even if a length was set before.
+    pub fn set_total(&mut self, total: usize) {
+        self.length_x256 = 0;
+        self.carry = 0;
+        self.total = total;
+        if self.is_mono() {
+            self.current = 0;
+            return;
+        }
+        self.current %= total;
+    }
+
+    /// Changes the total while keeping the current value at the same fraction of the way through.
+    pub fn rescale(&mut self, total: usize) {
+        if self.is_mono() {
+            self.set_total(total);
+            return;
+        }
+        self.current = self.current * total / self.total;
+        self.total = total;
+    }
+
+    /// Rescales the progression so that it takes the same amount of time at a new frame rate.
+    pub fn rescale_frame_rate(&mut self, old_rate: Hertz, new_rate: Hertz) {
+        match self.length_x256 {
+            0 => self.rescale(rescale_frames(self.total, old_rate, new_rate)),
+            // a pass that moved is kept moving, however short it gets:
+            _ => self.set_length_x256(rescale_frames(self.length_x256, old_rate, new_rate).max(1)),
+        }
+    }
+
+    /// Rescales the progression so that it runs at a new speed.
+    pub fn rescale_speed(&mut self, old_speed: Speed, new_speed: Speed) {
+        match self.length_x256 {
+            // a large speed up can round a progression down to no frames, so one that had frames
+            // keeps at least one:
+            0 => {
+                let total = rescale_speed(self.total, old_speed, new_speed);
+                self.rescale(total.max(self.total.min(1)));
+            }
+            _ => self.set_length_x256(rescale_speed(self.length_x256, old_speed, new_speed).max(1)),
+        }
+    }
+
+    /// Moves the progression as far from its end as it was from its start, so that something
+    /// driven by it can turn around from the same place.
+    pub fn mirror(&mut self) {
+        if self.is_mono() {
+            return;
+        }
+        let mirrored = (self.total - self.get_current()) % self.total;
+        self.current = match self.is_forward {
+            true => mirrored,
+            false => self.total - 1 - mirrored,
+        };
+    }
+
     pub fn reverse_direction(&mut self) {
         self.is_forward = !self.is_forward;
     }
@@ -211,6 +611,21 @@ impl Progression {
         self.current == 0
     }
 
+    /// Returns whether the next increment finishes a pass through the progression. Every frame
+    /// of a progression with a single frame is its last.
+    pub fn is_last_frame(&self) -> bool {
+        self.current + self.next_step().0 >= self.total
+    }
+
+    /// Returns how many positions the next increment moves by, and the carry it leaves behind.
+    fn next_step(&self) -> (usize, usize) {
+        if self.length_x256 == 0 {
+            return (1, 0);
+        }
+        let progress = self.carry + self.total * 256;
+        (progress / self.length_x256, progress % self.length_x256)
+    }
+
     pub fn get_current(&self) -> usize {
         if self.is_mono() {
             return 0;
@@ -248,15 +663,19 @@ impl Progression {
         if self.is_mono() {
             return;
         }
-        self.current = self.peek_next();
+        let (steps, carry) = self.next_step();
+        self.current = (self.current + steps) % self.total;
+        self.carry = carry;
     }
 
+    /// Increments the progression and returns whether it finished a pass.
     pub fn checked_increment(&mut self) -> bool {
         if self.is_mono() {
             return false;
         }
+        let is_last_frame = self.is_last_frame();
         self.increment();
-        self.current == 0
+        is_last_frame
     }
 
     pub fn peek_next(&self) -> usize {
@@ -282,6 +701,7 @@ impl Progression {
     }
 
     pub fn reset(&mut self) {
-        self.current = 0
+        self.current = 0;
+        self.carry = 0;
     }
 }
@@ -0,0 +1,217 @@
+//! Compatibility with a subset of WLED's JSON API, so devices built on this crate can be controlled
+//! from existing WLED apps. A WledState follows the `/json/state` updates sent by an app, and
+//! translates the effect, speed, intensity and palette of the first segment into
+//! AnimationParameters. Parsing the JSON needs an allocator.
+//!
+//! Only the effects and palettes that have a close match in this crate are translated. Others
+//! keep the modes and rainbows of the base parameters they are applied to.
+
+use crate::animations::{background, foreground, AnimationParameters};
+use crate::colors::{self, Rainbow, RainbowBuf};
+use crate::easing::Waveform;
+use alloc::vec::Vec;
+use rgb::RGB8;
+use smart_leds::colors::BLACK;
+
+/// The number of colors in a WLED segment: the primary, secondary and tertiary colors.
+pub const SEGMENT_COLORS: usize = 3;
+
+/// The speed and intensity that leave the base parameters unchanged.
+pub const DEFAULT_SPEED: u8 = 128;
+pub const DEFAULT_INTENSITY: u8 = 128;
+
+// WLED effect IDs:
+const FX_SOLID: u8 = 0;
+const FX_BLINK: u8 = 1;
+const FX_BREATHE: u8 = 2;
+const FX_COLORLOOP: u8 = 8;
+const FX_RAINBOW: u8 = 9;
+const FX_SCAN: u8 = 10;
+const FX_THEATER_CHASE: u8 = 13;
+const FX_SCANNER: u8 = 40;
+
+// WLED palette IDs:
+const PAL_DEFAULT: u8 = 0;
+const PAL_COLOR_1: u8 = 2;
+const PAL_COLORS_1_AND_2: u8 = 3;
+const PAL_COLOR_GRADIENT: u8 = 4;
+const PAL_COLORS_ONLY: u8 = 5;
+const PAL_RAINBOW: u8 = 11;
+const PAL_RAINBOW_BANDS: u8 = 12;
+const PAL_SUNSET: u8 = 13;
+
+/// The parts of a `/json/state` update that are understood. Every field is optional, as apps
+/// only send the values that changed.
+#[derive(serde::Deserialize)]
+struct StateUpdate {
+    on: Option<bool>,
+    bri: Option<u8>,
+    #[serde(default)]
+    seg: Vec<SegmentUpdate>,
+}
+
+#[derive(serde::Deserialize)]
+struct SegmentUpdate {
+    fx: Option<u8>,
+    sx: Option<u8>,
+    ix: Option<u8>,
+    pal: Option<u8>,
+    /// Colors are sent as [r, g, b] or [r, g, b, w] arrays, and any white channel is ignored.
+    col: Option<Vec<Vec<u8>>>,
+}
+
+/// The state of a WLED device, as set by the updates it has received.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WledState {
+    pub on: bool,
+    pub brightness: u8,
+    pub effect: u8,
+    pub speed: u8,
+    pub intensity: u8,
+    pub palette: u8,
+    pub colors: RainbowBuf<SEGMENT_COLORS>,
+}
+
+impl WledState {
+    /// The state of a WLED device after it first starts.
+    pub fn new() -> Self {
+        WledState {
+            on: true,
+            brightness: 128,
+            effect: FX_SOLID,
+            speed: DEFAULT_SPEED,
+            intensity: DEFAULT_INTENSITY,
+            palette: PAL_DEFAULT,
+            colors: [RGB8::new(255, 160, 0), BLACK, BLACK].into_iter().collect(),
+        }
+    }
+
+    /// Applies a `/json/state` update, changing only the values it includes. Nothing is changed
+    /// if the JSON can't be parsed.
+    pub fn apply_json(&mut self, json: &[u8]) -> Result<(), serde_json::Error> {
+        let update: StateUpdate = serde_json::from_slice(json)?;
+        if let Some(on) = update.on {
+            self.on = on;
+        }
+        if let Some(brightness) = update.bri {
+            self.brightness = brightness;
+        }
+
+        let Some(segment) = update.seg.into_iter().next() else {
+            return Ok(());
+        };
+        if let Some(effect) = segment.fx {
+            self.effect = effect;
+        }
+        if let Some(speed) = segment.sx {
+            self.speed = speed;
+        }
+        if let Some(intensity) = segment.ix {
+            self.intensity = intensity;
+        }
+        if let Some(palette) = segment.pal {
+            self.palette = palette;
+        }
+        for (color, channels) in self.colors.iter_mut().zip(segment.col.unwrap_or_default()) {
+            if let [r, g, b, ..] = channels[..] {
+                *color = RGB8::new(r, g, b);
+            }
+        }
+        Ok(())
+    }
+
+    /// The brightness to pass to `LightingController::set_brightness()`, which is 0 when the
+    /// device is turned off.
+    pub fn output_brightness(&self) -> u8 {
+        match self.on {
+            true => self.brightness,
+            false => 0,
+        }
+    }
+
+    /// Translates the state into AnimationParameters, starting from `base`. The speed scales the
+    /// durations of the base parameters, from twice as long at 0 to almost instant at 255.
+    pub fn to_parameters<'a>(&'a self, base: &AnimationParameters<'a>) -> AnimationParameters<'a> {
+        let mut parameters = *base;
+        let bg = &mut parameters.bg;
+        let fg = &mut parameters.fg;
+
+        match self.effect {
+            FX_SOLID => {
+                bg.mode = background::Mode::Solid;
+                fg.mode = foreground::Mode::NoForeground;
+            }
+            FX_BLINK | FX_BREATHE => {
+                bg.mode = background::Mode::Breathe;
+                bg.waveform = match self.effect {
+                    FX_BLINK => Waveform::Square,
+                    _ => Waveform::Sine,
+                };
+                bg.min_brightness = u8::MAX - self.intensity;
+                bg.max_brightness = colors::FULL_BRIGHTNESS;
+                fg.mode = foreground::Mode::NoForeground;
+            }
+            FX_COLORLOOP => {
+                bg.mode = background::Mode::SolidFade;
+                fg.mode = foreground::Mode::NoForeground;
+            }
+            FX_RAINBOW => {
+                bg.mode = background::Mode::FillRainbowRotate;
+                bg.subdivisions = 1 + self.intensity as usize / 32;
+                fg.mode = foreground::Mode::NoForeground;
+            }
+            FX_SCAN | FX_SCANNER => {
+                bg.mode = background::Mode::NoBackground;
+                fg.mode = foreground::Mode::Scanner;
+                fg.trail_length = match self.effect {
+                    FX_SCANNER => self.intensity as usize / 16,
+                    _ => 0,
+                };
+            }
+            FX_THEATER_CHASE => {
+                bg.mode = background::Mode::NoBackground;
+                fg.mode = foreground::Mode::TheaterChase;
+                fg.spacing = 2 + self.intensity as usize / 64;
+            }
+            _ => {}
+        }
+
+        let scale = |time_ns: u64| time_ns * (256 - self.speed as u64) / 128;
+        bg.duration_ns = scale(bg.duration_ns);
+        fg.duration_ns = scale(fg.duration_ns);
+        fg.step_time_ns = scale(fg.step_time_ns);
+
+        if let Some(rainbow) = self.palette_rainbow() {
+            bg.rainbow = rainbow;
+            fg.rainbow = rainbow;
+        }
+        parameters
+    }
+
+    /// The rainbow for the selected palette, or None to keep the rainbow of the base parameters.
+    fn palette_rainbow(&self) -> Option<Rainbow<'_>> {
+        let rainbow = match self.palette {
+            // WLED's default palette for the single color effects is the primary color:
+            PAL_DEFAULT => match self.effect {
+                FX_SOLID | FX_BLINK | FX_BREATHE | FX_SCAN | FX_SCANNER | FX_THEATER_CHASE => {
+                    self.colors.get(..1)?
+                }
+                _ => return None,
+            },
+            PAL_COLOR_1 => self.colors.get(..1)?,
+            PAL_COLORS_1_AND_2 => self.colors.get(..2)?,
+            PAL_COLOR_GRADIENT | PAL_COLORS_ONLY => &self.colors,
+            PAL_RAINBOW => colors::R_HUE_WHEEL,
+            PAL_RAINBOW_BANDS => colors::R_RGB,
+            PAL_SUNSET => colors::R_SUNSET,
+            _ => return None,
+        };
+        Some(rainbow)
+    }
+}
+
+impl Default for WledState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,289 @@
+//! Golden tests of every background, foreground and trigger mode. Each test records a short run
+//! of one mode with a `snapshot::Recorder` and compares a hash of all of its frames against the
+//! one saved in `tests/snapshots/` from a known good version, so any change to what a
+//! mode draws fails its test. The Custom modes draw effects from outside of the crate, so they
+//! have no tests here.
+//!
+//! After a change that is meant to alter the output of a mode, run the tests with
+//! `UPDATE_SNAPSHOTS=1` to save the new hashes, and check the differences in the file.
+
+use embedded_time::rate::Extensions;
+use lighting_controller::animations::trigger::RippleOrigin;
+use lighting_controller::animations::*;
+use lighting_controller::colors::BlendMode;
+use lighting_controller::default_animations::{ANI_ALL_OFF, BG_TEST, FG_TEST, TRIGGER_TEST};
+use lighting_controller::easing::Easing;
+use lighting_controller::mapping::{Matrix, Wiring};
+use lighting_controller::snapshot::Recorder;
+use lighting_controller::utility::seed_random;
+use rgb::RGB8;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+const N_LED: usize = 64;
+const FRAMES: usize = 90;
+
+/// The random number generator is shared by the whole crate, so only one mode is recorded at a
+/// time.
+static RECORDING: Mutex<()> = Mutex::new(());
+
+const BG_BASE: background::Parameters = background::Parameters {
+    duration_ns: 1_000_000_000,
+    spawn_interval_ns: 100_000_000,
+    min_brightness: 32,
+    ..BG_TEST
+};
+
+const FG_BASE: foreground::Parameters = foreground::Parameters {
+    duration_ns: 1_000_000_000,
+    step_time_ns: 100_000_000,
+    trail_length: 3,
+    text: "HI",
+    ..FG_TEST
+};
+
+const TRIGGER_BASE: trigger::Parameters = trigger::Parameters {
+    mode: trigger::Mode::NoTrigger,
+    direction: Direction::Positive,
+    easing: Easing::Linear,
+    fade_in_time_ns: 200_000_000,
+    fade_out_time_ns: 300_000_000,
+    decay_time_ns: 100_000_000,
+    sustain_level: 128,
+    sustain_time_ns: 200_000_000,
+    starting_offset: 0,
+    pixels_per_pixel_group: 2,
+    priority: 0,
+    density: 60,
+    strobe_count: 4,
+    strobe_period_ns: 150_000_000,
+    strobe_duty_cycle: 128,
+    leaves_color_behind: false,
+    ripple_origin: RippleOrigin::Led(N_LED / 2),
+    ripple_speed: 40,
+    target: None,
+    blend_mode: BlendMode::Normal,
+    rainbow: None,
+};
+
+/// The animation that the trigger modes are drawn over, so that the Background and Foreground
+/// modes have layers to change and the triggers have colors to blend with.
+const ANI_TRIGGER_BASE: AnimationParameters = AnimationParameters {
+    bg: background::Parameters {
+        mode: background::Mode::Solid,
+        ..BG_BASE
+    },
+    fg: foreground::Parameters {
+        mode: foreground::Mode::MarqueeSolid,
+        ..FG_BASE
+    },
+    trigger: trigger::GlobalParameters {
+        duration_ns: 1_000_000_000,
+        ..TRIGGER_TEST
+    },
+};
+
+/// The fading modes round their colors differently when they are worked out in 16 bits, so the
+/// `color16` feature has its own hashes.
+fn golden_file() -> PathBuf {
+    let file = match cfg!(feature = "color16") {
+        true => "tests/snapshots/modes_color16.txt",
+        false => "tests/snapshots/modes.txt",
+    };
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join(file)
+}
+
+/// Records a mode, calling `before_frame` before each frame, and checks the hash of the run
+/// against the golden file.
+fn check_mode(
+    name: &str,
+    parameters: AnimationParameters<'static>,
+    matrix: Option<Matrix>,
+    before_frame: impl FnMut(usize, &mut Animation<'static, N_LED>),
+) {
+    let _recording = RECORDING
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    seed_random(lighting_controller::snapshot::DEFAULT_SEED);
+    let mut animation = Animation::<N_LED>::new(parameters, 60.Hz());
+    if let Some(matrix) = matrix {
+        animation = animation.set_matrix(matrix);
+    }
+    let mut hashes = [0; FRAMES];
+    Recorder::new().record_with(&mut animation, &mut hashes, before_frame);
+    let hash = hashes.iter().fold(0x811c_9dc5_u32, |hash, &frame| {
+        (hash ^ frame).wrapping_mul(0x0100_0193)
+    });
+
+    let golden = std::fs::read_to_string(golden_file()).unwrap_or_default();
+    let saved = golden.lines().find_map(|line| {
+        let (mode, hash) = line.split_once(' ')?;
+        (mode == name).then(|| u32::from_str_radix(hash, 16).ok())?
+    });
+    if saved == Some(hash) {
+        return;
+    }
+    if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+        let mut lines: Vec<String> = golden
+            .lines()
+            .filter(|line| line.split(' ').next() != Some(name))
+            .map(String::from)
+            .collect();
+        lines.push(format!("{name} {hash:08x}"));
+        lines.sort();
+        std::fs::write(golden_file(), lines.join("\n") + "\n").unwrap();
+        return;
+    }
+    panic!("{name} drew {hash:08x} instead of {saved:08x?}, see tests/snapshots.rs to update it");
+}
+
+fn check_background(name: &str, mode: background::Mode<'static>) {
+    let parameters = AnimationParameters {
+        bg: background::Parameters { mode, ..BG_BASE },
+        ..ANI_ALL_OFF
+    };
+    check_mode(name, parameters, None, |_, _| {});
+}
+
+fn check_foreground(name: &str, fg: foreground::Parameters<'static>) {
+    // over a background, so that the modes which black out LEDs show up:
+    let parameters = AnimationParameters {
+        bg: background::Parameters {
+            mode: background::Mode::FillRainbow,
+            ..BG_BASE
+        },
+        fg,
+        ..ANI_ALL_OFF
+    };
+    // the text needs a matrix to be drawn on, and the other modes are drawn along the strip:
+    let matrix = matches!(fg.mode, foreground::Mode::ScrollingText)
+        .then(|| Matrix::new(8, N_LED / 8, Wiring::Serpentine));
+    let follows_offset = matches!(
+        fg.mode,
+        foreground::Mode::VUMeter | foreground::Mode::ScannerFill
+    );
+    // a level that rises and falls, for the modes that show the input level, progress or offset:
+    check_mode(name, parameters, matrix, |frame, animation| {
+        let level = (frame * 255 / (FRAMES / 2)).min(510 - frame * 255 / (FRAMES / 2));
+        let level = level.min(255) as u32;
+        let progress = (level * MAX_OFFSET as u32 / 255) as u16;
+        animation.set_input_level(level as u8);
+        animation.set_progress(progress);
+        if follows_offset {
+            animation.set_offset(AnimationType::Foreground, progress);
+        }
+    });
+}
+
+fn check_trigger(name: &str, mode: trigger::Mode) {
+    let params = trigger::Parameters {
+        mode,
+        ..TRIGGER_BASE
+    };
+    check_mode(name, ANI_TRIGGER_BASE, None, |frame, animation| {
+        if frame == 0 || frame == FRAMES / 2 {
+            animation.trigger(&params, 60.Hz());
+        }
+    });
+}
+
+macro_rules! background_tests {
+    ($($test:ident: $mode:ident,)*) => {
+        $(
+            #[test]
+            fn $test() {
+                check_background(
+                    concat!("background::", stringify!($mode)),
+                    background::Mode::$mode,
+                );
+            }
+        )*
+    };
+}
+
+macro_rules! foreground_tests {
+    ($($test:ident: $mode:ident,)*) => {
+        $(
+            #[test]
+            fn $test() {
+                check_foreground(
+                    concat!("foreground::", stringify!($mode)),
+                    foreground::Parameters {
+                        mode: foreground::Mode::$mode,
+                        ..FG_BASE
+                    },
+                );
+            }
+        )*
+    };
+}
+
+macro_rules! trigger_tests {
+    ($($test:ident: $mode:ident $(($color:expr))?,)*) => {
+        $(
+            #[test]
+            fn $test() {
+                check_trigger(
+                    concat!("trigger::", stringify!($mode)),
+                    trigger::Mode::$mode $(($color))?,
+                );
+            }
+        )*
+    };
+}
+
+background_tests! {
+    bg_no_background: NoBackground,
+    bg_solid: Solid,
+    bg_solid_fade: SolidFade,
+    bg_breathe: Breathe,
+    bg_fill_rainbow: FillRainbow,
+    bg_fill_rainbow_rotate: FillRainbowRotate,
+    bg_gradient: Gradient,
+    bg_noise: Noise,
+    bg_rain: Rain,
+}
+
+foreground_tests! {
+    fg_no_foreground: NoForeground,
+    fg_marquee_solid: MarqueeSolid,
+    fg_marquee_solid_fixed: MarqueeSolidFixed,
+    fg_marquee_fade: MarqueeFade,
+    fg_marquee_fade_fixed: MarqueeFadeFixed,
+    fg_vu_meter: VUMeter,
+    fg_level_meter: LevelMeter,
+    fg_scrolling_text: ScrollingText,
+    fg_scanner: Scanner,
+    fg_theater_chase: TheaterChase,
+    fg_progress_bar: ProgressBar,
+    fg_scanner_fill: ScannerFill,
+}
+
+#[test]
+fn fg_status_bar() {
+    check_foreground(
+        "foreground::StatusBar",
+        lighting_controller::default_animations::FG_STATUS_BAR,
+    );
+}
+
+trigger_tests! {
+    trigger_no_trigger: NoTrigger,
+    trigger_background: Background,
+    trigger_foreground: Foreground,
+    trigger_color_pulse: ColorPulse,
+    trigger_color_pulse_fade: ColorPulseFade,
+    trigger_color_pulse_rainbow: ColorPulseRainbow,
+    trigger_color_shot: ColorShot,
+    trigger_color_shot_fade: ColorShotFade,
+    trigger_color_shot_rainbow: ColorShotRainbow,
+    trigger_comet: Comet,
+    trigger_flash: Flash,
+    trigger_flash_fade: FlashFade,
+    trigger_flash_rainbow: FlashRainbow,
+    trigger_sparkle: Sparkle,
+    trigger_strobe: Strobe(RGB8::new(255, 255, 255)),
+    trigger_wipe: Wipe(RGB8::new(0, 0, 255)),
+    trigger_wipe_center_out: WipeCenterOut(RGB8::new(0, 255, 0)),
+    trigger_ripple: Ripple,
+}